@@ -0,0 +1,431 @@
+use crate::{
+    animations::AnimationId,
+    components::{
+        entity_builder::entity_builder,
+        game::{Health, HitStop, Invincibility, Team, TimedExistence},
+        graphics::{AnimationCounter, AnimationStateMachine},
+        physics::{Ducking, GravityDirection, Grounded, Orientation, Position, Velocity},
+    },
+    events::Events,
+    states::{
+        loading::AssetsDir,
+        pizzatopia::{TILE_HEIGHT, TILE_WIDTH},
+    },
+    systems::physics::{gravitationally_adapted_velocity, gravitationally_de_adapted_velocity},
+    ui::UiStack,
+};
+use amethyst::{
+    core::{shrev::EventChannel, timing::Time},
+    derive::SystemDesc,
+    ecs::{
+        prelude::{Component, DenseVecStorage},
+        Entities, Join, LazyUpdate, Read, ReadStorage, System, SystemData, World, WorldExt, Write,
+        WriteStorage,
+    },
+    input::StringBindings,
+};
+use bami::Input;
+use derivative::Derivative;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    sync::Arc,
+};
+use ultraviolet::Vec2;
+
+pub const ABILITIES: &str = "abilities/abilities.ron";
+
+// Offsets point the way the user is facing and are flipped along with them
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AbilityKind {
+    // A hitbox in front of the user, `delay` seconds after starting
+    Melee {
+        offset: Vec2,
+        size: Vec2,
+        delay: f32,
+    },
+    // A projectile from the projectile library
+    Throw {
+        projectile: String,
+        velocity: Vec2,
+    },
+    // A shield that blocks and reflects for `duration` seconds, hitting whatever it's pushed into
+    ShieldBash {
+        offset: Vec2,
+        size: Vec2,
+        lunge: f32,
+        duration: f32,
+    },
+    // A burst of speed, untouchable while it lasts
+    Dash {
+        speed: f32,
+        duration: f32,
+    },
+    // Straight down from the air, with a hitbox around the user on landing
+    GroundPound {
+        speed: f32,
+        size: Vec2,
+    },
+}
+
+impl Default for AbilityKind {
+    fn default() -> Self {
+        AbilityKind::Melee {
+            offset: Vec2::new(TILE_WIDTH / 4.0, TILE_HEIGHT / 8.0),
+            size: Vec2::new(TILE_WIDTH / 2.0, TILE_HEIGHT / 4.0),
+            delay: 0.15,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct AbilityDef {
+    pub kind: AbilityKind,
+    // Seconds before it can be used again
+    #[derivative(Default(value = "0.3"))]
+    pub cooldown: f32,
+    // Input that uses it as soon as it's unlocked, rather than from an equip slot
+    pub binding: Option<String>,
+    // Plays the attack animation
+    #[derivative(Default(value = "true"))]
+    pub animation: bool,
+}
+
+// Every ability in `abilities/abilities.ron`, by name
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AbilityLibrary {
+    // Input binding of each equip slot
+    pub slots: Vec<String>,
+    pub abilities: BTreeMap<String, AbilityDef>,
+    // What the player starts out with
+    pub starting: Vec<String>,
+}
+
+impl AbilityLibrary {
+    pub fn load(world: &World) -> AbilityLibrary {
+        let path = world.read_resource::<AssetsDir>().0.join(ABILITIES);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open abilities {:?}: {}", path, e);
+                return AbilityLibrary::default();
+            }
+        };
+        match ron::de::from_reader(file) {
+            Ok(library) => library,
+            Err(e) => {
+                error!("Failed to parse abilities {:?}: {}", path, e);
+                AbilityLibrary::default()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Abilities {
+    // What's in each of the library's slots
+    pub equipped: Vec<Option<String>>,
+    pub unlocked: BTreeSet<String>,
+    // Seconds until each ability can be used again
+    pub(crate) cooldowns: BTreeMap<String, f32>,
+    // Seconds left of a dash and how fast it goes
+    pub(crate) dash: Option<(f32, f32)>,
+    // Size of the hitbox to make on landing from a ground pound
+    pub(crate) pound: Option<Vec2>,
+}
+
+impl Abilities {
+    pub fn new(library: &AbilityLibrary) -> Abilities {
+        let mut abilities = Abilities {
+            equipped: vec![None; library.slots.len()],
+            ..Abilities::default()
+        };
+        for name in library.starting.iter() {
+            abilities.unlock(library, name);
+        }
+        abilities
+    }
+
+    // Also equips it into the first free slot, unless it has its own binding
+    pub fn unlock(&mut self, library: &AbilityLibrary, name: &str) {
+        let def = match library.abilities.get(name) {
+            Some(def) => def,
+            None => {
+                error!("Unknown ability {:?}", name);
+                return;
+            }
+        };
+        if !self.unlocked.insert(String::from(name)) || def.binding.is_some() {
+            return;
+        }
+        if let Some(slot) = self.equipped.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(String::from(name));
+        }
+    }
+
+    // Only unlocked abilities can be equipped, false if it couldn't be
+    pub fn equip(&mut self, name: &str, slot: usize) -> bool {
+        if !self.unlocked.contains(name) || slot >= self.equipped.len() {
+            return false;
+        }
+        for equipped in self.equipped.iter_mut() {
+            if equipped.as_ref().map_or(false, |equipped| equipped == name) {
+                *equipped = None;
+            }
+        }
+        self.equipped[slot] = Some(String::from(name));
+        true
+    }
+
+    fn ready(&self, name: &str) -> bool {
+        self.cooldowns
+            .get(name)
+            .map_or(true, |cooldown| *cooldown <= 0.0)
+    }
+}
+
+impl Component for Abilities {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(SystemDesc)]
+pub struct AbilitySystem;
+
+impl<'s> System<'s> for AbilitySystem {
+    type SystemData = (
+        WriteStorage<'s, Abilities>,
+        WriteStorage<'s, Velocity>,
+        WriteStorage<'s, Invincibility>,
+        WriteStorage<'s, AnimationCounter>,
+        WriteStorage<'s, AnimationStateMachine>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, Orientation>,
+        ReadStorage<'s, Grounded>,
+        ReadStorage<'s, GravityDirection>,
+        ReadStorage<'s, Ducking>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Team>,
+        Read<'s, AbilityLibrary>,
+        Read<'s, Input<StringBindings>>,
+        Read<'s, UiStack>,
+        Read<'s, HitStop>,
+        Read<'s, Time>,
+        Write<'s, EventChannel<Events>>,
+        Read<'s, LazyUpdate>,
+        Entities<'s>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut all_abilities,
+            mut velocities,
+            mut invincibilities,
+            mut counters,
+            mut animation_states,
+            positions,
+            orientations,
+            groundeds,
+            gravities,
+            duckings,
+            healths,
+            teams,
+            library,
+            input,
+            ui_stack,
+            hit_stop,
+            time,
+            mut events_channel,
+            lazy,
+            entities,
+        ): Self::SystemData,
+    ) {
+        let frozen = ui_stack.is_blocking_all_input() || hit_stop.active();
+        for (abilities, velocity, pos, health, team, entity) in (
+            &mut all_abilities,
+            &mut velocities,
+            &positions,
+            &healths,
+            &teams,
+            &entities,
+        )
+            .join()
+        {
+            for cooldown in abilities.cooldowns.values_mut() {
+                *cooldown = (*cooldown - time.delta_seconds()).max(0.0);
+            }
+            let facing = match orientations.get(entity).map_or(1.0, |o| o.vec.x) < 0.0 {
+                true => -1.0,
+                false => 1.0,
+            };
+            let grounded = groundeds.get(entity).map_or(false, |grounded| grounded.0);
+            let gravity = gravities.get(entity).cloned().unwrap_or_default();
+            let mut grav_vel = gravitationally_de_adapted_velocity(&velocity.0, &gravity);
+
+            // Carry on with whatever's still going
+            if let Some((time_left, speed)) = abilities.dash {
+                grav_vel = Vec2::new(facing * speed, 0.0);
+                let time_left = time_left - time.delta_seconds();
+                abilities.dash = match time_left > 0.0 {
+                    true => Some((time_left, speed)),
+                    false => None,
+                };
+            }
+            if let Some(size) = abilities.pound {
+                if grounded {
+                    abilities.pound = None;
+                    events_channel.single_write(Events::CreateDamageBox(
+                        Some(entity),
+                        Vec2::zero(),
+                        size,
+                        *team,
+                    ));
+                }
+            }
+
+            if !health.alive() || frozen {
+                velocity.0 = gravitationally_adapted_velocity(&grav_vel, &gravity);
+                continue;
+            }
+
+            // Abilities pressed this frame, from the slots and their own bindings
+            let slots = library
+                .slots
+                .iter()
+                .zip(abilities.equipped.iter())
+                .filter_map(|(binding, name)| name.as_ref().map(|name| (binding, name)));
+            let own = abilities.unlocked.iter().filter_map(|name| {
+                library
+                    .abilities
+                    .get(name)
+                    .and_then(|def| def.binding.as_ref())
+                    .map(|binding| (binding, name))
+            });
+            let pressed: Vec<String> = slots
+                .chain(own)
+                .filter(|(binding, _)| input.actions.single_press(binding).is_down)
+                .map(|(_, name)| name.clone())
+                .collect();
+
+            for name in pressed {
+                let def = match library.abilities.get(&name) {
+                    Some(def) => def,
+                    None => continue,
+                };
+                if !abilities.ready(&name) {
+                    continue;
+                }
+                let used = match &def.kind {
+                    AbilityKind::Melee {
+                        offset,
+                        size,
+                        delay,
+                    } => {
+                        if counters.contains(entity) {
+                            continue;
+                        }
+                        // Swing low while ducking
+                        let offset = match duckings.contains(entity) {
+                            true => Vec2::new(offset.x, -offset.y),
+                            false => *offset,
+                        };
+                        let (size, team) = (*size, *team);
+                        let counter = AnimationCounter::new(
+                            *delay,
+                            AnimationId::None,
+                            Arc::new(move |world| {
+                                entity_builder::initialize_damage_box(
+                                    world,
+                                    Some(entity),
+                                    &offset,
+                                    &size,
+                                    &team,
+                                );
+                            }),
+                        );
+                        counters
+                            .insert(entity, counter)
+                            .expect("Failed to insert AnimationCounter for attack");
+                        true
+                    }
+                    AbilityKind::Throw {
+                        projectile,
+                        velocity,
+                    } => {
+                        events_channel.single_write(Events::FireProjectile(
+                            projectile.clone(),
+                            pos.0,
+                            gravitationally_adapted_velocity(
+                                &Vec2::new(velocity.x * facing, velocity.y),
+                                &gravity,
+                            ),
+                            *team,
+                        ));
+                        true
+                    }
+                    AbilityKind::ShieldBash {
+                        offset,
+                        size,
+                        lunge,
+                        duration,
+                    } => {
+                        grav_vel.x = facing * lunge;
+                        let (offset, size, team, duration) = (*offset, *size, *team, *duration);
+                        lazy.exec_mut(move |world| {
+                            let shield = entity_builder::initialize_shield(
+                                world,
+                                Some(entity),
+                                &offset,
+                                &size,
+                                &team,
+                            );
+                            let shield = world.entities().entity(shield);
+                            world
+                                .write_storage::<TimedExistence>()
+                                .insert(shield, TimedExistence(duration))
+                                .expect("Failed to time shield");
+                            entity_builder::initialize_damage_box(
+                                world,
+                                Some(entity),
+                                &offset,
+                                &size,
+                                &team,
+                            );
+                        });
+                        true
+                    }
+                    AbilityKind::Dash { speed, duration } => {
+                        abilities.dash = Some((*duration, *speed));
+                        if let Some(invincibility) = invincibilities.get_mut(entity) {
+                            invincibility.0 = invincibility.0.max(*duration);
+                        }
+                        true
+                    }
+                    AbilityKind::GroundPound { speed, size } => match grounded {
+                        true => false,
+                        false => {
+                            grav_vel = Vec2::new(0.0, -speed);
+                            abilities.pound = Some(*size);
+                            true
+                        }
+                    },
+                };
+                if !used {
+                    continue;
+                }
+                abilities.cooldowns.insert(name.clone(), def.cooldown);
+                if def.animation {
+                    if let Some(state) = animation_states.get_mut(entity) {
+                        state.attacking = true;
+                    }
+                }
+            }
+            velocity.0 = gravitationally_adapted_velocity(&grav_vel, &gravity);
+        }
+    }
+}
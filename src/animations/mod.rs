@@ -1,3 +1,4 @@
+use crate::states::loading::AssetsDir;
 use amethyst::{
     animation::*,
     assets::*,
@@ -6,15 +7,31 @@ use amethyst::{
     prelude::{World, WorldExt},
     renderer::SpriteRender,
 };
+use derivative::Derivative;
+use log::error;
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::File};
+use ultraviolet::Vec2;
 
-#[derive(Eq, PartialOrd, PartialEq, Hash, Debug, Copy, Clone)]
+pub const ANIMATION_LIBRARY: &str = "animations/animations.ron";
+pub const DEFAULT_CHARACTER_ANIMATIONS: &str = "actor";
+
+#[derive(Eq, PartialOrd, Ord, PartialEq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum AnimationId {
     None,
     Scale,
     Rotate,
     Translate,
     Animate,
+    // Character states
+    Idle,
+    Run,
+    Jump,
+    Fall,
+    Duck,
+    Attack,
+    Hurt,
+    Dead,
 }
 
 pub enum AnimationAction {
@@ -27,167 +44,383 @@ pub enum SamplerAction {
     SetRate(f32),
 }
 
-pub struct AnimationFactory;
-impl AnimationFactory {
-    fn create_attack_rot(world: &World) -> Handle<Animation<Transform>> {
-        let loader = world.read_resource::<Loader>();
-        let mut trans = Transform::default();
-        trans.set_rotation_z_axis(-1.);
-        let rot = trans.rotation().clone();
-        trans.set_rotation_z_axis(0.);
-        let rot2 = trans.rotation().clone();
-        let ar_rot = [rot.coords.x, rot.coords.y, rot.coords.z, rot.coords.w];
-        let ar_stable = [rot2.coords.x, rot2.coords.y, rot2.coords.z, rot2.coords.w];
-        let sampler = loader.load_from_data(
-            Sampler {
-                input: vec![0., 0.175, 0.2],
-                output: vec![
-                    SamplerPrimitive::Vec4(ar_stable),
-                    SamplerPrimitive::Vec4(ar_rot),
-                    SamplerPrimitive::Vec4(ar_stable),
-                ],
-                function: InterpolationFunction::SphericalLinear,
-            },
-            (),
-            &world.read_resource(),
-        );
-        let animation = loader.load_from_data(
-            Animation::new_single(0, TransformChannel::Rotation, sampler),
-            (),
-            &world.read_resource(),
-        );
-        return animation;
+// Only the interpolations whose samplers need no extra tangent data
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    SphericalLinear,
+}
+
+impl Default for Interpolation {
+    fn default() -> Self {
+        Interpolation::Linear
     }
+}
 
-    pub fn create_bob(world: &World, amplitude: f32) -> AnimationSet<AnimationId, Transform> {
-        let mut anim: AnimationSet<AnimationId, Transform> = AnimationSet::default();
-        let loader = world.read_resource::<Loader>();
-        let sampler = loader.load_from_data(
-            Sampler {
-                input: vec![0., 1., 2.],
-                output: vec![
-                    SamplerPrimitive::Vec3([0., 0., 0.]),
-                    SamplerPrimitive::Vec3([0., amplitude, 0.]),
-                    SamplerPrimitive::Vec3([0., 0., 0.]),
-                ],
-                function: InterpolationFunction::Linear,
-            },
-            (),
-            &world.read_resource(),
-        );
-        let animation = loader.load_from_data(
-            Animation::new_single(0, TransformChannel::Translation, sampler),
-            (),
-            &world.read_resource(),
-        );
-        anim.animations.insert(AnimationId::Animate, animation);
-        anim.animations
-            .insert(AnimationId::Rotate, Self::create_attack_rot(world));
-        anim
+impl Interpolation {
+    fn to_function<T>(self) -> InterpolationFunction<T>
+    where
+        T: InterpolationPrimitive,
+    {
+        match self {
+            Interpolation::Step => InterpolationFunction::Step,
+            Interpolation::Linear => InterpolationFunction::Linear,
+            Interpolation::SphericalLinear => InterpolationFunction::SphericalLinear,
+        }
     }
+}
 
-    pub fn create_walking_animation(world: &World) -> AnimationSet<AnimationId, Transform> {
-        let mut anim: AnimationSet<AnimationId, Transform> = AnimationSet::default();
-        let loader = world.read_resource::<Loader>();
-        let mut trans = Transform::default();
-        trans.set_rotation_z_axis(0.);
-        let quat_stable = trans.rotation().clone();
-        trans.set_rotation_z_axis(0.5);
-        let quat_rot = trans.rotation().clone();
-        trans.set_rotation_z_axis(-0.5);
-        let quat_rot_neg = trans.rotation().clone();
-        let ar_stable = [
-            quat_stable.coords.x,
-            quat_stable.coords.y,
-            quat_stable.coords.z,
-            quat_stable.coords.w,
-        ];
-        let ar_rot = [
-            quat_rot.coords.x,
-            quat_rot.coords.y,
-            quat_rot.coords.z,
-            quat_rot.coords.w,
-        ];
-        let ar_rot_neg = [
-            quat_rot_neg.coords.x,
-            quat_rot_neg.coords.y,
-            quat_rot_neg.coords.z,
-            quat_rot_neg.coords.w,
-        ];
-        let backward_rot_sampler = loader.load_from_data(
-            Sampler {
-                input: vec![0., 1., 2.],
-                output: vec![
-                    SamplerPrimitive::Vec4(ar_stable),
-                    SamplerPrimitive::Vec4(ar_rot_neg),
-                    SamplerPrimitive::Vec4(ar_stable),
-                ],
-                function: InterpolationFunction::SphericalLinear,
-            },
-            (),
-            &world.read_resource(),
-        );
-        let forward_rot_sampler = loader.load_from_data(
-            Sampler {
-                input: vec![0., 1., 2.],
-                output: vec![
-                    SamplerPrimitive::Vec4(ar_stable),
-                    SamplerPrimitive::Vec4(ar_rot),
-                    SamplerPrimitive::Vec4(ar_stable),
-                ],
-                function: InterpolationFunction::SphericalLinear,
-            },
-            (),
-            &world.read_resource(),
-        );
+// Keyframes of a transform channel as (time in seconds, value)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TransformKeys {
+    Translation(Vec<(f32, [f32; 3])>),
+    Scale(Vec<(f32, [f32; 3])>),
+    // Angle around the z axis, in radians
+    RotationZ(Vec<(f32, f32)>),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransformChannelDef {
+    // Node of the animation hierarchy, 0 is the animated entity itself
+    #[serde(default)]
+    pub node: usize,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    pub keys: TransformKeys,
+}
 
-        let mut animation = Animation::new();
-        animation
-            .nodes
-            .push((1, TransformChannel::Rotation, backward_rot_sampler.clone()));
-        animation
-            .nodes
-            .push((2, TransformChannel::Rotation, forward_rot_sampler.clone()));
-        animation
-            .nodes
-            .push((3, TransformChannel::Rotation, backward_rot_sampler.clone()));
-        animation
-            .nodes
-            .push((4, TransformChannel::Rotation, forward_rot_sampler.clone()));
-
-        let animation = loader.load_from_data(animation, (), &world.read_resource());
-        anim.animations.insert(AnimationId::Animate, animation);
-        anim
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AnimationClipDef {
+    Transform(Vec<TransformChannelDef>),
+    // (sprite number, seconds it is shown for)
+    Sprite(Vec<(usize, f32)>),
+}
+
+// What a character is doing this frame, for the state graph to decide on
+pub struct AnimationFacts {
+    pub grounded: bool,
+    // Velocity relative to the character's gravity
+    pub velocity: Vec2,
+    pub ducking: bool,
+    pub attacking: bool,
+    pub hurt: bool,
+    pub dead: bool,
+    pub time_in_state: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AnimationCondition {
+    Grounded,
+    Airborne,
+    Rising,
+    Falling,
+    Moving,
+    Still,
+    Ducking,
+    Standing,
+    Attacking,
+    Hurt,
+    Unhurt,
+    Dead,
+    Alive,
+    // Seconds since the current state was entered
+    After(f32),
+}
+
+impl AnimationCondition {
+    pub fn holds(&self, facts: &AnimationFacts) -> bool {
+        match self {
+            AnimationCondition::Grounded => facts.grounded,
+            AnimationCondition::Airborne => !facts.grounded,
+            AnimationCondition::Rising => !facts.grounded && facts.velocity.y > 0.0,
+            AnimationCondition::Falling => !facts.grounded && facts.velocity.y <= 0.0,
+            AnimationCondition::Moving => facts.velocity.x != 0.0,
+            AnimationCondition::Still => facts.velocity.x == 0.0,
+            AnimationCondition::Ducking => facts.ducking,
+            AnimationCondition::Standing => !facts.ducking,
+            AnimationCondition::Attacking => facts.attacking,
+            AnimationCondition::Hurt => facts.hurt,
+            AnimationCondition::Unhurt => !facts.hurt,
+            AnimationCondition::Dead => facts.dead,
+            AnimationCondition::Alive => !facts.dead,
+            AnimationCondition::After(seconds) => facts.time_in_state >= *seconds,
+        }
     }
+}
 
-    pub fn create_sprite_animation(world: &World) -> AnimationSet<AnimationId, SpriteRender> {
-        let mut anim: AnimationSet<AnimationId, SpriteRender> = AnimationSet::default();
-        let loader = world.read_resource::<Loader>();
-        let sampler = loader.load_from_data(
-            Sampler {
-                input: vec![0., 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.5],
-                output: vec![
-                    SpriteRenderPrimitive::SpriteIndex(1),
-                    SpriteRenderPrimitive::SpriteIndex(2),
-                    SpriteRenderPrimitive::SpriteIndex(3),
-                    SpriteRenderPrimitive::SpriteIndex(4),
-                    SpriteRenderPrimitive::SpriteIndex(5),
-                    SpriteRenderPrimitive::SpriteIndex(6),
-                    SpriteRenderPrimitive::SpriteIndex(7),
-                    SpriteRenderPrimitive::SpriteIndex(0),
-                ],
-                function: InterpolationFunction::Step,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnimationTransition {
+    pub to: AnimationId,
+    // Every condition has to hold
+    #[serde(default)]
+    pub when: Vec<AnimationCondition>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct AnimationStateDef {
+    // Checked in order, the first one that holds is taken
+    pub transitions: Vec<AnimationTransition>,
+    // Sprite shown on entering the state, for states without a sprite clip
+    pub frame: Option<usize>,
+    // Restart the state's clips whenever they finish
+    #[derivative(Default(value = "true"))]
+    pub looping: bool,
+    // Play the clips faster the faster the character moves horizontally
+    pub rate_from_speed: bool,
+}
+
+// The clips a character plays for each animation id, by clip name,
+// and the state graph that picks which of them is playing
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+pub struct CharacterAnimations {
+    #[serde(default)]
+    pub sprite: BTreeMap<AnimationId, String>,
+    #[serde(default)]
+    pub transform: BTreeMap<AnimationId, String>,
+    #[derivative(Default(value = "AnimationId::Idle"))]
+    #[serde(default = "default_initial_state")]
+    pub initial: AnimationId,
+    #[serde(default)]
+    pub states: BTreeMap<AnimationId, AnimationStateDef>,
+    // Transitions that can be taken from every state, checked before the state's own
+    #[serde(default)]
+    pub any_state: Vec<AnimationTransition>,
+}
+
+fn default_initial_state() -> AnimationId {
+    AnimationId::Idle
+}
+
+impl CharacterAnimations {
+    pub fn next_state(&self, current: AnimationId, facts: &AnimationFacts) -> Option<AnimationId> {
+        let own = self
+            .states
+            .get(&current)
+            .map(|state| state.transitions.iter())
+            .into_iter()
+            .flatten();
+        self.any_state
+            .iter()
+            .chain(own)
+            .find(|transition| {
+                transition.to != current
+                    && transition
+                        .when
+                        .iter()
+                        .all(|condition| condition.holds(facts))
+            })
+            .map(|transition| transition.to)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct AnimationLibraryFile {
+    clips: BTreeMap<String, AnimationClipDef>,
+    characters: BTreeMap<String, CharacterAnimations>,
+}
+
+// Every clip in `animations/animations.ron`. Handles are only created the first time a clip is
+// used, since the animation asset storages don't exist until the game dispatcher is built.
+#[derive(Default)]
+pub struct AnimationLibrary {
+    clips: BTreeMap<String, AnimationClipDef>,
+    characters: BTreeMap<String, CharacterAnimations>,
+    transform_handles: BTreeMap<String, Handle<Animation<Transform>>>,
+    sprite_handles: BTreeMap<String, Handle<Animation<SpriteRender>>>,
+}
+
+impl AnimationLibrary {
+    pub fn character(&self, name: &str) -> Option<&CharacterAnimations> {
+        self.characters.get(name)
+    }
+
+    pub fn load(world: &World) -> AnimationLibrary {
+        let path = world.read_resource::<AssetsDir>().0.join(ANIMATION_LIBRARY);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open animation library {:?}: {}", path, e);
+                return AnimationLibrary::default();
+            }
+        };
+        match ron::de::from_reader::<_, AnimationLibraryFile>(file) {
+            Ok(library) => AnimationLibrary {
+                clips: library.clips,
+                characters: library.characters,
+                ..AnimationLibrary::default()
             },
-            (),
-            &world.read_resource(),
-        );
-        let animation = loader.load_from_data(
-            Animation::new_single(0, SpriteRenderChannel::SpriteIndex, sampler),
-            (),
-            &world.read_resource(),
-        );
-        anim.animations.insert(AnimationId::Animate, animation);
-        anim
+            Err(e) => {
+                error!("Failed to parse animation library {:?}: {}", path, e);
+                AnimationLibrary::default()
+            }
+        }
+    }
+}
+
+pub struct AnimationFactory;
+impl AnimationFactory {
+    // Build the sprite and transform animation sets of the character called `name`
+    pub fn create_character_animations(
+        world: &mut World,
+        name: &str,
+    ) -> (
+        AnimationSet<AnimationId, SpriteRender>,
+        AnimationSet<AnimationId, Transform>,
+    ) {
+        let character = world
+            .read_resource::<AnimationLibrary>()
+            .characters
+            .get(name)
+            .cloned();
+        let character = match character {
+            Some(character) => character,
+            None => {
+                error!("No character animations named {}", name);
+                CharacterAnimations::default()
+            }
+        };
+
+        let mut sprite_set: AnimationSet<AnimationId, SpriteRender> = AnimationSet::default();
+        for (id, clip) in character.sprite.iter() {
+            if let Some(handle) = Self::sprite_clip(world, clip) {
+                sprite_set.animations.insert(*id, handle);
+            }
+        }
+        let mut transform_set: AnimationSet<AnimationId, Transform> = AnimationSet::default();
+        for (id, clip) in character.transform.iter() {
+            if let Some(handle) = Self::transform_clip(world, clip) {
+                transform_set.animations.insert(*id, handle);
+            }
+        }
+        (sprite_set, transform_set)
+    }
+
+    fn sprite_clip(world: &mut World, name: &str) -> Option<Handle<Animation<SpriteRender>>> {
+        if let Some(handle) = world
+            .read_resource::<AnimationLibrary>()
+            .sprite_handles
+            .get(name)
+        {
+            return Some(handle.clone());
+        }
+        let frames = match world.read_resource::<AnimationLibrary>().clips.get(name) {
+            Some(AnimationClipDef::Sprite(frames)) => frames.clone(),
+            _ => {
+                error!("No sprite animation clip named {}", name);
+                return None;
+            }
+        };
+
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+        let mut time = 0.0;
+        for (sprite, duration) in frames.iter() {
+            input.push(time);
+            output.push(SpriteRenderPrimitive::SpriteIndex(*sprite));
+            time += duration;
+        }
+        // Repeat the last frame so it's shown for its whole duration too
+        if let Some((sprite, _)) = frames.last() {
+            input.push(time);
+            output.push(SpriteRenderPrimitive::SpriteIndex(*sprite));
+        }
+
+        let handle = {
+            let loader = world.read_resource::<Loader>();
+            let sampler = loader.load_from_data(
+                Sampler {
+                    input,
+                    output,
+                    function: InterpolationFunction::Step,
+                },
+                (),
+                &world.read_resource(),
+            );
+            loader.load_from_data(
+                Animation::new_single(0, SpriteRenderChannel::SpriteIndex, sampler),
+                (),
+                &world.read_resource(),
+            )
+        };
+        world
+            .write_resource::<AnimationLibrary>()
+            .sprite_handles
+            .insert(String::from(name), handle.clone());
+        Some(handle)
+    }
+
+    fn transform_clip(world: &mut World, name: &str) -> Option<Handle<Animation<Transform>>> {
+        if let Some(handle) = world
+            .read_resource::<AnimationLibrary>()
+            .transform_handles
+            .get(name)
+        {
+            return Some(handle.clone());
+        }
+        let channels = match world.read_resource::<AnimationLibrary>().clips.get(name) {
+            Some(AnimationClipDef::Transform(channels)) => channels.clone(),
+            _ => {
+                error!("No transform animation clip named {}", name);
+                return None;
+            }
+        };
+
+        let handle = {
+            let loader = world.read_resource::<Loader>();
+            let mut animation = Animation::new();
+            for channel in channels.iter() {
+                let (transform_channel, input, output) = match &channel.keys {
+                    TransformKeys::Translation(keys) => (
+                        TransformChannel::Translation,
+                        keys.iter().map(|(time, _)| *time).collect(),
+                        keys.iter()
+                            .map(|(_, value)| SamplerPrimitive::Vec3(*value))
+                            .collect(),
+                    ),
+                    TransformKeys::Scale(keys) => (
+                        TransformChannel::Scale,
+                        keys.iter().map(|(time, _)| *time).collect(),
+                        keys.iter()
+                            .map(|(_, value)| SamplerPrimitive::Vec3(*value))
+                            .collect(),
+                    ),
+                    TransformKeys::RotationZ(keys) => (
+                        TransformChannel::Rotation,
+                        keys.iter().map(|(time, _)| *time).collect(),
+                        keys.iter()
+                            .map(|(_, angle)| {
+                                let mut trans = Transform::default();
+                                trans.set_rotation_z_axis(*angle);
+                                let rot = trans.rotation().coords;
+                                SamplerPrimitive::Vec4([rot.x, rot.y, rot.z, rot.w])
+                            })
+                            .collect(),
+                    ),
+                };
+                let sampler = loader.load_from_data(
+                    Sampler {
+                        input,
+                        output,
+                        function: channel.interpolation.to_function(),
+                    },
+                    (),
+                    &world.read_resource(),
+                );
+                animation
+                    .nodes
+                    .push((channel.node, transform_channel, sampler));
+            }
+            loader.load_from_data(animation, (), &world.read_resource())
+        };
+        world
+            .write_resource::<AnimationLibrary>()
+            .transform_handles
+            .insert(String::from(name), handle.clone());
+        Some(handle)
     }
 
     pub fn set_sprite_animation(
@@ -308,3 +541,115 @@ impl AnimationFactory {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAPH: &str = "(
+        any_state: [
+            (to: Dead, when: [Dead]),
+        ],
+        states: {
+            Idle: (
+                transitions: [
+                    (to: Jump, when: [Rising]),
+                    (to: Fall, when: [Falling]),
+                    (to: Run, when: [Moving, Grounded]),
+                ],
+            ),
+            Run: (
+                transitions: [
+                    (to: Idle, when: [Still]),
+                ],
+            ),
+            Attack: (
+                transitions: [
+                    (to: Idle, when: [After(0.2)]),
+                ],
+            ),
+            Dead: (
+                transitions: [
+                    (to: Idle, when: [Alive]),
+                ],
+            ),
+        },
+    )";
+
+    fn graph() -> CharacterAnimations {
+        ron::de::from_str(GRAPH).expect("Failed to read the test graph")
+    }
+
+    fn standing() -> AnimationFacts {
+        AnimationFacts {
+            grounded: true,
+            velocity: Vec2::zero(),
+            ducking: false,
+            attacking: false,
+            hurt: false,
+            dead: false,
+            time_in_state: 0.0,
+        }
+    }
+
+    #[test]
+    fn stays_put_while_nothing_holds() {
+        assert_eq!(graph().next_state(AnimationId::Idle, &standing()), None);
+        // States without an entry only have the shared transitions
+        assert_eq!(graph().next_state(AnimationId::Duck, &standing()), None);
+    }
+
+    #[test]
+    fn every_condition_has_to_hold() {
+        let moving = AnimationFacts {
+            velocity: Vec2::new(3.0, 0.0),
+            ..standing()
+        };
+        assert_eq!(
+            graph().next_state(AnimationId::Idle, &moving),
+            Some(AnimationId::Run)
+        );
+        let airborne = AnimationFacts {
+            grounded: false,
+            velocity: Vec2::new(3.0, 0.0),
+            ..standing()
+        };
+        // Falling comes first, and moving isn't enough without the ground
+        assert_eq!(
+            graph().next_state(AnimationId::Idle, &airborne),
+            Some(AnimationId::Fall)
+        );
+    }
+
+    #[test]
+    fn shared_transitions_come_first() {
+        let dead = AnimationFacts {
+            dead: true,
+            velocity: Vec2::new(3.0, 0.0),
+            ..standing()
+        };
+        assert_eq!(
+            graph().next_state(AnimationId::Idle, &dead),
+            Some(AnimationId::Dead)
+        );
+        // Not into the state it's already in
+        assert_eq!(graph().next_state(AnimationId::Dead, &dead), None);
+    }
+
+    #[test]
+    fn waits_for_time_in_state() {
+        let early = AnimationFacts {
+            time_in_state: 0.1,
+            ..standing()
+        };
+        assert_eq!(graph().next_state(AnimationId::Attack, &early), None);
+        let late = AnimationFacts {
+            time_in_state: 0.2,
+            ..standing()
+        };
+        assert_eq!(
+            graph().next_state(AnimationId::Attack, &late),
+            Some(AnimationId::Idle)
+        );
+    }
+}
@@ -1,19 +1,377 @@
+use crate::{
+    components::{game::CollisionEvent, physics::Position},
+    events::{DamageEvent, Events, PlayerEvent},
+    settings::Settings,
+    states::{loading::AssetsDir, pizzatopia::CAM_WIDTH},
+};
 use amethyst::{
     assets::{AssetStorage, Loader},
-    audio::{output::Output, OggFormat, Source, SourceHandle},
-    ecs::{World, WorldExt},
+    audio::{output::Output, AudioSink, OggFormat, Source, SourceHandle},
+    core::{
+        shrev::{EventChannel, ReaderId},
+        timing::Time,
+    },
+    derive::SystemDesc,
+    ecs::{
+        Entities, Join, Read, ReadExpect, ReadStorage, System, SystemData, World, WorldExt, Write,
+    },
+    renderer::Camera,
 };
+use derivative::Derivative;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::File};
+use ultraviolet::Vec2;
+
+const SOUND_TABLE: &str = "audio/sounds.ron";
+// Seconds it takes for one music track to fade into the next
+const MUSIC_FADE_TIME: f32 = 1.5;
+// Sounds within this distance of the camera play at full volume...
+const SFX_FULL_VOLUME_DISTANCE: f32 = CAM_WIDTH / 2.0;
+// ...and fade out until they can't be heard at this distance
+const SFX_SILENT_DISTANCE: f32 = CAM_WIDTH * 1.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Sfx {
+    Damage,
+    Jump,
+    Land,
+    Pickup,
+    ProjectileFire,
+    Reflect,
+    Talk,
+    Gift,
+}
+
+// The game events that can make a sound
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SoundTrigger {
+    EnemyCollision,
+    ProjectileReflection,
+    ProjectileBlock,
+    ItemCollect,
+    Talk,
+    Gift,
+    FireProjectile,
+    CreateDamageBox,
+    Warp,
+    Reset,
+    Revive,
+    Jump,
+    Land,
+}
 
-const DAMAGE_SOUND: &str = "audio/ding-ding-ding.ogg";
+// Contents of `audio/sounds.ron`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SoundTable {
+    // Sound effect -> ogg file, relative to the assets directory
+    sounds: BTreeMap<Sfx, String>,
+    // Game event -> sound effect it plays
+    triggers: BTreeMap<SoundTrigger, Sfx>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct AudioSettings {
+    #[derivative(Default(value = "1.0"))]
+    pub master: f32,
+    #[derivative(Default(value = "1.0"))]
+    pub music: f32,
+    #[derivative(Default(value = "1.0"))]
+    pub sfx: f32,
+}
+
+impl AudioSettings {
+    pub fn music_volume(&self) -> f32 {
+        self.master * self.music
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.master * self.sfx
+    }
+}
+
+// Where sounds are heard from, follows the camera
+#[derive(Default)]
+pub struct AudioListener(pub Vec2);
 
 pub struct Sounds {
-    pub dmg_sfx: SourceHandle,
+    pub sfx: BTreeMap<Sfx, SourceHandle>,
+    pub triggers: BTreeMap<SoundTrigger, Sfx>,
 }
 
-pub fn play_damage_sound(sounds: &Sounds, storage: &AssetStorage<Source>, output: Option<&Output>) {
-    if let Some(ref output) = output.as_ref() {
-        if let Some(sound) = storage.get(&sounds.dmg_sfx) {
-            output.play_once(sound, 1.0);
+// Everything a system needs to play a sound effect
+#[derive(SystemData)]
+pub struct SoundPlayer<'s> {
+    sounds: ReadExpect<'s, Sounds>,
+    storage: Read<'s, AssetStorage<Source>>,
+    output: Option<Read<'s, Output>>,
+    settings: Read<'s, Settings>,
+    listener: Read<'s, AudioListener>,
+}
+
+impl<'s> SoundPlayer<'s> {
+    // Play whatever sound the table assigns to this trigger, if any
+    pub fn trigger(&self, trigger: SoundTrigger, pos: Option<Vec2>) {
+        if let Some(sfx) = self.sounds.triggers.get(&trigger) {
+            self.play(*sfx, pos);
+        }
+    }
+
+    // Play a sound, attenuated by its distance from the camera if it has a position
+    pub fn play(&self, sfx: Sfx, pos: Option<Vec2>) {
+        let output = match &self.output {
+            Some(output) => output,
+            None => return,
+        };
+        let sound = match self.sounds.sfx.get(&sfx) {
+            Some(handle) => self.storage.get(handle),
+            None => None,
+        };
+        if let Some(sound) = sound {
+            let attenuation = match pos {
+                Some(pos) => positional_volume(pos, self.listener.0),
+                None => 1.0,
+            };
+            let volume = self.settings.audio.sfx_volume() * attenuation;
+            if volume > 0.0 {
+                output.play_once(sound, volume);
+            }
+        }
+    }
+}
+
+pub fn positional_volume(pos: Vec2, listener: Vec2) -> f32 {
+    let distance = (pos - listener).mag();
+    let falloff =
+        (distance - SFX_FULL_VOLUME_DISTANCE) / (SFX_SILENT_DISTANCE - SFX_FULL_VOLUME_DISTANCE);
+    1.0 - falloff.max(0.0).min(1.0)
+}
+
+struct MusicTrack {
+    path: String,
+    source: SourceHandle,
+    sink: AudioSink,
+    // 0 is silent and 1 is fully faded in, before the volume settings are applied
+    fade: f32,
+}
+
+#[derive(Default)]
+pub struct MusicPlayer {
+    current: Option<MusicTrack>,
+    fading_out: Vec<MusicTrack>,
+}
+
+impl MusicPlayer {
+    pub fn current_track(&self) -> Option<String> {
+        self.current.as_ref().map(|track| track.path.clone())
+    }
+}
+
+/// Crossfade into a new background track, or fade out to silence with `None`.
+/// Asking for the track that's already playing leaves it alone.
+pub fn change_music(world: &mut World, track: Option<&str>) {
+    let current = world.read_resource::<MusicPlayer>().current_track();
+    if current.as_ref().map(|path| path.as_str()) == track {
+        return;
+    }
+
+    let next = match track {
+        Some(path) => {
+            let output = match world.try_fetch::<Output>() {
+                Some(output) => output,
+                None => {
+                    warn!("No audio output, can't play music {}", path);
+                    return;
+                }
+            };
+            let loader = world.read_resource::<Loader>();
+            Some(MusicTrack {
+                path: String::from(path),
+                source: load_audio_track(&loader, &world, path),
+                sink: AudioSink::new(&output),
+                fade: 0.0,
+            })
+        }
+        None => None,
+    };
+
+    let mut music = world.write_resource::<MusicPlayer>();
+    if let Some(previous) = music.current.take() {
+        music.fading_out.push(previous);
+    }
+    music.current = next;
+}
+
+#[derive(SystemDesc)]
+pub struct MusicSystem;
+
+impl<'s> System<'s> for MusicSystem {
+    type SystemData = (
+        Write<'s, MusicPlayer>,
+        Write<'s, AudioListener>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, Camera>,
+        Read<'s, AssetStorage<Source>>,
+        Read<'s, Settings>,
+        Read<'s, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (mut music, mut listener, positions, cameras, storage, settings, time): Self::SystemData,
+    ) {
+        for (position, _camera) in (&positions, &cameras).join() {
+            listener.0 = position.0;
+        }
+
+        let fade_step = time.delta_real_seconds() / MUSIC_FADE_TIME;
+        let volume = settings.audio.music_volume();
+
+        if let Some(track) = &mut music.current {
+            // Loop the track, this also starts it once the asset is done loading
+            if track.sink.empty() {
+                if let Some(source) = storage.get(&track.source) {
+                    if let Err(e) = track.sink.append(source) {
+                        error!("Failed to play music {}: {}", track.path, e);
+                    }
+                }
+            }
+            track.fade = (track.fade + fade_step).min(1.0);
+            track.sink.set_volume(track.fade * volume);
+        }
+
+        for track in music.fading_out.iter_mut() {
+            track.fade = (track.fade - fade_step).max(0.0);
+            track.sink.set_volume(track.fade * volume);
+            if track.fade <= 0.0 {
+                track.sink.stop();
+            }
+        }
+        music.fading_out.retain(|track| track.fade > 0.0);
+    }
+}
+
+// Turns gameplay events into sound effects, so gameplay systems don't need to know about audio
+#[derive(SystemDesc)]
+#[system_desc(name(AudioEventSystemDesc))]
+pub struct AudioEventSystem {
+    #[system_desc(event_channel_reader)]
+    collision_reader: ReaderId<CollisionEvent>,
+    #[system_desc(event_channel_reader)]
+    events_reader: ReaderId<Events>,
+    #[system_desc(event_channel_reader)]
+    player_reader: ReaderId<PlayerEvent>,
+    #[system_desc(event_channel_reader)]
+    damage_reader: ReaderId<DamageEvent>,
+}
+
+impl AudioEventSystem {
+    pub(crate) fn new(
+        collision_reader: ReaderId<CollisionEvent>,
+        events_reader: ReaderId<Events>,
+        player_reader: ReaderId<PlayerEvent>,
+        damage_reader: ReaderId<DamageEvent>,
+    ) -> Self {
+        Self {
+            collision_reader,
+            events_reader,
+            player_reader,
+            damage_reader,
+        }
+    }
+}
+
+impl<'s> System<'s> for AudioEventSystem {
+    type SystemData = (
+        SoundPlayer<'s>,
+        Read<'s, EventChannel<CollisionEvent>>,
+        Read<'s, EventChannel<Events>>,
+        Read<'s, EventChannel<PlayerEvent>>,
+        Read<'s, EventChannel<DamageEvent>>,
+        ReadStorage<'s, Position>,
+        Entities<'s>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            sound_player,
+            collision_channel,
+            events_channel,
+            player_channel,
+            damage_channel,
+            positions,
+            entities,
+        ): Self::SystemData,
+    ) {
+        let pos_of = |id: u32| positions.get(entities.entity(id)).map(|pos| pos.0);
+
+        for event in damage_channel.read(&mut self.damage_reader) {
+            match event {
+                DamageEvent::Damaged { pos, .. } => {
+                    sound_player.trigger(SoundTrigger::EnemyCollision, *pos);
+                }
+                DamageEvent::Died { .. } => {}
+            }
+        }
+
+        for event in collision_channel.read(&mut self.collision_reader) {
+            match event {
+                CollisionEvent::EnemyCollision(..) => {}
+                CollisionEvent::ProjectileReflection(id, _) => {
+                    sound_player.trigger(SoundTrigger::ProjectileReflection, pos_of(*id));
+                }
+                CollisionEvent::ProjectileBlock(id) => {
+                    sound_player.trigger(SoundTrigger::ProjectileBlock, pos_of(*id));
+                }
+                CollisionEvent::ItemCollect(_, item_id) => {
+                    sound_player.trigger(SoundTrigger::ItemCollect, pos_of(*item_id));
+                }
+                CollisionEvent::Talk(_, id) => {
+                    sound_player.trigger(SoundTrigger::Talk, pos_of(*id));
+                }
+                CollisionEvent::Gift(_, _, id) => {
+                    sound_player.trigger(SoundTrigger::Gift, pos_of(*id));
+                }
+            }
+        }
+
+        for event in events_channel.read(&mut self.events_reader) {
+            match event {
+                Events::FireProjectile(_, pos, _, _) => {
+                    sound_player.trigger(SoundTrigger::FireProjectile, Some(*pos));
+                }
+                Events::CreateDamageBox(parent, _, _, _) => {
+                    let pos = parent
+                        .and_then(|parent| positions.get(parent))
+                        .map(|pos| pos.0);
+                    sound_player.trigger(SoundTrigger::CreateDamageBox, pos);
+                }
+                Events::Warp(pos) => {
+                    sound_player.trigger(SoundTrigger::Warp, Some(*pos));
+                }
+                Events::Reset => {
+                    sound_player.trigger(SoundTrigger::Reset, None);
+                }
+                _ => {}
+            }
+        }
+
+        for event in player_channel.read(&mut self.player_reader) {
+            match event {
+                PlayerEvent::Revive(_) => {
+                    sound_player.trigger(SoundTrigger::Revive, None);
+                }
+                PlayerEvent::Jump(pos) => {
+                    sound_player.trigger(SoundTrigger::Jump, Some(*pos));
+                }
+                PlayerEvent::Land(pos) => {
+                    sound_player.trigger(SoundTrigger::Land, Some(*pos));
+                }
+            }
         }
     }
 }
@@ -23,21 +381,41 @@ fn load_audio_track(loader: &Loader, world: &World, file: &str) -> SourceHandle
     loader.load(file, OggFormat, (), &world.read_resource())
 }
 
-/// Initialise audio in the world. This will eventually include
-/// the background tracks as well as the sound effects, but for now
-/// we'll just work on sound effects.
+/// Initialise audio in the world. Sound effects and the events that trigger them are listed
+/// in `audio/sounds.ron`, music is loaded per level by `change_music`.
 pub fn initialise_audio(world: &mut World) {
+    let path = world.read_resource::<AssetsDir>().0.join(SOUND_TABLE);
+    let table: SoundTable = match File::open(&path) {
+        Ok(file) => match ron::de::from_reader(file) {
+            Ok(table) => table,
+            Err(e) => {
+                error!("Failed to parse sound table {:?}: {}", path, e);
+                SoundTable::default()
+            }
+        },
+        Err(e) => {
+            error!("Failed to open sound table {:?}: {}", path, e);
+            SoundTable::default()
+        }
+    };
+
     let sound_effects = {
         let loader = world.read_resource::<Loader>();
 
-        let sound = Sounds {
-            dmg_sfx: load_audio_track(&loader, &world, DAMAGE_SOUND),
-        };
+        let sfx = table
+            .sounds
+            .iter()
+            .map(|(sfx, file)| (*sfx, load_audio_track(&loader, &world, file)))
+            .collect();
 
-        sound
+        Sounds {
+            sfx,
+            triggers: table.triggers,
+        }
     };
 
     // Add sound effects to the world. We have to do this in another scope because
     // world won't let us insert new resources as long as `Loader` is borrowed.
     world.insert(sound_effects);
+    world.insert(MusicPlayer::default());
 }
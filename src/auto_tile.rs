@@ -0,0 +1,326 @@
+use crate::{
+    components::{
+        editor::{EditorFlag, InstanceEntityId, SizeForEditorGrid, TileLayer},
+        game::{AnimatedTileComp, SerializedObject, SerializedObjectType, SpriteRenderData, Tile},
+        graphics::{InTileChunk, SpriteSheetId, TileGroup},
+        physics::Position,
+    },
+    states::{loading::SpriteSheetManifest, pizzatopia::TILE_HEIGHT, pizzatopia::TILE_WIDTH},
+    tile_chunks::set_chunk_tile,
+};
+use amethyst::{
+    ecs::prelude::{Entity, Join},
+    prelude::{World, WorldExt},
+    renderer::SpriteRender,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use ultraviolet::Vec2;
+
+// Simple16 only looks at the four edges
+const EDGE_NORTH: u8 = 1;
+const EDGE_EAST: u8 = 2;
+const EDGE_SOUTH: u8 = 4;
+const EDGE_WEST: u8 = 8;
+
+// Blob47 looks at all eight neighbours, clockwise from the top
+const BLOB_NORTH: u8 = 1;
+const BLOB_NORTH_EAST: u8 = 2;
+const BLOB_EAST: u8 = 4;
+const BLOB_SOUTH_EAST: u8 = 8;
+const BLOB_SOUTH: u8 = 16;
+const BLOB_SOUTH_WEST: u8 = 32;
+const BLOB_WEST: u8 = 64;
+const BLOB_NORTH_WEST: u8 = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AutoTileKind {
+    // Edge neighbours only: N = 1, E = 2, S = 4, W = 8
+    Simple16,
+    // Edges and corners: N = 1, NE = 2, E = 4, SE = 8, S = 16, SW = 32, W = 64, NW = 128
+    // A corner only counts when both edges next to it are set, which leaves 47 combinations
+    Blob47,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AutoTileRules {
+    pub kind: AutoTileKind,
+    // Neighbour bitmask -> sprite number in the sheet
+    pub tiles: BTreeMap<u8, usize>,
+    // Sprite used for bitmasks that have no entry in `tiles`
+    pub fallback: usize,
+}
+
+impl AutoTileRules {
+    pub fn sprite_for(&self, has_neighbour: impl Fn(i32, i32) -> bool) -> usize {
+        let mask = match self.kind {
+            AutoTileKind::Simple16 => Self::edge_mask(&has_neighbour),
+            AutoTileKind::Blob47 => Self::blob_mask(&has_neighbour),
+        };
+        *self.tiles.get(&mask).unwrap_or(&self.fallback)
+    }
+
+    fn edge_mask(has_neighbour: &impl Fn(i32, i32) -> bool) -> u8 {
+        let mut mask = 0;
+        if has_neighbour(0, 1) {
+            mask |= EDGE_NORTH;
+        }
+        if has_neighbour(1, 0) {
+            mask |= EDGE_EAST;
+        }
+        if has_neighbour(0, -1) {
+            mask |= EDGE_SOUTH;
+        }
+        if has_neighbour(-1, 0) {
+            mask |= EDGE_WEST;
+        }
+        mask
+    }
+
+    fn blob_mask(has_neighbour: &impl Fn(i32, i32) -> bool) -> u8 {
+        let north = has_neighbour(0, 1);
+        let east = has_neighbour(1, 0);
+        let south = has_neighbour(0, -1);
+        let west = has_neighbour(-1, 0);
+
+        let mut mask = 0;
+        if north {
+            mask |= BLOB_NORTH;
+        }
+        if east {
+            mask |= BLOB_EAST;
+        }
+        if south {
+            mask |= BLOB_SOUTH;
+        }
+        if west {
+            mask |= BLOB_WEST;
+        }
+        if north && east && has_neighbour(1, 1) {
+            mask |= BLOB_NORTH_EAST;
+        }
+        if south && east && has_neighbour(1, -1) {
+            mask |= BLOB_SOUTH_EAST;
+        }
+        if south && west && has_neighbour(-1, -1) {
+            mask |= BLOB_SOUTH_WEST;
+        }
+        if north && west && has_neighbour(-1, 1) {
+            mask |= BLOB_NORTH_WEST;
+        }
+        mask
+    }
+}
+
+// The tile that was placed or removed, around which the auto-tiles need recalculating
+pub struct AutoTileTarget {
+    pub pos: Vec2,
+    pub size: Vec2,
+    pub layer: TileLayer,
+    pub sheet: String,
+    pub group: String,
+}
+
+impl AutoTileTarget {
+    pub fn from_serialized_object(serialized_object: &SerializedObject) -> Option<AutoTileTarget> {
+        let group = serialized_object.tile_group.clone()?;
+        match serialized_object.object_type {
+            SerializedObjectType::StaticTile { .. } => Some(AutoTileTarget {
+                pos: serialized_object.pos?,
+                size: serialized_object
+                    .size
+                    .unwrap_or(Vec2::new(TILE_WIDTH, TILE_HEIGHT)),
+                layer: serialized_object.layer.unwrap_or(TileLayer::default()),
+                sheet: serialized_object
+                    .sprite
+                    .clone()
+                    .unwrap_or(SpriteRenderData::default())
+                    .sheet,
+                group,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn from_editor_entity(world: &World, id: u32) -> Option<AutoTileTarget> {
+        let entity = world.entities().entity(id);
+        if !world.read_storage::<Tile>().contains(entity) {
+            return None;
+        }
+        Some(AutoTileTarget {
+            pos: world.read_storage::<Position>().get(entity)?.0,
+            size: world.read_storage::<SizeForEditorGrid>().get(entity)?.0,
+            layer: *world.read_storage::<TileLayer>().get(entity)?,
+            sheet: world.read_storage::<SpriteSheetId>().get(entity)?.0.clone(),
+            group: world.read_storage::<TileGroup>().get(entity)?.0.clone(),
+        })
+    }
+}
+
+fn grid_cell(pos: &Vec2, size: &Vec2) -> (i32, i32) {
+    (
+        (pos.x / size.x).round() as i32,
+        (pos.y / size.y).round() as i32,
+    )
+}
+
+// Recompute the sprite of the target tile and its eight neighbours on the same layer.
+// Must be called after `world.maintain()` so deleted tiles are no longer joined over.
+pub fn recalculate_auto_tiles_around(world: &mut World, target: &AutoTileTarget) {
+    let rules = {
+        let manifest = world.read_resource::<SpriteSheetManifest>();
+        let entry = manifest
+            .sprite_sheets
+            .iter()
+            .find(|entry| entry.id == target.sheet);
+        match entry.and_then(|entry| entry.auto_tile.get(&target.group).cloned()) {
+            Some(rules) => rules,
+            None => return,
+        }
+    };
+
+    // Every tile in the same group with the same sheet, layer and size, keyed by grid cell
+    let mut cells: HashMap<(i32, i32), (Entity, Vec2)> = HashMap::new();
+    for (entity, pos, size, layer, sheet, group, _, _) in (
+        &world.entities(),
+        &world.read_storage::<Position>(),
+        &world.read_storage::<SizeForEditorGrid>(),
+        &world.read_storage::<TileLayer>(),
+        &world.read_storage::<SpriteSheetId>(),
+        &world.read_storage::<TileGroup>(),
+        &world.read_storage::<EditorFlag>(),
+        &world.read_storage::<Tile>(),
+    )
+        .join()
+    {
+        if *layer as usize != target.layer as usize
+            || sheet.0 != target.sheet
+            || group.0 != target.group
+            || size.0 != target.size
+        {
+            continue;
+        }
+        cells.insert(grid_cell(&pos.0, &size.0), (entity, pos.0));
+    }
+
+    let (center_x, center_y) = grid_cell(&target.pos, &target.size);
+    let mut new_sprites = Vec::new();
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let cell = (center_x + dx, center_y + dy);
+            if let Some((entity, pos)) = cells.get(&cell) {
+                let sprite_number =
+                    rules.sprite_for(|x, y| cells.contains_key(&(cell.0 + x, cell.1 + y)));
+                new_sprites.push((*entity, *pos, sprite_number));
+            }
+        }
+    }
+
+    for (editor_entity, pos, sprite_number) in new_sprites {
+        set_tile_sprite(world, editor_entity, sprite_number);
+        if world.read_storage::<InTileChunk>().contains(editor_entity) {
+            set_chunk_tile(
+                world,
+                pos,
+                target.size,
+                target.layer,
+                &target.sheet,
+                Some(sprite_number),
+            );
+        }
+        let instance_id = world
+            .read_storage::<InstanceEntityId>()
+            .get(editor_entity)
+            .and_then(|instance| instance.0);
+        if let Some(instance_id) = instance_id {
+            let instance_entity = world.entities().entity(instance_id);
+            set_tile_sprite(world, instance_entity, sprite_number);
+        }
+    }
+}
+
+fn set_tile_sprite(world: &mut World, entity: Entity, sprite_number: usize) {
+    if let Some(sprite) = world.write_storage::<SpriteRender>().get_mut(entity) {
+        sprite.sprite_number = sprite_number;
+    }
+    if let Some(animation) = world.write_storage::<AnimatedTileComp>().get_mut(entity) {
+        animation.base_sprite = sprite_number;
+        animation.counter = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // Neighbours at the given offsets from the tile
+    fn neighbours(offsets: &[(i32, i32)]) -> impl Fn(i32, i32) -> bool {
+        let offsets: HashSet<(i32, i32)> = offsets.iter().cloned().collect();
+        move |x, y| offsets.contains(&(x, y))
+    }
+
+    fn everywhere() -> impl Fn(i32, i32) -> bool {
+        |x, y| (x, y) != (0, 0)
+    }
+
+    fn rules(kind: AutoTileKind, tiles: &[(u8, usize)]) -> AutoTileRules {
+        AutoTileRules {
+            kind,
+            tiles: tiles.iter().cloned().collect(),
+            fallback: 99,
+        }
+    }
+
+    #[test]
+    fn edge_mask_ignores_corners() {
+        let mask = AutoTileRules::edge_mask(&neighbours(&[(0, 1), (-1, 0), (1, 1), (-1, -1)]));
+        assert_eq!(mask, EDGE_NORTH | EDGE_WEST);
+        assert_eq!(AutoTileRules::edge_mask(&everywhere()), 15);
+        assert_eq!(AutoTileRules::edge_mask(&neighbours(&[])), 0);
+    }
+
+    #[test]
+    fn blob_mask_counts_corners_between_set_edges() {
+        let mask = AutoTileRules::blob_mask(&neighbours(&[(0, 1), (1, 0), (1, 1)]));
+        assert_eq!(mask, BLOB_NORTH | BLOB_EAST | BLOB_NORTH_EAST);
+        assert_eq!(AutoTileRules::blob_mask(&everywhere()), 255);
+    }
+
+    #[test]
+    fn blob_mask_drops_corners_without_both_edges() {
+        // Only one of the edges next to the corner
+        let mask = AutoTileRules::blob_mask(&neighbours(&[(0, 1), (1, 1)]));
+        assert_eq!(mask, BLOB_NORTH);
+        // Neither edge, so the corners on their own count for nothing
+        let corners = neighbours(&[(1, 1), (1, -1), (-1, -1), (-1, 1)]);
+        assert_eq!(AutoTileRules::blob_mask(&corners), 0);
+        let mask = AutoTileRules::blob_mask(&neighbours(&[(0, -1), (-1, 0), (-1, -1), (1, -1)]));
+        assert_eq!(mask, BLOB_SOUTH | BLOB_WEST | BLOB_SOUTH_WEST);
+    }
+
+    #[test]
+    fn sprites_are_looked_up_by_mask() {
+        let simple = rules(
+            AutoTileKind::Simple16,
+            &[(0, 1), (EDGE_EAST | EDGE_WEST, 2)],
+        );
+        assert_eq!(simple.sprite_for(neighbours(&[])), 1);
+        assert_eq!(simple.sprite_for(neighbours(&[(1, 0), (-1, 0), (1, 1)])), 2);
+
+        let blob = rules(AutoTileKind::Blob47, &[(255, 5)]);
+        assert_eq!(blob.sprite_for(everywhere()), 5);
+    }
+
+    #[test]
+    fn unknown_masks_use_the_fallback() {
+        let simple = rules(AutoTileKind::Simple16, &[(0, 1)]);
+        assert_eq!(simple.sprite_for(neighbours(&[(0, 1)])), 99);
+        // The same neighbours without the corner are a different blob tile
+        let blob = rules(AutoTileKind::Blob47, &[(255, 5)]);
+        assert_eq!(
+            blob.sprite_for(|x, y| (x, y) != (0, 0) && (x, y) != (1, 1)),
+            99
+        );
+    }
+}
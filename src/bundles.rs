@@ -1,4 +1,7 @@
 use crate::{
+    audio::AudioEventSystemDesc,
+    particles::ParticleEventSystemDesc,
+    projectiles::ProjectileHitSystemDesc,
     systems,
     systems::game::{EnemyCollisionSystemDesc, PlayerEventsSystemDesc},
 };
@@ -29,11 +32,37 @@ impl<'a, 'b> SystemBundle<'a, 'b> for GameLogicBundle {
             "invincibility_system",
             &["apply_velocity_system"],
         );
+        builder.add(
+            systems::game::FallDamageSystem,
+            "fall_damage_system",
+            &["apply_velocity_system"],
+        );
         builder.add(
             EnemyCollisionSystemDesc::default().build(world),
             "enemy_collision_system",
             &["invincibility_system"],
         );
+        // These hear about the hits that landed in the same frame
+        builder.add(
+            AudioEventSystemDesc::default().build(world),
+            "audio_event_system",
+            &["enemy_collision_system"],
+        );
+        builder.add(
+            ParticleEventSystemDesc::default().build(world),
+            "particle_event_system",
+            &["enemy_collision_system"],
+        );
+        builder.add(
+            ProjectileHitSystemDesc::default().build(world),
+            "projectile_hit_system",
+            &["invincibility_system"],
+        );
+        builder.add(
+            systems::game::HealthRegenSystem,
+            "health_regen_system",
+            &["enemy_collision_system"],
+        );
         builder.add(
             PlayerEventsSystemDesc::default().build(world),
             "player_events_system",
@@ -7,24 +7,33 @@ use amethyst::{
 };
 use derivative::Derivative;
 
-use crate::components::physics::Orientation;
+use crate::{components::physics::Orientation, states::loading::AssetsDir};
+use log::error;
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::File};
 
-#[derive(Derivative, Copy, Clone)]
+#[derive(Derivative, Debug, Copy, Clone, Serialize, Deserialize)]
 #[derivative(Default)]
+#[serde(default)]
 pub struct BasicWalkAi {
+    #[serde(skip)]
     pub orientation: Orientation,
+    // Turn around rather than walk off the edge of a platform
+    #[derivative(Default(value = "true"))]
+    pub turn_at_ledges: bool,
+    // Hop up one-tile steps rather than turn around at them
+    #[derivative(Default(value = "true"))]
+    pub jump_steps: bool,
 }
 impl Component for BasicWalkAi {
     type Storage = DenseVecStorage<Self>;
 }
 
+// Fires the actor's `Shooter` whenever it's ready
 #[derive(Default, Debug, Copy, Clone, Serialize, Deserialize)]
-pub struct BasicShootAi {
-    pub counter: f32,
-}
+pub struct BasicShootAi;
 impl Component for BasicShootAi {
-    type Storage = DenseVecStorage<Self>;
+    type Storage = NullStorage<Self>;
 }
 
 #[derive(Default, Debug, Copy, Clone, Serialize, Deserialize)]
@@ -34,3 +43,93 @@ pub struct BasicAttackAi {
 impl Component for BasicAttackAi {
     type Storage = DenseVecStorage<Self>;
 }
+
+pub const BEHAVIOURS: &str = "ai/behaviours.ron";
+
+// A node of a behaviour tree, run from the root every frame. Every node either succeeds or
+// fails: perception nodes check something about the world, actions try to do something.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BehaviourNode {
+    // Runs its children in order until one fails
+    Sequence(Vec<BehaviourNode>),
+    // Runs its children in order until one succeeds
+    Selector(Vec<BehaviourNode>),
+    Invert(Box<BehaviourNode>),
+    Succeed(Box<BehaviourNode>),
+
+    // The nearest good guy is within this many pixels
+    TargetWithin(f32),
+    // Nothing solid between us and the nearest good guy
+    TargetInSight,
+    // The nearest good guy is in front of us
+    TargetAhead,
+    // No ground in front of our feet
+    LedgeAhead,
+    // Something solid right in front of us
+    WallAhead,
+    // A wall in front of us that's only a tile high
+    StepAhead,
+    Grounded,
+
+    // Walk the way we're facing, turning at walls and optionally at ledges
+    Patrol { speed: f32, turn_at_ledges: bool },
+    Chase { speed: f32 },
+    // Chase across platforms, jumping and dropping between them. Fails without a way there.
+    PathToTarget { speed: f32 },
+    Flee { speed: f32 },
+    // Stop walking
+    Idle,
+    TurnAround,
+    FaceTarget,
+    // Fire the actor's shooter from the projectile library, fails while it's not ready
+    Fire,
+    // Swing in front of us, fails while cooling down
+    Melee { cooldown: f32 },
+    // Fails when not on the ground
+    Jump(f32),
+}
+
+// Every behaviour tree in `ai/behaviours.ron`, by name
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Behaviours(pub BTreeMap<String, BehaviourNode>);
+
+impl Behaviours {
+    pub fn load(world: &World) -> Behaviours {
+        let path = world.read_resource::<AssetsDir>().0.join(BEHAVIOURS);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open behaviours {:?}: {}", path, e);
+                return Behaviours::default();
+            }
+        };
+        match ron::de::from_reader(file) {
+            Ok(behaviours) => behaviours,
+            Err(e) => {
+                error!("Failed to parse behaviours {:?}: {}", path, e);
+                Behaviours::default()
+            }
+        }
+    }
+}
+
+// Runs the named tree from the behaviour library
+#[derive(Clone, Debug, Default)]
+pub struct BehaviourAi {
+    pub name: String,
+    // Seconds until the actor can swing again
+    pub(crate) melee_cooldown: f32,
+}
+
+impl BehaviourAi {
+    pub fn new(name: &str) -> BehaviourAi {
+        BehaviourAi {
+            name: String::from(name),
+            ..BehaviourAi::default()
+        }
+    }
+}
+
+impl Component for BehaviourAi {
+    type Storage = DenseVecStorage<Self>;
+}
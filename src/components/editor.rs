@@ -89,7 +89,7 @@ impl Component for InstanceEntityId {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Default, Clone, Debug, Copy)]
+#[derive(Default, Clone, Debug)]
 pub struct InsertionGameObject(pub SerializedObject);
 
 impl Component for InsertionGameObject {
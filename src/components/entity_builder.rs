@@ -1,30 +1,38 @@
 pub mod entity_builder {
+    use crate::level::LevelBackground;
     use crate::{
-        animations::{AnimationFactory, AnimationId},
+        abilities::{Abilities, AbilityLibrary},
+        animations::{AnimationFactory, AnimationId, DEFAULT_CHARACTER_ANIMATIONS},
         components::{
             ai,
-            ai::{BasicShootAi, BasicWalkAi},
+            ai::{BasicShootAi, BasicWalkAi, BehaviourAi},
             editor::{
                 CursorWasInThisEntity, EditorFlag, InsertionGameObject, InstanceEntityId,
                 RealCursorPosition, SizeForEditorGrid, TileLayer,
             },
             entity_builder::entity_builder,
             game::{
-                Damage, Health, Invincibility, Player, Projectile, Reflect, Resettable,
-                SerialHelper, SerializedObject, SerializedObjectType, SpriteRenderData, Team, Tile,
-                TimedExistence,
+                Activatable, Damage, Health, HitFlash, HitReaction, Invincibility, Knockback,
+                Player, Projectile, Reflect, Resettable, SerialHelper, SerializedObject,
+                SerializedObjectType, SpriteRenderData, Team, Tile, TimedExistence,
+                DEFAULT_MAX_HEALTH,
             },
             graphics::{
-                AnimationCounter, BackgroundParallax, CameraLimit, PulseAnimation, Scale,
-                SpriteSheetType,
+                AnimationCounter, AnimationStateMachine, BackgroundParallax, CameraLimit,
+                CharacterAnimationsId, InTileChunk, PulseAnimation, Scale, SpriteSheetId,
+                TileGroup, SPRITE_SHEET_ROLLING_HILLS_BG, SPRITE_SHEET_TILES,
             },
             physics::{
-                Collidee, GravityDirection, Grounded, PlatformCollisionPoints, PlatformCuboid,
-                Position, RTreeEntity, Sticky,
+                Collidee, GravityDirection, GravityZone, Grounded, PlatformCollisionPoints,
+                PlatformCuboid, Position, RTreeEntity, Sticky,
             },
         },
+        loot::{Loot, LootLibrary, PickupDef, DEFAULT_LOOT},
+        mechanisms::{Door, Switch},
+        projectiles::{ProjectileLibrary, ProjectileStats, Shooter},
+        scripting::Script,
         states::{
-            loading::AssetsDir,
+            loading::{get_sprite_sheet, AssetsDir},
             pizzatopia,
             pizzatopia::{
                 get_camera_center, CAM_HEIGHT, CAM_WIDTH, DEPTH_ACTORS, DEPTH_BACKGROUND,
@@ -32,7 +40,9 @@ pub mod entity_builder {
             },
         },
         systems::{editor::EditorButtonEventSystem, physics::CollisionDirection},
+        tile_chunks::set_chunk_tile,
         ui::file_picker::{FilePickerFilename, DIR_LEVELS},
+        utils::MaybeWith,
     };
     use amethyst::{
         animation::*,
@@ -64,7 +74,8 @@ pub mod entity_builder {
 
     use crate::components::ai::BasicAttackAi;
     use crate::components::game::{
-        AnimatedTile, AnimatedTileComp, Block, Drops, Gifts, PicksThingsUp, Pickup, Talks,
+        AnimatedTile, AnimatedTileComp, Block, DamageKind, Gifts, Inventory, OnDeath,
+        PicksThingsUp, Pickup, Resistances, Talks,
     };
     use crate::components::physics::{ChildTo, MoveIntent, Orientation, Velocity};
     use amethyst::ui::{FontAsset, UiText, UiTransform};
@@ -90,13 +101,14 @@ pub mod entity_builder {
             .get(entity)
             .unwrap()
             .clone();
-        let sprite_render = world
+        // Entities whose sprite sheet failed to load have no render to read the number from
+        let sprite_number = world
             .read_storage::<SpriteRender>()
             .get(entity)
-            .unwrap()
-            .clone();
-        let sprite_sheet_type = world
-            .read_storage::<SpriteSheetType>()
+            .map(|sprite_render| sprite_render.sprite_number)
+            .unwrap_or_default();
+        let sprite_sheet_id = world
+            .read_storage::<SpriteSheetId>()
             .get(entity)
             .unwrap()
             .clone();
@@ -109,11 +121,41 @@ pub mod entity_builder {
         let mut result: SerializedObject = SerializedObject::default();
         result.size = Some(size);
         result.pos = Some(position.0);
-        result.sprite = Some(SpriteRenderData::new(
-            sprite_sheet_type.clone(),
-            sprite_render.sprite_number,
-        ));
+        result.sprite = Some(SpriteRenderData::new(&sprite_sheet_id.0, sprite_number));
         result.layer = Some(layer);
+        result.animations = world
+            .read_storage::<CharacterAnimationsId>()
+            .get(entity)
+            .filter(|animations| animations.0 != DEFAULT_CHARACTER_ANIMATIONS)
+            .map(|animations| animations.0.clone());
+        result.dialogue = world
+            .read_storage::<Talks>()
+            .get(entity)
+            .and_then(|talks| talks.dialogue.clone());
+        result.script = world
+            .read_storage::<Script>()
+            .get(entity)
+            .map(|script| script.path.clone());
+        result.behaviour = world
+            .read_storage::<BehaviourAi>()
+            .get(entity)
+            .map(|ai| ai.name.clone());
+        result.walk = world.read_storage::<BasicWalkAi>().get(entity).cloned();
+        result.shooter = world
+            .read_storage::<Shooter>()
+            .get(entity)
+            .map(|shooter| shooter.name.clone());
+        result.team = world.read_storage::<Team>().get(entity).cloned();
+        result.damage = world.read_storage::<Damage>().get(entity).cloned();
+        result.resistances = world.read_storage::<Resistances>().get(entity).cloned();
+        result.loot = world
+            .read_storage::<Loot>()
+            .get(entity)
+            .map(|loot| loot.0.clone());
+        result.on_death = world
+            .read_storage::<OnDeath>()
+            .get(entity)
+            .map(|on_death| on_death.0.clone());
 
         match object_type {
             SerializedObjectType::StaticTile { animation } => {
@@ -123,26 +165,83 @@ pub mod entity_builder {
                 let is_player = world.read_storage::<Player>().get(entity).unwrap().clone();
                 result.object_type = SerializedObjectType::Player { is_player };
             }
+            SerializedObjectType::Door { .. }
+            | SerializedObjectType::Switch { .. }
+            | SerializedObjectType::GravityZone { .. } => {
+                result.object_type = object_type;
+            }
         };
+        result.tile_group = world
+            .read_storage::<TileGroup>()
+            .get(entity)
+            .map(|group| group.0.clone());
+        result.key = world
+            .read_storage::<Door>()
+            .get(entity)
+            .and_then(|door| door.key.clone());
+        result.gravity = world
+            .read_storage::<GravityDirection>()
+            .get(entity)
+            .map(|gravity| gravity.0);
+        let instance_id = world
+            .read_storage::<InstanceEntityId>()
+            .get(entity)
+            .and_then(|instance| instance.0);
+        if let Some(instance_id) = instance_id {
+            let instance = world.entities().entity(instance_id);
+            if let Some(activatable) = world.read_storage::<Activatable>().get(instance) {
+                result.respawn = match activatable.respawn {
+                    true => Some(true),
+                    false => None,
+                };
+            }
+            // Only the instance has health, the editor entity isn't something that can be hit
+            if let Some(health) = world.read_storage::<Health>().get(instance) {
+                result.max_health = Some(health.max).filter(|max| *max != DEFAULT_MAX_HEALTH);
+                result.health_regen = Some(health.regen).filter(|regen| *regen != 0.0);
+            }
+        }
         result
     }
 
+    // Returns the ID of the instance entity, if the object has one
     pub fn initialize_serialized_object(
         world: &mut World,
         serialized_object: &SerializedObject,
         ignore_editor: bool,
-    ) -> u32 {
+    ) -> Option<u32> {
         match serialized_object.object_type {
-            SerializedObjectType::Player { .. } => {
-                entity_builder::initialize_player(world, serialized_object, ignore_editor)
-            }
+            SerializedObjectType::Player { .. } => Some(entity_builder::initialize_player(
+                world,
+                serialized_object,
+                ignore_editor,
+            )),
             SerializedObjectType::StaticTile { .. } => {
-                entity_builder::initialize_ground(world, serialized_object)
+                entity_builder::initialize_ground(world, serialized_object, ignore_editor)
+                    .map(|entity| entity.id())
             }
+            SerializedObjectType::Door { .. } => Some(entity_builder::initialize_door(
+                world,
+                serialized_object,
+                ignore_editor,
+            )),
+            SerializedObjectType::Switch { .. } => Some(entity_builder::initialize_switch(
+                world,
+                serialized_object,
+                ignore_editor,
+            )),
+            SerializedObjectType::GravityZone { .. } => Some(
+                entity_builder::initialize_gravity_zone(world, serialized_object, ignore_editor),
+            ),
         }
     }
 
-    pub fn initialize_ground(world: &mut World, serialized_object: &SerializedObject) -> u32 {
+    // Returns the entity that collides or animates, if the tile needs one
+    pub fn initialize_ground(
+        world: &mut World,
+        serialized_object: &SerializedObject,
+        ignore_editor: bool,
+    ) -> Option<Entity> {
         let helper = SerialHelper::build(serialized_object, world);
 
         let tile_size = PlatformCuboid::create(helper.size.x, helper.size.y);
@@ -154,41 +253,254 @@ pub mod entity_builder {
         let animation = AnimatedTileComp {
             anim,
             counter: 0.0,
-            base_sprite: helper.sprite_render.sprite_number,
+            base_sprite: helper.sprite_number,
         };
 
-        // Create gameplay entity
+        let sheet = serialized_object
+            .sprite
+            .clone()
+            .unwrap_or(SpriteRenderData::default())
+            .sheet;
+
+        // Only animated tiles need to draw themselves, the rest are batched into chunks.
+        // Tiles without an editor entity can't be cleared from their chunk, so they draw themselves too.
+        let chunked = animation.anim.num_frames == 0 && !ignore_editor;
+        let instance = match (chunked, helper.layer) {
+            (false, _) => Some(
+                world
+                    .create_entity()
+                    .with(tile_size.clone())
+                    .with(animation)
+                    .with(Activatable::default())
+                    .with(Transparent)
+                    .with(helper.layer)
+                    .with(helper.pos)
+                    .with(helper.transform.clone())
+                    .maybe_with(helper.sprite_render.clone())
+                    .with(helper.scale.clone())
+                    .build(),
+            ),
+            // Middle layer tiles still collide, so they get a collider that draws nothing
+            (true, TileLayer::Middle) => Some(
+                world
+                    .create_entity()
+                    .with(tile_size.clone())
+                    .with(helper.layer)
+                    .with(helper.pos)
+                    .build(),
+            ),
+            (true, _) => None,
+        };
+        if chunked {
+            set_chunk_tile(
+                world,
+                helper.pos.0,
+                helper.size,
+                helper.layer,
+                &sheet,
+                Some(helper.sprite_number),
+            );
+        }
+        if ignore_editor {
+            return instance;
+        }
+
+        // create editor entity
+        let mut builder = world
+            .create_entity()
+            .with(serialized_object.object_type.clone())
+            .with(SpriteSheetId(sheet))
+            .maybe_with(serialized_object.tile_group.clone().map(TileGroup))
+            .with(InstanceEntityId(instance.map(|entity| entity.id())))
+            .with(Transparent)
+            .with(EditorFlag)
+            .with(Tile)
+            .with(helper.layer)
+            .with(helper.transform.clone())
+            .maybe_with(helper.sprite_render.clone())
+            .with(helper.pos)
+            .with(amethyst::core::Hidden)
+            .with(helper.scale.clone())
+            .with(SizeForEditorGrid(helper.size.clone()));
+        if chunked {
+            builder = builder.with(InTileChunk);
+        }
+        builder.build();
+
+        instance
+    }
+
+    pub fn initialize_door(
+        world: &mut World,
+        serialized_object: &SerializedObject,
+        ignore_editor: bool,
+    ) -> u32 {
+        let helper = SerialHelper::build(serialized_object, world);
+        let (link, open) = match serialized_object.object_type {
+            SerializedObjectType::Door { link, open } => (link, open),
+            _ => (None, false),
+        };
+        let door = Door {
+            key: serialized_object.key.clone(),
+            link,
+            open,
+        };
+        let sheet = serialized_object
+            .sprite
+            .clone()
+            .unwrap_or(SpriteRenderData::default())
+            .sheet;
+
+        // Open doors are left out of the collision tree when it's calculated
         let entity = world
             .create_entity()
-            .with(tile_size.clone())
-            .with(animation)
-            //.with(PlatformCuboid::new())
+            .with(PlatformCuboid::create(helper.size.x, helper.size.y))
+            .with(door.clone())
             .with(Transparent)
             .with(helper.layer)
             .with(helper.pos)
             .with(helper.transform.clone())
-            .with(helper.sprite_render.clone())
+            .maybe_with(helper.sprite_render.clone())
             .with(helper.scale.clone())
             .build();
+        if open {
+            world
+                .write_storage::<amethyst::core::Hidden>()
+                .insert(entity, amethyst::core::Hidden)
+                .expect("Failed to hide open door");
+        }
 
         // create editor entity
-        world
+        if !ignore_editor {
+            world
+                .create_entity()
+                .with(serialized_object.object_type.clone())
+                .with(SpriteSheetId(sheet))
+                .with(InstanceEntityId(Some(entity.id())))
+                .with(door)
+                .with(Transparent)
+                .with(EditorFlag)
+                .with(Resettable)
+                .with(helper.layer)
+                .with(helper.transform.clone())
+                .maybe_with(helper.sprite_render.clone())
+                .with(helper.pos)
+                .with(amethyst::core::Hidden)
+                .with(helper.scale.clone())
+                .with(SizeForEditorGrid(helper.size.clone()))
+                .build();
+        }
+        return entity.id();
+    }
+
+    pub fn initialize_switch(
+        world: &mut World,
+        serialized_object: &SerializedObject,
+        ignore_editor: bool,
+    ) -> u32 {
+        let helper = SerialHelper::build(serialized_object, world);
+        let (link, plate) = match serialized_object.object_type {
+            SerializedObjectType::Switch { link, plate } => (link, plate),
+            _ => (0, false),
+        };
+        let switch = Switch {
+            link,
+            plate,
+            on: false,
+            half_size: helper.size / 2.0,
+            base_sprite: helper.sprite_number,
+        };
+        let sheet = serialized_object
+            .sprite
+            .clone()
+            .unwrap_or(SpriteRenderData::default())
+            .sheet;
+
+        let entity = world
             .create_entity()
-            .with(serialized_object.object_type.clone())
-            .with(serialized_object.sprite.unwrap().sheet)
-            .with(InstanceEntityId(Some(entity.id())))
+            .with(switch.clone())
             .with(Transparent)
-            .with(EditorFlag)
-            .with(Tile)
             .with(helper.layer)
+            .with(helper.pos)
             .with(helper.transform.clone())
-            .with(helper.sprite_render.clone())
+            .maybe_with(helper.sprite_render.clone())
+            .with(helper.scale.clone())
+            .build();
+
+        // create editor entity
+        if !ignore_editor {
+            world
+                .create_entity()
+                .with(serialized_object.object_type.clone())
+                .with(SpriteSheetId(sheet))
+                .with(InstanceEntityId(Some(entity.id())))
+                .with(switch)
+                .with(Transparent)
+                .with(EditorFlag)
+                .with(Resettable)
+                .with(helper.layer)
+                .with(helper.transform.clone())
+                .maybe_with(helper.sprite_render.clone())
+                .with(helper.pos)
+                .with(amethyst::core::Hidden)
+                .with(helper.scale.clone())
+                .with(SizeForEditorGrid(helper.size.clone()))
+                .build();
+        }
+        return entity.id();
+    }
+
+    pub fn initialize_gravity_zone(
+        world: &mut World,
+        serialized_object: &SerializedObject,
+        ignore_editor: bool,
+    ) -> u32 {
+        let helper = SerialHelper::build(serialized_object, world);
+        let gravity = match serialized_object.object_type {
+            SerializedObjectType::GravityZone { gravity } => gravity,
+            _ => CollisionDirection::default(),
+        };
+        let sheet = serialized_object
+            .sprite
+            .clone()
+            .unwrap_or(SpriteRenderData::default())
+            .sheet;
+
+        // See-through, so what's inside the zone still shows
+        let entity = world
+            .create_entity()
+            .with(GravityZone {
+                gravity,
+                half_size: helper.size / 2.0,
+            })
+            .with(Tint(Srgba::new(1.0, 1.0, 1.0, 0.25).into()))
+            .with(Transparent)
+            .with(helper.layer)
             .with(helper.pos)
-            .with(amethyst::core::Hidden)
+            .with(helper.transform.clone())
+            .maybe_with(helper.sprite_render.clone())
             .with(helper.scale.clone())
-            .with(SizeForEditorGrid(helper.size.clone()))
             .build();
 
+        // create editor entity
+        if !ignore_editor {
+            world
+                .create_entity()
+                .with(serialized_object.object_type.clone())
+                .with(SpriteSheetId(sheet))
+                .with(InstanceEntityId(Some(entity.id())))
+                .with(Transparent)
+                .with(EditorFlag)
+                .with(Resettable)
+                .with(helper.layer)
+                .with(helper.transform.clone())
+                .maybe_with(helper.sprite_render.clone())
+                .with(helper.pos)
+                .with(amethyst::core::Hidden)
+                .with(helper.scale.clone())
+                .with(SizeForEditorGrid(helper.size.clone()))
+                .build();
+        }
         return entity.id();
     }
 
@@ -212,90 +524,228 @@ pub mod entity_builder {
 
         let collision_points =
             PlatformCollisionPoints::plus(helper.size.x / 2.25, helper.size.y / 2.25);
-        let animation = AnimationFactory::create_sprite_animation(world);
-        let attack_animation = AnimationFactory::create_bob(world, 10.0);
+        let animations_id = CharacterAnimationsId(
+            serialized_object
+                .animations
+                .clone()
+                .unwrap_or(String::from(DEFAULT_CHARACTER_ANIMATIONS)),
+        );
+        let (sprite_animations, transform_animations) =
+            AnimationFactory::create_character_animations(world, &animations_id.0);
 
         let scale = Scale(Vec2::new(1., 1.));
+        let health = Health {
+            regen: serialized_object.health_regen.unwrap_or(0.0),
+            ..Health::new(serialized_object.max_health.unwrap_or(DEFAULT_MAX_HEALTH))
+        };
+        let talks = Talks {
+            text: String::from("Hello!"),
+            dialogue: serialized_object.dialogue.clone(),
+        };
+        let team = serialized_object.team.unwrap_or(Team::Neutral);
+        let gravity = serialized_object.gravity.unwrap_or_default();
+        let damage = serialized_object
+            .damage
+            .unwrap_or(Damage(1, DamageKind::Contact));
+        let on_death = OnDeath(serialized_object.on_death.clone().unwrap_or_default());
+        let loot = Loot(
+            serialized_object
+                .loot
+                .clone()
+                .unwrap_or(String::from(DEFAULT_LOOT)),
+        );
+        let abilities = Abilities::new(&world.read_resource::<AbilityLibrary>());
+        let behaviour = serialized_object.behaviour.as_ref().filter(|_| !player);
+        let shooter = serialized_object
+            .shooter
+            .as_ref()
+            .filter(|_| !player)
+            .map(|name| Shooter::new(name, behaviour.is_none()));
+        let walk = serialized_object.walk.filter(|_| !player);
 
         // Data common to both editor and entity
         let mut builder = world
             .create_entity()
-            .with(animation)
-            .with(attack_animation)
+            .with(sprite_animations)
+            .with(transform_animations)
+            .with(AnimationStateMachine::new(&animations_id.0))
+            .with(animations_id.clone())
             .with(helper.transform.clone())
-            .with(helper.sprite_render.clone())
+            .maybe_with(helper.sprite_render.clone())
             .with(helper.pos.clone())
             .with(scale.clone())
             .with(helper.layer)
             .with(Orientation::default())
             .with(MoveIntent::default())
             .with(Transparent)
-            .with(GravityDirection(CollisionDirection::FromTop))
+            .with(GravityDirection(gravity))
             .with(Grounded(false))
             .with(Velocity::default())
             .with(collision_points)
             .with(Collidee::new())
-            .with(Health(5))
+            .with(health)
             .with(Invincibility(0.0));
         // .with(Sticky(false))
         if player {
             builder = builder
                 .with(Player(player))
                 .with(Team::GoodGuys)
-                .with(PicksThingsUp::default());
+                .with(PicksThingsUp::default())
+                .with(Inventory::default())
+                .with(abilities)
+                .with(HitReaction {
+                    flash: HitFlash::Blink(0.1),
+                    hit_stop: 0.1,
+                    shake: 12.0,
+                    ..HitReaction::default()
+                });
         } else {
             builder = builder
-                // .with(BasicWalkAi::default())
                 // .with(BasicShootAi::default())
                 // .with(BasicAttackAi::default())
-                .with(Team::Neutral)
-                .with(Talks {
-                    text: String::from("Hello!"),
-                })
+                .with(team)
+                .with(talks.clone())
                 .with(Gifts {
                     hearts: 2,
                     veggies: 2,
                 })
-                .with(Drops(10))
-                .with(Damage(1));
+                .with(on_death)
+                .with(loot.clone())
+                .with(damage)
+                .with(Knockback::default())
+                .with(HitReaction::default())
+                .with(Activatable {
+                    respawn: serialized_object.respawn.unwrap_or(false),
+                    spawn_pos: helper.pos.0,
+                    spawn_gravity: gravity,
+                    left: false,
+                });
         }
         let entity = builder.build();
-
-        if player {
-            initialize_shield(
-                world,
-                Some(entity),
-                &Vec2::new(helper.size.x / 2., helper.size.y / 4.),
-                &Vec2::new(TILE_WIDTH / 4., TILE_HEIGHT / 2.),
-                &Team::GoodGuys,
-            );
+        // Only non-player actors are driven by scripts
+        let script = serialized_object.script.as_ref().filter(|_| !player);
+        if let Some(path) = script {
+            world
+                .write_storage::<Script>()
+                .insert(entity, Script::new(path))
+                .expect("Failed to add script");
+        }
+        if let Some(name) = behaviour {
+            world
+                .write_storage::<BehaviourAi>()
+                .insert(entity, BehaviourAi::new(name))
+                .expect("Failed to add behaviour");
+        }
+        if let Some(shooter) = &shooter {
+            world
+                .write_storage::<Shooter>()
+                .insert(entity, shooter.clone())
+                .expect("Failed to add shooter");
+        }
+        if let Some(walk) = walk {
+            world
+                .write_storage::<BasicWalkAi>()
+                .insert(entity, walk)
+                .expect("Failed to add walk ai");
+        }
+        let resistances = serialized_object.resistances.as_ref();
+        if let Some(resistances) = resistances {
+            world
+                .write_storage::<Resistances>()
+                .insert(entity, resistances.clone())
+                .expect("Failed to add resistances");
         }
 
         // create editor entity
         if !ignore_editor {
-            world
+            let editor_entity = world
                 .create_entity()
                 .with(serialized_object.object_type.clone())
-                .with(
+                .with(SpriteSheetId(
                     serialized_object
                         .sprite
+                        .clone()
                         .unwrap_or(SpriteRenderData::default())
                         .sheet,
-                )
+                ))
                 .with(helper.transform.clone())
                 .with(Player(player))
                 .with(helper.layer)
-                .with(helper.sprite_render.clone())
+                .maybe_with(helper.sprite_render.clone())
                 .with(helper.pos)
                 .with(scale.clone())
                 .with(Transparent)
                 .with(Resettable)
                 .with(InstanceEntityId(Some(entity.id())))
                 .with(EditorFlag)
+                .with(animations_id)
+                .with(talks)
                 .with(SizeForEditorGrid(Vec2::new(helper.size.x, helper.size.y)))
                 // .with(Tint(Srgba::new(1.0, 1.0, 1.0, 0.5).into()))
                 .with(amethyst::core::Hidden)
                 .build();
+            if let Some(path) = script {
+                world
+                    .write_storage::<Script>()
+                    .insert(editor_entity, Script::new(path))
+                    .expect("Failed to add script");
+            }
+            if let Some(name) = behaviour {
+                world
+                    .write_storage::<BehaviourAi>()
+                    .insert(editor_entity, BehaviourAi::new(name))
+                    .expect("Failed to add behaviour");
+            }
+            if let Some(shooter) = shooter {
+                world
+                    .write_storage::<Shooter>()
+                    .insert(editor_entity, shooter)
+                    .expect("Failed to add shooter");
+            }
+            if let Some(walk) = walk {
+                world
+                    .write_storage::<BasicWalkAi>()
+                    .insert(editor_entity, walk)
+                    .expect("Failed to add walk ai");
+            }
+            // Only kept when set, the editor entity has no physics for it to affect
+            if serialized_object.gravity.is_some() {
+                world
+                    .write_storage::<GravityDirection>()
+                    .insert(editor_entity, GravityDirection(gravity))
+                    .expect("Failed to add gravity");
+            }
+            if serialized_object.team.is_some() && !player {
+                world
+                    .write_storage::<Team>()
+                    .insert(editor_entity, team)
+                    .expect("Failed to add team");
+            }
+            // Only kept when set, so saving doesn't write out the defaults
+            if serialized_object.damage.is_some() && !player {
+                world
+                    .write_storage::<Damage>()
+                    .insert(editor_entity, damage)
+                    .expect("Failed to add damage");
+            }
+            if let Some(resistances) = resistances {
+                world
+                    .write_storage::<Resistances>()
+                    .insert(editor_entity, resistances.clone())
+                    .expect("Failed to add resistances");
+            }
+            if serialized_object.loot.is_some() && !player {
+                world
+                    .write_storage::<Loot>()
+                    .insert(editor_entity, loot)
+                    .expect("Failed to add loot");
+            }
+            if let Some(on_death) = serialized_object.on_death.as_ref().filter(|_| !player) {
+                world
+                    .write_storage::<OnDeath>()
+                    .insert(editor_entity, OnDeath(on_death.clone()))
+                    .expect("Failed to add death hooks");
+            }
         }
         return entity.id();
     }
@@ -307,23 +757,27 @@ pub mod entity_builder {
         // Correctly position the tile.
         let pos = Position(Vec2::new(CAM_WIDTH / 2.0, CAM_HEIGHT / 2.0));
 
-        let sprite_sheet = world.read_resource::<BTreeMap<u8, Handle<SpriteSheet>>>()
-            [&(SpriteSheetType::RollingHillsBg as u8)]
-            .clone();
-
-        for chain_num in 0..2 {
-            for i in 0..4 {
-                // Assign the sprite
-                let sprite_render = SpriteRender {
-                    sprite_sheet: sprite_sheet.clone(),
-                    sprite_number: i, // grass is the first sprite in the sprite_sheet
-                };
-                let z = DEPTH_BACKGROUND - 0.1 * i as f32;
-                let mut transform = Transform::default();
-                transform.set_translation_xyz(pos.0.x, pos.0.y, z);
-                transform.set_scale(Vector3::new(scale.0.x, scale.0.y, 1.0));
+        let layers = world.read_resource::<LevelBackground>().0.clone();
+        for (i, layer) in layers.into_iter().enumerate() {
+            // Assign the sprite
+            let sprite_render = match get_sprite_sheet(world, &layer.sheet) {
+                Some(sprite_sheet) => SpriteRender {
+                    sprite_sheet,
+                    sprite_number: layer.sprite,
+                },
+                None => continue,
+            };
+            let z = DEPTH_BACKGROUND - 0.1 * i as f32;
+            let mut transform = Transform::default();
+            transform.set_translation_xyz(pos.0.x, pos.0.y, z);
+            transform.set_scale(Vector3::new(scale.0.x, scale.0.y, 1.0));
 
-                // Create gameplay entity
+            // Tiled layers need a second copy to fill in while the first wraps around
+            let chain_length = match layer.tiled {
+                true => 2,
+                false => 1,
+            };
+            for chain in 0..chain_length {
                 world
                     .create_entity()
                     .with(Transparent)
@@ -331,7 +785,11 @@ pub mod entity_builder {
                     .with(pos.clone())
                     .with(scale.clone())
                     .with(sprite_render.clone())
-                    .with(BackgroundParallax(i as u32, chain_num))
+                    .with(BackgroundParallax {
+                        layer: layer.clone(),
+                        chain,
+                        scroll: 0.0,
+                    })
                     .build();
             }
         }
@@ -341,21 +799,27 @@ pub mod entity_builder {
         let mut transform = Transform::default();
         transform.set_translation_xyz(pos.x, pos.y, DEPTH_PROJECTILES);
 
-        let sprite_sheet_type = SpriteSheetType::Tiles;
-        let sprite_sheet = world.read_resource::<BTreeMap<u8, Handle<SpriteSheet>>>()
-            [&(sprite_sheet_type as u8)]
-            .clone();
-
-        let sprite_number = match kind {
-            Pickup::Heart => 5,
-            Pickup::Veggie => 2,
+        // Keys are rare, they stay around until collected
+        let (sheet, sprite_number, lifetime) = match &kind {
+            Pickup::Key(_) => (String::from(SPRITE_SHEET_TILES), 1, None),
+            Pickup::Item(name) => {
+                let def = world
+                    .read_resource::<LootLibrary>()
+                    .pickups
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_else(|| {
+                        error!("Unknown pickup {:?}", name);
+                        PickupDef::default()
+                    });
+                (def.sheet, def.sprite, def.lifetime)
+            }
         };
-
         // Assign the sprite
-        let sprite_render = SpriteRender {
-            sprite_sheet: sprite_sheet.clone(),
+        let sprite_render = get_sprite_sheet(world, &sheet).map(|sprite_sheet| SpriteRender {
+            sprite_sheet,
             sprite_number,
-        };
+        });
 
         let position = Position(Vec2::new(pos.x, pos.y));
 
@@ -369,40 +833,48 @@ pub mod entity_builder {
         let entity = world
             .create_entity()
             .with(transform.clone())
-            .with(sprite_render.clone())
+            .maybe_with(sprite_render.clone())
             .with(position.clone())
             .with(scale.clone())
             .with(Transparent)
             .with(velocity)
             .with(collision_points)
             .with(Collidee::new())
-            .with(TimedExistence(10.0))
             .with(Team::Neutral)
             .with(GravityDirection::default())
             .with(Grounded(false))
             .with(kind)
             .build();
+        if let Some(lifetime) = lifetime {
+            world
+                .write_storage::<TimedExistence>()
+                .insert(entity, TimedExistence(lifetime))
+                .expect("Failed to add lifetime to pickup");
+        }
 
         return entity.id();
     }
 
-    pub fn initialize_projectile(world: &mut World, pos: &Vec2, vel: &Vec2, team: &Team) -> u32 {
+    pub fn initialize_projectile(
+        world: &mut World,
+        kind: &str,
+        pos: &Vec2,
+        vel: &Vec2,
+        team: &Team,
+    ) -> u32 {
+        let def = world.read_resource::<ProjectileLibrary>().projectile(kind);
         let mut transform = Transform::default();
         transform.set_translation_xyz(pos.x, pos.y, DEPTH_PROJECTILES);
 
-        let sprite_sheet_type = SpriteSheetType::Tiles;
-        let sprite_sheet = world.read_resource::<BTreeMap<u8, Handle<SpriteSheet>>>()
-            [&(sprite_sheet_type as u8)]
-            .clone();
         // Assign the sprite
-        let sprite_render = SpriteRender {
-            sprite_sheet: sprite_sheet.clone(),
-            sprite_number: 5,
-        };
+        let sprite_render = get_sprite_sheet(world, &def.sheet).map(|sprite_sheet| SpriteRender {
+            sprite_sheet,
+            sprite_number: def.sprite,
+        });
 
         let position = Position(Vec2::new(pos.x, pos.y));
 
-        let size = Vec2::new(TILE_WIDTH / 3.0, TILE_HEIGHT / 3.0);
+        let size = def.size;
         let scale = Scale(Vec2::new(size.x / TILE_WIDTH, size.y / TILE_HEIGHT));
         let collision_points = PlatformCollisionPoints::plus(size.x / 2.25, size.y / 2.25);
         let mut velocity = Velocity::default();
@@ -412,7 +884,7 @@ pub mod entity_builder {
         let entity = world
             .create_entity()
             .with(transform.clone())
-            .with(sprite_render.clone())
+            .maybe_with(sprite_render.clone())
             .with(position.clone())
             .with(scale.clone())
             .with(Transparent)
@@ -420,9 +892,11 @@ pub mod entity_builder {
             .with(collision_points)
             .with(Collidee::new())
             .with(Projectile)
-            .with(TimedExistence(10.0))
+            .with(ProjectileStats::new(&def))
+            .with(TimedExistence(def.lifetime))
             .with(team.clone())
-            .with(Damage(1))
+            .with(Damage(def.damage, DamageKind::Projectile))
+            .with(Knockback(def.knockback))
             .build();
 
         return entity.id();
@@ -438,15 +912,12 @@ pub mod entity_builder {
         let mut transform = Transform::default();
         transform.set_translation_xyz(pos.x, pos.y, DEPTH_PROJECTILES);
 
-        let sprite_sheet_type = SpriteSheetType::Tiles;
-        let sprite_sheet = world.read_resource::<BTreeMap<u8, Handle<SpriteSheet>>>()
-            [&(sprite_sheet_type as u8)]
-            .clone();
         // Assign the sprite
-        let sprite_render = SpriteRender {
-            sprite_sheet: sprite_sheet.clone(),
-            sprite_number: 0,
-        };
+        let sprite_render =
+            get_sprite_sheet(world, SPRITE_SHEET_TILES).map(|sprite_sheet| SpriteRender {
+                sprite_sheet,
+                sprite_number: 0,
+            });
 
         let position = Position(Vec2::new(pos.x, pos.y));
 
@@ -458,7 +929,7 @@ pub mod entity_builder {
         let mut entity = world
             .create_entity()
             .with(transform.clone())
-            .with(sprite_render.clone())
+            .maybe_with(sprite_render.clone())
             .with(position.clone())
             .with(scale.clone())
             .with(Transparent)
@@ -489,15 +960,12 @@ pub mod entity_builder {
         let mut transform = Transform::default();
         transform.set_translation_xyz(pos.x, pos.y, DEPTH_PROJECTILES);
 
-        let sprite_sheet_type = SpriteSheetType::Tiles;
-        let sprite_sheet = world.read_resource::<BTreeMap<u8, Handle<SpriteSheet>>>()
-            [&(sprite_sheet_type as u8)]
-            .clone();
         // Assign the sprite
-        let sprite_render = SpriteRender {
-            sprite_sheet: sprite_sheet.clone(),
-            sprite_number: 5,
-        };
+        let sprite_render =
+            get_sprite_sheet(world, SPRITE_SHEET_TILES).map(|sprite_sheet| SpriteRender {
+                sprite_sheet,
+                sprite_number: 5,
+            });
 
         let position = Position(Vec2::new(pos.x, pos.y));
 
@@ -509,7 +977,7 @@ pub mod entity_builder {
         let mut entity = world
             .create_entity()
             .with(transform.clone())
-            .with(sprite_render.clone())
+            .maybe_with(sprite_render.clone())
             .with(position.clone())
             .with(scale.clone())
             .with(Transparent)
@@ -520,7 +988,8 @@ pub mod entity_builder {
             .with(Collidee::new())
             .with(TimedExistence(0.2))
             .with(team.clone())
-            .with(Damage(1));
+            .with(Damage(1, DamageKind::Contact))
+            .with(Knockback(Vec2::new(12., 6.)));
         if let Some(parent) = parent {
             let parent = ChildTo {
                 parent,
@@ -542,14 +1011,12 @@ pub mod entity_builder {
         let pos = Position(get_camera_center(world));
         transform.set_translation_z(DEPTH_UI);
 
-        let sprite_sheet = world.read_resource::<BTreeMap<u8, Handle<SpriteSheet>>>()
-            [&(SpriteSheetType::Tiles as u8)]
-            .clone();
         // Assign the sprite
-        let sprite_render = SpriteRender {
-            sprite_sheet: sprite_sheet.clone(),
-            sprite_number: 4,
-        };
+        let sprite_render =
+            get_sprite_sheet(world, SPRITE_SHEET_TILES).map(|sprite_sheet| SpriteRender {
+                sprite_sheet,
+                sprite_number: 4,
+            });
 
         // Create cursor
         world
@@ -566,7 +1033,7 @@ pub mod entity_builder {
             )))
             .with(CursorWasInThisEntity(None))
             .with(transform.clone())
-            .with(sprite_render.clone())
+            .maybe_with(sprite_render.clone())
             .with(pos.clone())
             .with(Transparent)
             .build();
@@ -1,14 +1,19 @@
 use crate::{
     components::{
+        ai::BasicWalkAi,
         editor::TileLayer,
-        graphics::{Scale, SpriteSheetType},
+        graphics::{
+            Scale, SPRITE_SHEET_ANIMATION, SPRITE_SHEET_DIDI, SPRITE_SHEET_ROLLING_HILLS_BG,
+            SPRITE_SHEET_SNAP, SPRITE_SHEET_TILES, SPRITE_SHEET_UI,
+        },
         physics::Position,
     },
     states::{
         editor::EDITOR_GRID_SIZE,
+        loading::get_sprite_sheet,
         pizzatopia::{DEPTH_ACTORS, DEPTH_TILES, TILE_HEIGHT, TILE_WIDTH},
     },
-    systems::editor::align_cursor_position_with_grid,
+    systems::{editor::align_cursor_position_with_grid, physics::CollisionDirection},
 };
 use amethyst::{
     assets::{AssetStorage, Handle, Loader},
@@ -26,7 +31,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use ultraviolet::Vec2;
 
-#[derive(Derivative, Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Derivative, Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[derivative(Default)]
 pub enum Team {
     GoodGuys,
@@ -41,8 +46,8 @@ impl Component for Team {
 
 #[derive(Debug, Clone)]
 pub enum CollisionEvent {
-    // (Entity getting hit id, entity hitting id, damage dealt)
-    EnemyCollision(u32, u32, u32),
+    // (Entity getting hit id, entity hitting id, damage dealt, kind of damage)
+    EnemyCollision(u32, u32, u32, DamageKind),
     ProjectileReflection(u32, Team),
     ProjectileBlock(u32),
     // Picker, pickee
@@ -58,10 +63,12 @@ impl Component for Player {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Pickup {
-    Veggie,
-    Heart,
+    // Pickup kind in the loot file
+    Item(String),
+    // Goes into the picker's inventory, opens doors that need a key of that name
+    Key(String),
 }
 impl Component for Pickup {
     type Storage = DenseVecStorage<Self>;
@@ -75,9 +82,50 @@ impl Component for PicksThingsUp {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Default, Debug, Copy, Clone, Serialize, Deserialize)]
-pub struct Drops(pub u32);
-impl Component for Drops {
+// Keys the player is carrying, by name
+#[derive(Default, Debug, Clone)]
+pub struct Inventory {
+    pub keys: BTreeMap<String, u32>,
+}
+
+impl Inventory {
+    pub fn add_key(&mut self, name: &str) {
+        *self.keys.entry(String::from(name)).or_insert(0) += 1;
+    }
+
+    // Uses up one of the keys, false if there aren't any left
+    pub fn take_key(&mut self, name: &str) -> bool {
+        match self.keys.get_mut(name) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                if *count == 0 {
+                    self.keys.remove(name);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Component for Inventory {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Run when the entity's health reaches zero
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum DeathHook {
+    // Scatters a roll of the named loot table
+    DropLoot(String),
+    SetFlag(String),
+    AddValue(String, i32),
+    // Creates the object without an editor counterpart, it goes away when the level resets
+    Spawn(SerializedObject),
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct OnDeath(pub Vec<DeathHook>);
+impl Component for OnDeath {
     type Storage = DenseVecStorage<Self>;
 }
 
@@ -111,15 +159,103 @@ impl Component for Projectile {
     type Storage = NullStorage<Self>;
 }
 
+#[derive(
+    Derivative, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[derivative(Default)]
+pub enum DamageKind {
+    // Bodies and melee attacks
+    #[derivative(Default)]
+    Contact,
+    Projectile,
+    // Spikes and the like
+    Hazard,
+    // Landing too hard
+    Fall,
+    // Scripts lowering an actor's health
+    Scripted,
+}
+
 #[derive(Default, Debug, Copy, Clone, Serialize, Deserialize)]
-pub struct Damage(pub u32);
+pub struct Damage(pub u32, pub DamageKind);
 impl Component for Damage {
     type Storage = DenseVecStorage<Self>;
 }
 
+// What damage of each kind is multiplied by, above 1 for weaknesses and 0 for immunities
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Resistances(pub BTreeMap<DamageKind, f32>);
+impl Resistances {
+    pub fn apply(&self, amount: u32, kind: DamageKind) -> u32 {
+        let multiplier = self.0.get(&kind).cloned().unwrap_or(1.0).max(0.0);
+        (amount as f32 * multiplier).round() as u32
+    }
+}
+impl Component for Resistances {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Velocity given to whoever a `Damage` source hits, pointing away from the source horizontally
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+pub struct Knockback(#[derivative(Default(value = "Vec2::new(8., 4.)"))] pub Vec2);
+impl Component for Knockback {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum HitFlash {
+    None,
+    // Red, green, blue and alpha to tint the character while it's invincible
+    Tint(f32, f32, f32, f32),
+    // Flicker while invincible, seconds per blink
+    Blink(f32),
+}
+
+// How a character reacts to getting hit
+#[derive(Debug, Clone, Derivative)]
+#[derivative(Default)]
+pub struct HitReaction {
+    #[derivative(Default(value = "HitFlash::Tint(1.0, 0.3, 0.3, 1.0)"))]
+    pub flash: HitFlash,
+    // Seconds the whole game freezes for
+    #[derivative(Default(value = "0.05"))]
+    pub hit_stop: f32,
+    // How far the camera shakes, in pixels
+    #[derivative(Default(value = "4.0"))]
+    pub shake: f32,
+    // Whether the flash is showing, so the tint can be reset once it's over
+    pub(crate) flashing: bool,
+}
+impl Component for HitReaction {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Default)]
+pub struct HitStop {
+    // Real time left in which the game is frozen after a hit
+    pub(crate) seconds: f32,
+    // Time scale from before the game froze, put back once it's over
+    pub(crate) resume_scale: Option<f32>,
+}
+
+impl HitStop {
+    pub fn active(&self) -> bool {
+        self.seconds > 0.0
+    }
+
+    pub fn add(&mut self, seconds: f32) {
+        self.seconds = self.seconds.max(seconds);
+    }
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Talks {
+    // Said when there's no dialogue, or it can't be found
     pub text: String,
+    // Name of a dialogue in `dialogue/dialogues.ron`
+    #[serde(default)]
+    pub dialogue: Option<String>,
 }
 impl Component for Talks {
     type Storage = DenseVecStorage<Self>;
@@ -140,8 +276,67 @@ impl Component for Resettable {
     type Storage = NullStorage<Self>;
 }
 
-#[derive(Default)]
-pub struct Health(pub u32);
+// Entities that go dormant when they are too far from the camera
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Activatable {
+    // Put the entity back where it started, with full health, when it wakes up
+    pub respawn: bool,
+    pub spawn_pos: Vec2,
+    pub spawn_gravity: CollisionDirection,
+    // Whether it's been out of range since it went dormant, so the dead don't come back in sight
+    pub(crate) left: bool,
+}
+impl Component for Activatable {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Skipped by simulation systems and hidden until the entity is near the camera again
+#[derive(Default, Debug, Copy, Clone)]
+pub struct Dormant;
+impl Component for Dormant {
+    type Storage = NullStorage<Self>;
+}
+
+pub const DEFAULT_MAX_HEALTH: u32 = 5;
+
+#[derive(Default, Debug, Clone)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+    // Health regained per second while alive
+    pub regen: f32,
+    // Partial health regained so far
+    pub(crate) regen_progress: f32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Health {
+        Health {
+            current: max,
+            max,
+            ..Health::default()
+        }
+    }
+
+    pub fn alive(&self) -> bool {
+        self.current > 0
+    }
+
+    // Returns how much was actually healed
+    pub fn heal(&mut self, amount: u32) -> u32 {
+        let healed = amount.min(self.max.saturating_sub(self.current));
+        self.current += healed;
+        healed
+    }
+
+    // Returns how much was actually taken
+    pub fn hurt(&mut self, amount: u32) -> u32 {
+        let taken = amount.min(self.current);
+        self.current -= taken;
+        taken
+    }
+}
+
 impl Component for Health {
     type Storage = DenseVecStorage<Self>;
 }
@@ -165,20 +360,77 @@ impl Component for CameraTarget {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, Derivative)]
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
 #[derivative(Default)]
+#[serde(from = "SpriteRenderDataRepr")]
 pub struct SpriteRenderData {
-    pub(crate) sheet: SpriteSheetType,
+    #[serde(rename = "sheet_id")]
+    #[derivative(Default(value = "String::from(SPRITE_SHEET_TILES)"))]
+    pub(crate) sheet: String,
     pub(crate) number: usize,
 }
 
 impl SpriteRenderData {
-    pub fn new(sheet: SpriteSheetType, number: usize) -> Self {
-        SpriteRenderData { sheet, number }
+    pub fn new(sheet: &str, number: usize) -> Self {
+        SpriteRenderData {
+            sheet: String::from(sheet),
+            number,
+        }
     }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, Derivative)]
+// Sprite sheets as they were named before the sprite sheet manifest existed.
+// Only used to read level files that still store `sheet: Tiles` and friends.
+#[derive(Clone, Copy, Debug, Deserialize, Derivative)]
+#[derivative(Default)]
+enum LegacySpriteSheetType {
+    #[derivative(Default)]
+    Unset,
+    Tiles,
+    Didi,
+    Snap,
+    Ui,
+    Animation,
+    RollingHillsBg,
+}
+
+impl LegacySpriteSheetType {
+    fn to_sheet_id(&self) -> Option<&'static str> {
+        match self {
+            LegacySpriteSheetType::Unset => None,
+            LegacySpriteSheetType::Tiles => Some(SPRITE_SHEET_TILES),
+            LegacySpriteSheetType::Didi => Some(SPRITE_SHEET_DIDI),
+            LegacySpriteSheetType::Snap => Some(SPRITE_SHEET_SNAP),
+            LegacySpriteSheetType::Ui => Some(SPRITE_SHEET_UI),
+            LegacySpriteSheetType::Animation => Some(SPRITE_SHEET_ANIMATION),
+            LegacySpriteSheetType::RollingHillsBg => Some(SPRITE_SHEET_ROLLING_HILLS_BG),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SpriteRenderDataRepr {
+    #[serde(default)]
+    sheet_id: String,
+    #[serde(default)]
+    sheet: LegacySpriteSheetType,
+    number: usize,
+}
+
+impl From<SpriteRenderDataRepr> for SpriteRenderData {
+    fn from(repr: SpriteRenderDataRepr) -> Self {
+        let sheet = match repr.sheet_id.is_empty() {
+            false => repr.sheet_id,
+            true => String::from(repr.sheet.to_sheet_id().unwrap_or(SPRITE_SHEET_TILES)),
+        };
+        SpriteRenderData {
+            sheet,
+            number: repr.number,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
 #[derivative(Default)]
 pub struct SerializedObject {
     pub(crate) object_type: SerializedObjectType,
@@ -190,6 +442,57 @@ pub struct SerializedObject {
     pub(crate) sprite: Option<SpriteRenderData>,
     #[derivative(Default(value = "Some(TileLayer::Middle)"))]
     pub(crate) layer: Option<TileLayer>,
+    #[serde(default)]
+    pub(crate) respawn: Option<bool>,
+    // Character in the animation library, actors use the default one when unset
+    #[serde(default)]
+    pub(crate) animations: Option<String>,
+    // Dialogue in the dialogue file that non-player actors start when talked to
+    #[serde(default)]
+    pub(crate) dialogue: Option<String>,
+    // Rhai script driving a non-player actor, relative to the assets directory
+    #[serde(default)]
+    pub(crate) script: Option<String>,
+    // Behaviour tree in the behaviour file driving a non-player actor
+    #[serde(default)]
+    pub(crate) behaviour: Option<String>,
+    // Makes a non-player actor walk back and forth on its own
+    #[serde(default)]
+    pub(crate) walk: Option<BasicWalkAi>,
+    // Shooter in the projectile library a non-player actor fires, on its own unless it has a
+    // behaviour to decide when
+    #[serde(default)]
+    pub(crate) shooter: Option<String>,
+    // Team of a non-player actor, neutral when unset
+    #[serde(default)]
+    pub(crate) team: Option<Team>,
+    // Damage a non-player actor does by touching things, 1 contact damage when unset
+    #[serde(default)]
+    pub(crate) damage: Option<Damage>,
+    // Health an actor starts with and heals up to, DEFAULT_MAX_HEALTH when unset
+    #[serde(default)]
+    pub(crate) max_health: Option<u32>,
+    // Health an actor regains per second, none when unset
+    #[serde(default)]
+    pub(crate) health_regen: Option<f32>,
+    #[serde(default)]
+    pub(crate) resistances: Option<Resistances>,
+    // What else happens when a non-player actor dies
+    #[serde(default)]
+    pub(crate) on_death: Option<Vec<DeathHook>>,
+    // Loot table in the loot file a non-player actor drops when it dies, the default one when
+    // unset
+    #[serde(default)]
+    pub(crate) loot: Option<String>,
+    // Key a door needs the player to be carrying to open it
+    #[serde(default)]
+    pub(crate) key: Option<String>,
+    // Gravity an actor starts with, pulling down when unset
+    #[serde(default)]
+    pub(crate) gravity: Option<CollisionDirection>,
+    // Auto-tile group of a static tile in its sprite sheet's manifest entry, left alone when unset
+    #[serde(default)]
+    pub(crate) tile_group: Option<String>,
 }
 
 impl SerializedObject {
@@ -249,6 +552,21 @@ pub enum SerializedObjectType {
     Player {
         is_player: Player,
     },
+    // Solid until opened, by its key or by a switch or trigger with the same link
+    Door {
+        link: Option<u32>,
+        open: bool,
+    },
+    // Toggles the doors with the same link. Pressure plates toggle them when something steps on
+    // and again when it steps off, levers when the player interacts with them.
+    Switch {
+        link: u32,
+        plate: bool,
+    },
+    // Changes the gravity of whatever comes into it
+    GravityZone {
+        gravity: CollisionDirection,
+    },
 }
 
 impl Component for SerializedObjectType {
@@ -257,7 +575,8 @@ impl Component for SerializedObjectType {
 
 pub struct SerialHelper {
     pub(crate) transform: Transform,
-    pub(crate) sprite_render: SpriteRender,
+    pub(crate) sprite_render: Option<SpriteRender>,
+    pub(crate) sprite_number: usize,
     pub(crate) layer: TileLayer,
     pub(crate) pos: Position,
     pub(crate) scale: Scale,
@@ -266,14 +585,13 @@ pub struct SerialHelper {
 
 impl SerialHelper {
     pub fn build(so: &SerializedObject, world: &mut World) -> SerialHelper {
-        let sprite = so.sprite.unwrap_or(SpriteRenderData::default());
-        let sprite_sheet = world.read_resource::<BTreeMap<u8, Handle<SpriteSheet>>>()
-            [&(sprite.sheet as u8)]
-            .clone();
-        let sprite_render = SpriteRender {
-            sprite_sheet: sprite_sheet.clone(),
-            sprite_number: sprite.number,
-        };
+        let sprite = so.sprite.clone().unwrap_or(SpriteRenderData::default());
+        let sprite_number = sprite.number;
+        let sprite_render =
+            get_sprite_sheet(world, &sprite.sheet).map(|sprite_sheet| SpriteRender {
+                sprite_sheet,
+                sprite_number,
+            });
 
         let layer = so.layer.unwrap_or(TileLayer::default());
 
@@ -282,7 +600,10 @@ impl SerialHelper {
         let z = layer.to_z_offset()
             + match so.object_type {
                 SerializedObjectType::Player { .. } => DEPTH_ACTORS,
-                SerializedObjectType::StaticTile { .. } => DEPTH_TILES,
+                SerializedObjectType::StaticTile { .. }
+                | SerializedObjectType::Door { .. }
+                | SerializedObjectType::Switch { .. }
+                | SerializedObjectType::GravityZone { .. } => DEPTH_TILES,
             };
 
         // Build tile using GameObject
@@ -295,6 +616,7 @@ impl SerialHelper {
 
         SerialHelper {
             sprite_render,
+            sprite_number,
             layer,
             pos,
             scale,
@@ -303,3 +625,84 @@ impl SerialHelper {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite(ron: &str) -> SpriteRenderData {
+        ron::de::from_str(ron).expect("Failed to read sprite")
+    }
+
+    #[test]
+    fn legacy_sheets_are_migrated() {
+        assert_eq!(sprite("(sheet: Didi, number: 3)").sheet, SPRITE_SHEET_DIDI);
+        assert_eq!(sprite("(sheet: Snap, number: 3)").sheet, SPRITE_SHEET_SNAP);
+        assert_eq!(sprite("(sheet: Ui, number: 3)").sheet, SPRITE_SHEET_UI);
+        let background = sprite("(sheet: RollingHillsBg, number: 0)");
+        assert_eq!(background.sheet, SPRITE_SHEET_ROLLING_HILLS_BG);
+        assert_eq!(sprite("(sheet: Tiles, number: 3)").number, 3);
+    }
+
+    #[test]
+    fn sheet_ids_win_over_legacy_sheets() {
+        let sprite = sprite("(sheet_id: \"props\", sheet: Didi, number: 1)");
+        assert_eq!(sprite.sheet, "props");
+    }
+
+    #[test]
+    fn unset_sheets_default_to_tiles() {
+        assert_eq!(sprite("(number: 1)").sheet, SPRITE_SHEET_TILES);
+        assert_eq!(
+            sprite("(sheet: Unset, number: 1)").sheet,
+            SPRITE_SHEET_TILES
+        );
+    }
+
+    #[test]
+    fn sprites_keep_their_sheet_id_through_saving() {
+        let saved = ron::ser::to_string(&SpriteRenderData::new("props", 2)).unwrap();
+        let loaded = sprite(&saved);
+        assert_eq!(loaded.sheet, "props");
+        assert_eq!(loaded.number, 2);
+    }
+
+    #[test]
+    fn resistances_scale_their_kind_only() {
+        let mut resistances = Resistances::default();
+        resistances.0.insert(DamageKind::Fall, 0.0);
+        resistances.0.insert(DamageKind::Projectile, 2.0);
+        resistances.0.insert(DamageKind::Hazard, 0.5);
+        assert_eq!(resistances.apply(3, DamageKind::Contact), 3);
+        assert_eq!(resistances.apply(3, DamageKind::Fall), 0);
+        assert_eq!(resistances.apply(3, DamageKind::Projectile), 6);
+        assert_eq!(resistances.apply(3, DamageKind::Hazard), 2);
+    }
+
+    #[test]
+    fn negative_resistances_do_not_heal() {
+        let mut resistances = Resistances::default();
+        resistances.0.insert(DamageKind::Contact, -1.0);
+        assert_eq!(resistances.apply(3, DamageKind::Contact), 0);
+    }
+
+    #[test]
+    fn keys_are_used_up() {
+        let mut inventory = Inventory::default();
+        assert!(!inventory.take_key("red"));
+        inventory.add_key("red");
+        inventory.add_key("red");
+        assert!(inventory.take_key("red"));
+        assert!(inventory.take_key("red"));
+        assert!(!inventory.take_key("red"));
+        assert!(inventory.keys.is_empty());
+    }
+
+    #[test]
+    fn keys_only_open_their_own_doors() {
+        let mut inventory = Inventory::default();
+        inventory.add_key("red");
+        assert!(!inventory.take_key("blue"));
+        assert!(inventory.take_key("red"));
+    }
+}
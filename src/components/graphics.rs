@@ -1,14 +1,16 @@
 use amethyst::{
     assets::{AssetStorage, Handle, Loader, PrefabData, ProgressCounter},
-    core::transform::Transform,
+    core::{math::Point3, transform::Transform},
     derive::PrefabData,
     ecs::{
         prelude::{Component, DenseVecStorage, NullStorage},
         Entity, WriteStorage,
     },
     prelude::*,
-    renderer::{Camera, ImageFormat, SpriteRender, SpriteSheet, SpriteSheetFormat, Texture},
-    Error,
+    renderer::{
+        palette::Srgba, Camera, ImageFormat, SpriteRender, SpriteSheet, SpriteSheetFormat, Texture,
+    },
+    tiles, Error,
 };
 use serde::{Deserialize, Serialize};
 
@@ -36,6 +38,42 @@ impl Component for Pan {
     type Storage = DenseVecStorage<Self>;
 }
 
+pub const CAMERA_SHAKE_TIME: f32 = 0.3;
+
+// Shake layered on top of the camera's position, fading out over `CAMERA_SHAKE_TIME`
+#[derive(Default)]
+pub struct CameraShake {
+    pub strength: f32,
+    pub remaining: f32,
+}
+
+impl CameraShake {
+    pub fn add(&mut self, strength: f32) {
+        self.strength = match self.remaining > 0.0 {
+            true => self.strength.max(strength),
+            false => strength,
+        };
+        self.remaining = CAMERA_SHAKE_TIME;
+    }
+}
+
+impl Component for CameraShake {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Turns the camera towards `target`, so the player's gravity points down the screen
+#[derive(Derivative)]
+#[derivative(Default)]
+pub struct CameraRotation {
+    pub angle: f32,
+    pub target: f32,
+    #[derivative(Default(value = "6.0"))]
+    pub speed_factor: f32,
+}
+impl Component for CameraRotation {
+    type Storage = DenseVecStorage<Self>;
+}
+
 #[derive(Clone)]
 pub struct AnimationCounter {
     pub count_down: f32,
@@ -86,27 +124,132 @@ impl Component for PulseAnimation {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Default)]
-// sprite index / depth, number in the `seamless` chain
-pub struct BackgroundParallax(pub(crate) u32, pub i32);
+// One layer of a level's background stack, listed from front to back
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct BackgroundLayer {
+    #[derivative(Default(value = "String::from(SPRITE_SHEET_ROLLING_HILLS_BG)"))]
+    pub sheet: String,
+    pub sprite: usize,
+    // How much the layer moves with the world: 0 sticks to the camera, 1 moves like the tiles
+    pub parallax: Vec2,
+    pub offset_y: f32,
+    // Repeat the layer horizontally so it never runs out
+    #[derivative(Default(value = "true"))]
+    pub tiled: bool,
+    // Horizontal units per second, for things like clouds
+    pub scroll_speed: f32,
+}
+
+pub struct BackgroundParallax {
+    pub layer: BackgroundLayer,
+    // Number in the `seamless` chain
+    pub chain: i32,
+    // Distance travelled by auto-scrolling so far
+    pub scroll: f32,
+}
 
 impl Component for BackgroundParallax {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[enum_cycle]
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, Derivative)]
+pub const SPRITE_SHEET_TILES: &str = "tiles";
+pub const SPRITE_SHEET_DIDI: &str = "didi";
+pub const SPRITE_SHEET_SNAP: &str = "snap";
+pub const SPRITE_SHEET_UI: &str = "ui";
+pub const SPRITE_SHEET_ANIMATION: &str = "animation";
+pub const SPRITE_SHEET_ROLLING_HILLS_BG: &str = "rolling_hills_bg";
+
+// ID of the sprite sheet an editor entity was built from, as listed in the sprite sheet manifest
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SpriteSheetId(pub String);
+
+impl Component for SpriteSheetId {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Auto-tile group of the sprite sheet's manifest entry an editor tile belongs to
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileGroup(pub String);
+
+impl Component for TileGroup {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Name of the character in the animation library whose clips an actor plays
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharacterAnimationsId(pub String);
+
+impl Component for CharacterAnimationsId {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Where a character is in the state graph of its library entry
+#[derive(Clone, Debug)]
+pub struct AnimationStateMachine {
+    pub character: String,
+    // `AnimationId::None` until the graph's initial state is entered
+    pub state: AnimationId,
+    pub time_in_state: f32,
+    // Set by whoever starts an attack, cleared once the graph has seen it
+    pub attacking: bool,
+}
+
+impl AnimationStateMachine {
+    pub fn new(character: &str) -> AnimationStateMachine {
+        AnimationStateMachine {
+            character: String::from(character),
+            state: AnimationId::None,
+            time_in_state: 0.0,
+            attacking: false,
+        }
+    }
+}
+
+impl Component for AnimationStateMachine {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// One cell of a tile chunk, `None` when no tile was placed there
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChunkTile(pub Option<usize>);
+
+impl tiles::Tile for ChunkTile {
+    fn sprite(&self, _: Point3<u32>, _: &World) -> Option<usize> {
+        self.0
+    }
+
+    fn tint(&self, _: Point3<u32>, world: &World) -> Srgba {
+        match world.try_fetch::<TileChunkTint>() {
+            Some(tint) => tint.0,
+            None => Srgba::new(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
+// Tint applied to every chunk, since chunks can't carry a `Tint` per tile
+#[derive(Derivative)]
 #[derivative(Default)]
-pub enum SpriteSheetType {
-    #[derivative(Default)]
-    Tiles,
-    Didi,
-    Snap,
-    Ui,
-    Animation,
-    RollingHillsBg,
+pub struct TileChunkTint(
+    #[derivative(Default(value = "Srgba::new(1.0, 1.0, 1.0, 1.0)"))] pub Srgba,
+);
+
+// World-space bounds of a chunk entity, used for culling
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TileChunk {
+    pub center: Vec2,
+    pub half_size: Vec2,
 }
 
-impl Component for SpriteSheetType {
+impl Component for TileChunk {
     type Storage = DenseVecStorage<Self>;
 }
+
+// Editor tiles whose sprite is drawn by a chunk instead of their own instance entity
+#[derive(Default)]
+pub struct InTileChunk;
+
+impl Component for InTileChunk {
+    type Storage = NullStorage<Self>;
+}
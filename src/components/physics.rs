@@ -25,7 +25,7 @@ impl Component for MoveIntent {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Copy, Clone, Derivative)]
+#[derive(Debug, Copy, Clone, Derivative)]
 #[derivative(Default)]
 pub struct Orientation {
     #[derivative(Default(value = "Vec2::new(1.0, 0.0)"))]
@@ -184,7 +184,18 @@ impl Component for GravityDirection {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(Clone)]
+// Gives anything with a gravity that comes into it the zone's gravity
+#[derive(Debug, Copy, Clone)]
+pub struct GravityZone {
+    pub gravity: CollisionDirection,
+    pub half_size: Vec2,
+}
+
+impl Component for GravityZone {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Clone, PartialEq)]
 pub struct RTreeEntity {
     pub pos: Vec2,
     pub half_size: Vec2,
@@ -0,0 +1,101 @@
+use crate::{flags::Flags, states::loading::AssetsDir};
+use amethyst::ecs::{World, WorldExt};
+use derivative::Derivative;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::File};
+
+pub const DIALOGUES: &str = "dialogue/dialogues.ron";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DialogueAction {
+    // Hands out whatever the speaker's `Gifts` still has
+    Gift,
+    SetFlag(String),
+    ClearFlag(String),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DialogueChoice {
+    pub text: String,
+    pub actions: Vec<DialogueAction>,
+    // Node to go to once picked, the dialogue closes when unset
+    pub next: Option<String>,
+    // Only offered when the flag is set...
+    pub if_flag: Option<String>,
+    // ...or not set
+    pub unless_flag: Option<String>,
+}
+
+impl DialogueChoice {
+    pub fn available(&self, flags: &Flags) -> bool {
+        self.if_flag
+            .as_ref()
+            .map_or(true, |flag| flags.is_set(flag))
+            && self
+                .unless_flag
+                .as_ref()
+                .map_or(true, |flag| !flags.is_set(flag))
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DialogueNode {
+    // Shown one after the other, the choices come up with the last one
+    pub lines: Vec<String>,
+    pub choices: Vec<DialogueChoice>,
+    // Node to go to after the last line when there are no choices
+    pub next: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct DialogueTree {
+    pub speaker: String,
+    #[derivative(Default(value = "String::from(\"start\")"))]
+    pub start: String,
+    pub nodes: BTreeMap<String, DialogueNode>,
+}
+
+impl DialogueTree {
+    // A single line, for talkers without a dialogue of their own
+    pub fn line(speaker: &str, text: &str) -> DialogueTree {
+        let mut tree = DialogueTree::default();
+        tree.speaker = String::from(speaker);
+        tree.nodes.insert(
+            tree.start.clone(),
+            DialogueNode {
+                lines: vec![String::from(text)],
+                ..DialogueNode::default()
+            },
+        );
+        tree
+    }
+}
+
+// Every dialogue in `dialogue/dialogues.ron`, by name
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Dialogues(pub BTreeMap<String, DialogueTree>);
+
+impl Dialogues {
+    pub fn load(world: &World) -> Dialogues {
+        let path = world.read_resource::<AssetsDir>().0.join(DIALOGUES);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open dialogues {:?}: {}", path, e);
+                return Dialogues::default();
+            }
+        };
+        match ron::de::from_reader(file) {
+            Ok(dialogues) => dialogues,
+            Err(e) => {
+                error!("Failed to parse dialogues {:?}: {}", path, e);
+                Dialogues::default()
+            }
+        }
+    }
+}
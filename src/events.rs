@@ -1,5 +1,7 @@
-use crate::components::game::Drops;
-use crate::components::game::{SerializedObject, Team};
+use crate::{
+    components::game::{Pickup, SerializedObject, Team},
+    mechanisms::DoorChange,
+};
 use amethyst::ecs::prelude::Entity;
 use ultraviolet::Vec2;
 
@@ -16,13 +18,39 @@ pub enum Events {
     EntityToInsertionGameObject(u32),
     OpenFilePickerUi,
     HoverGameObject,
-    // Pos, vel, team
-    FireProjectile(Vec2, Vec2, Team),
+    // Kind in the projectile library, pos, vel, team
+    FireProjectile(String, Vec2, Vec2, Team),
     // Parent, Pos, size, team
     CreateDamageBox(Option<Entity>, Vec2, Vec2, Team),
+    // Instance only, for things that appear during play
+    SpawnGameObject(SerializedObject),
+    // Instance only, a pickup that sits at the position until collected
+    SpawnPickup(Pickup, Vec2),
+    // Name of a dialogue in the dialogue file
+    ShowDialogue(String),
+    // Every door with the link
+    ChangeDoors(u32, DoorChange),
+}
+
+// Hits that landed, after resistances and invincibility frames have had their say.
+// Positions are from the moment of the hit, since the dead are moved out of the level.
+#[derive(Debug, Clone)]
+pub enum DamageEvent {
+    Damaged {
+        id: u32,
+        amount: u32,
+        pos: Option<Vec2>,
+    },
+    Died {
+        id: u32,
+        pos: Option<Vec2>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub enum PlayerEvent {
     Revive(u32),
+    // Where the player was when it happened
+    Jump(Vec2),
+    Land(Vec2),
 }
@@ -0,0 +1,136 @@
+use crate::{components::game::Team, states::loading::AssetsDir};
+use amethyst::ecs::{World, WorldExt};
+use derivative::Derivative;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+
+pub const FACTIONS: &str = "factions/factions.ron";
+
+#[derive(Derivative, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[derivative(Default)]
+pub enum Relation {
+    // Hurt each other, go after each other, and reflect or block each other's projectiles
+    Hostile,
+    // Can't hurt each other, can pick each other up and talk, and shoot through each other's shields
+    Friendly,
+    // Leave each other alone and can pick each other up and talk, but still reflect or block each
+    // other's projectiles
+    #[derivative(Default)]
+    Neutral,
+}
+
+// How the teams feel about each other, the same both ways round
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct Factions {
+    #[derivative(Default(value = "vec![(Team::GoodGuys, Team::BadGuys, Relation::Hostile)]"))]
+    pub relations: Vec<(Team, Team, Relation)>,
+    // Between different teams that aren't in `relations`
+    pub default: Relation,
+    // Between members of the same team that isn't in `relations`
+    #[derivative(Default(value = "Relation::Friendly"))]
+    pub same_team: Relation,
+    // Between an individual and any other team but the neutral one, since individuals can't be
+    // listed in `relations`
+    #[derivative(Default(value = "Relation::Hostile"))]
+    pub individual: Relation,
+}
+
+impl Factions {
+    pub fn load(world: &World) -> Factions {
+        let path = world.read_resource::<AssetsDir>().0.join(FACTIONS);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open factions {:?}: {}", path, e);
+                return Factions::default();
+            }
+        };
+        match ron::de::from_reader(file) {
+            Ok(factions) => factions,
+            Err(e) => {
+                error!("Failed to parse factions {:?}: {}", path, e);
+                Factions::default()
+            }
+        }
+    }
+
+    pub fn relation(&self, team: &Team, other: &Team) -> Relation {
+        let listed = self
+            .relations
+            .iter()
+            .find(|(a, b, _)| (a == team && b == other) || (a == other && b == team));
+        // The neutral team stays out of everyone's fights, individuals included
+        let individual = match (team, other) {
+            (Team::Neutral, _) | (_, Team::Neutral) => false,
+            (Team::Individual(_), _) | (_, Team::Individual(_)) => true,
+            _ => false,
+        };
+        match listed {
+            Some((_, _, relation)) => *relation,
+            None => match (team == other, individual) {
+                (true, _) => self.same_team,
+                (false, true) => self.individual,
+                (false, false) => self.default,
+            },
+        }
+    }
+
+    pub fn hostile(&self, team: &Team, other: &Team) -> bool {
+        self.relation(team, other) == Relation::Hostile
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_relations_go_both_ways() {
+        let factions = Factions::default();
+        assert_eq!(
+            factions.relation(&Team::GoodGuys, &Team::BadGuys),
+            Relation::Hostile
+        );
+        assert_eq!(
+            factions.relation(&Team::BadGuys, &Team::GoodGuys),
+            Relation::Hostile
+        );
+    }
+
+    #[test]
+    fn unlisted_relations_use_the_defaults() {
+        let factions = Factions::default();
+        assert_eq!(
+            factions.relation(&Team::GoodGuys, &Team::GoodGuys),
+            Relation::Friendly
+        );
+        assert_eq!(
+            factions.relation(&Team::GoodGuys, &Team::Neutral),
+            Relation::Neutral
+        );
+    }
+
+    #[test]
+    fn individuals_fight_everyone_but_the_neutral_team() {
+        let factions = Factions::default();
+        let individual = Team::Individual(1);
+        assert!(factions.hostile(&individual, &Team::GoodGuys));
+        assert!(factions.hostile(&Team::BadGuys, &individual));
+        assert!(factions.hostile(&individual, &Team::Individual(2)));
+        assert!(!factions.hostile(&individual, &individual));
+        assert!(!factions.hostile(&individual, &Team::Neutral));
+    }
+
+    #[test]
+    fn listed_relations_win_over_the_defaults() {
+        let factions = Factions {
+            relations: vec![(Team::GoodGuys, Team::GoodGuys, Relation::Hostile)],
+            ..Factions::default()
+        };
+        assert!(factions.hostile(&Team::GoodGuys, &Team::GoodGuys));
+        assert!(!factions.hostile(&Team::GoodGuys, &Team::BadGuys));
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Named facts and counters about the world that outlive any single entity, like having been
+// given a gift. A flag is just a value that isn't 0.
+#[derive(Clone, Debug, Default)]
+pub struct Flags {
+    values: BTreeMap<String, i32>,
+}
+
+impl Flags {
+    pub fn is_set(&self, flag: &str) -> bool {
+        self.value(flag) != 0
+    }
+
+    pub fn set(&mut self, flag: &str) {
+        self.set_value(flag, 1);
+    }
+
+    pub fn clear(&mut self, flag: &str) {
+        self.values.remove(flag);
+    }
+
+    pub fn value(&self, name: &str) -> i32 {
+        *self.values.get(name).unwrap_or(&0)
+    }
+
+    pub fn set_value(&mut self, name: &str, value: i32) {
+        match value {
+            0 => self.clear(name),
+            _ => {
+                self.values.insert(String::from(name), value);
+            }
+        }
+    }
+
+    pub fn add_value(&mut self, name: &str, amount: i32) {
+        self.set_value(name, self.value(name) + amount);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &i32)> {
+        self.values.iter()
+    }
+}
+
+// The flags as they were when the level was loaded, put back when it resets so that triggers
+// and death hooks don't pile up their effects
+#[derive(Clone, Debug, Default)]
+pub struct LevelStartFlags(pub Flags);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FlagCondition {
+    Set(String),
+    Unset(String),
+    Equals(String, i32),
+    AtLeast(String, i32),
+    AtMost(String, i32),
+    All(Vec<FlagCondition>),
+    Any(Vec<FlagCondition>),
+}
+
+impl FlagCondition {
+    pub fn holds(&self, flags: &Flags) -> bool {
+        match self {
+            FlagCondition::Set(flag) => flags.is_set(flag),
+            FlagCondition::Unset(flag) => !flags.is_set(flag),
+            FlagCondition::Equals(name, value) => flags.value(name) == *value,
+            FlagCondition::AtLeast(name, value) => flags.value(name) >= *value,
+            FlagCondition::AtMost(name, value) => flags.value(name) <= *value,
+            FlagCondition::All(conditions) => conditions.iter().all(|c| c.holds(flags)),
+            FlagCondition::Any(conditions) => conditions.iter().any(|c| c.holds(flags)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(name: &str) -> FlagCondition {
+        FlagCondition::Set(String::from(name))
+    }
+
+    #[test]
+    fn zero_values_are_cleared() {
+        let mut flags = Flags::default();
+        flags.set_value("coins", 3);
+        assert_eq!(flags.value("coins"), 3);
+        assert!(flags.is_set("coins"));
+        flags.add_value("coins", -3);
+        assert_eq!(flags.value("coins"), 0);
+        assert!(!flags.is_set("coins"));
+        assert_eq!(flags.iter().count(), 0);
+    }
+
+    #[test]
+    fn negative_values_are_set() {
+        let mut flags = Flags::default();
+        flags.set_value("mood", -2);
+        assert!(flags.is_set("mood"));
+        assert_eq!(flags.value("mood"), -2);
+    }
+
+    #[test]
+    fn comparisons_hold() {
+        let mut flags = Flags::default();
+        flags.set_value("coins", 2);
+        let coins = String::from("coins");
+        assert!(FlagCondition::Equals(coins.clone(), 2).holds(&flags));
+        assert!(!FlagCondition::Equals(coins.clone(), 1).holds(&flags));
+        assert!(FlagCondition::AtLeast(coins.clone(), 2).holds(&flags));
+        assert!(!FlagCondition::AtLeast(coins.clone(), 3).holds(&flags));
+        assert!(FlagCondition::AtMost(coins.clone(), 2).holds(&flags));
+        assert!(!FlagCondition::AtMost(coins.clone(), 1).holds(&flags));
+        // Unknown values count as 0
+        assert!(FlagCondition::Equals(String::from("gems"), 0).holds(&flags));
+    }
+
+    #[test]
+    fn combined_conditions_hold() {
+        let mut flags = Flags::default();
+        flags.set("gift");
+        assert!(set("gift").holds(&flags));
+        assert!(!FlagCondition::Unset(String::from("gift")).holds(&flags));
+        assert!(!FlagCondition::All(vec![set("gift"), set("talked")]).holds(&flags));
+        assert!(FlagCondition::Any(vec![set("gift"), set("talked")]).holds(&flags));
+        flags.set("talked");
+        assert!(FlagCondition::All(vec![set("gift"), set("talked")]).holds(&flags));
+        // Nothing to disagree with, and nothing to agree with
+        assert!(FlagCondition::All(Vec::new()).holds(&flags));
+        assert!(!FlagCondition::Any(Vec::new()).holds(&flags));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     animations::{AnimationFactory, AnimationId},
+    audio::{change_music, MusicPlayer},
     components::{
         ai,
         ai::{BasicShootAi, BasicWalkAi},
@@ -9,12 +10,19 @@ use crate::{
             Damage, Health, Invincibility, Player, Projectile, Reflect, Resettable, SerialHelper,
             SerializedObject, SerializedObjectType, SpriteRenderData, Team, Tile, TimedExistence,
         },
-        graphics::{AnimationCounter, BackgroundParallax, CameraLimit, Scale, SpriteSheetType},
+        graphics::{
+            AnimationCounter, BackgroundLayer, BackgroundParallax, CameraLimit, Scale,
+            SPRITE_SHEET_ROLLING_HILLS_BG,
+        },
         physics::{
             Collidee, GravityDirection, Grounded, PlatformCollisionPoints, PlatformCuboid,
             Position, RTreeEntity, Sticky, Velocity,
         },
     },
+    flags::{Flags, LevelStartFlags},
+    mechanisms::{swap_solids, Door},
+    navigation::NavGraph,
+    rng::WorldRng,
     states::{
         loading::AssetsDir,
         pizzatopia::{
@@ -23,6 +31,8 @@ use crate::{
         },
     },
     systems::{editor::EditorButtonEventSystem, physics::CollisionDirection},
+    tile_chunks::TileChunks,
+    triggers::{initialize_triggers, LevelTriggers, TriggerDef},
     ui::file_picker::{FilePickerFilename, DIR_LEVELS},
     utils::{Vec2, Vec3},
 };
@@ -60,6 +70,32 @@ use std::{collections::BTreeMap, fs::File, io::Write, ops::Index, path::PathBuf,
 #[derivative(Default)]
 pub struct Level {
     serialized_objects: Option<Vec<SerializedObject>>,
+    #[serde(default)]
+    background: Option<Vec<BackgroundLayer>>,
+    // Path of the background music, relative to the assets directory
+    #[serde(default)]
+    music: Option<String>,
+    #[serde(default)]
+    triggers: Option<Vec<TriggerDef>>,
+}
+
+// The background stack of the currently-loaded level
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelBackground(pub Vec<BackgroundLayer>);
+
+impl Default for LevelBackground {
+    // The rolling hills, for levels that don't declare a background
+    fn default() -> Self {
+        let layers = (0..4)
+            .map(|i| BackgroundLayer {
+                sheet: String::from(SPRITE_SHEET_ROLLING_HILLS_BG),
+                sprite: i,
+                parallax: ultraviolet::Vec2::new(0.25 / (1. + i as f32), 0.),
+                ..BackgroundLayer::default()
+            })
+            .collect();
+        LevelBackground(layers)
+    }
 }
 
 impl Asset for Level {
@@ -116,14 +152,18 @@ impl Level {
 
     pub fn recalculate_collision_tree(world: &mut World) {
         let mut positions = Vec::new();
-        for (entity, pos, platform_cuboid, layer) in (
+        for (entity, pos, platform_cuboid, layer, door) in (
             &world.entities(),
             &world.read_storage::<Position>(),
             &world.read_storage::<PlatformCuboid>(),
             &world.read_storage::<TileLayer>(),
+            (&world.read_storage::<Door>()).maybe(),
         )
             .join()
         {
+            if door.map_or(false, |door| door.open) {
+                continue;
+            }
             match layer {
                 TileLayer::Middle => {
                     let rtree_entity = RTreeEntity::new(pos.0, platform_cuboid.to_vec2(), entity);
@@ -133,20 +173,33 @@ impl Level {
             }
         }
         let tree = RTree::bulk_load(positions);
+        world.insert(NavGraph::build(&tree));
         world.insert(tree);
     }
 
     // Turn the currently-loaded Level asset into entities
     pub(crate) fn load_level(world: &mut World) {
-        let serialized_objects = {
+        world.write_resource::<WorldRng>().reseed();
+        let (serialized_objects, background, music, triggers) = {
             let asset = &world.read_resource::<AssetStorage<Level>>();
             let level = asset
                 .get(&world.read_resource::<Handle<Level>>().clone())
                 .unwrap_or(&Level::default())
                 .clone();
-            level.serialized_objects
+            (
+                level.serialized_objects,
+                level.background,
+                level.music,
+                level.triggers,
+            )
         };
+        change_music(world, music.as_ref().map(|path| path.as_str()));
+        world.insert(match background {
+            Some(layers) => LevelBackground(layers),
+            None => LevelBackground::default(),
+        });
 
+        world.insert(TileChunks::default());
         if let Some(serialized_objects) = serialized_objects {
             for mut serialized_object in serialized_objects {
                 entity_builder::initialize_serialized_object(world, &mut serialized_object, false);
@@ -156,6 +209,11 @@ impl Level {
         Self::recalculate_collision_tree(world);
 
         Level::calculate_camera_limits(world);
+
+        let flags = world.read_resource::<Flags>().clone();
+        world.insert(LevelStartFlags(flags));
+        world.insert(LevelTriggers(triggers.unwrap_or_default()));
+        initialize_triggers(world);
     }
 
     pub(crate) fn save_level(world: &mut World) {
@@ -190,6 +248,18 @@ impl Level {
             true => None,
             false => Some(serialized_objects),
         };
+        // Levels on the default background keep picking up changes to it
+        let background = world.read_resource::<LevelBackground>().clone();
+        level.background = match background == LevelBackground::default() {
+            true => None,
+            false => Some(background.0),
+        };
+        level.music = world.read_resource::<MusicPlayer>().current_track();
+        let triggers = world.read_resource::<LevelTriggers>().0.clone();
+        level.triggers = match triggers.is_empty() {
+            true => None,
+            false => Some(triggers),
+        };
 
         // Serialize
         let config = ron::ser::PrettyConfig::default();
@@ -216,6 +286,7 @@ impl Level {
 
     // Reset the entities in the level to match the editor entity states
     pub(crate) fn reinitialize_level(world: &mut World) {
+        world.write_resource::<WorldRng>().reseed();
         let mut resettables = Vec::new();
 
         {
@@ -241,21 +312,32 @@ impl Level {
 
         // Re-create the entities according to their type
         let mut to_remove = Vec::new();
+        let mut created = Vec::new();
         for (editor_entity, instance_entity, _) in resettables {
             to_remove.push(instance_entity);
             let serialized_object =
                 entity_builder::entity_to_serialized_object(world, editor_entity.id());
             let new_instance_id =
                 entity_builder::initialize_serialized_object(world, &serialized_object, true);
+            if let Some(id) = new_instance_id {
+                created.push(world.entities().entity(id));
+            }
             world
                 .write_storage::<InstanceEntityId>()
                 .get_mut(editor_entity)
                 .unwrap()
-                .0 = Some(new_instance_id);
+                .0 = new_instance_id;
         }
 
+        // Doors are the only resettables that are solid
+        swap_solids(world, &to_remove, &created);
+
         world
             .delete_entities(to_remove.as_slice())
             .expect("Failed to delete entities for reset.");
+
+        let flags = world.read_resource::<LevelStartFlags>().0.clone();
+        world.insert(flags);
+        initialize_triggers(world);
     }
 }
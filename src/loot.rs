@@ -0,0 +1,253 @@
+use crate::{
+    components::{game::Pickup, graphics::SPRITE_SHEET_TILES},
+    states::loading::AssetsDir,
+    systems::physics::CollisionDirection,
+};
+use amethyst::ecs::{
+    prelude::{Component, DenseVecStorage},
+    World, WorldExt,
+};
+use derivative::Derivative;
+use log::error;
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::File};
+use ultraviolet::Vec2;
+
+pub const LOOT: &str = "loot/loot.ron";
+// Dropped by actors that don't name a table
+pub const DEFAULT_LOOT: &str = "default";
+// Pickup kinds handed out as gifts
+pub const HEART: &str = "heart";
+pub const VEGGIE: &str = "veggie";
+
+// What picking up an `Item` does
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PickupEffect {
+    // Counts towards the picker's gathered total
+    Gather(u32),
+    Heal(u32),
+    // Seconds of not being able to be hurt
+    Invincible(f32),
+    // Unlocks the named ability from the ability library
+    Unlock(String),
+    SetFlag(String),
+    AddValue(String, i32),
+    // Turns the picker's gravity around
+    FlipGravity,
+    SetGravity(CollisionDirection),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct PickupDef {
+    #[derivative(Default(value = "String::from(SPRITE_SHEET_TILES)"))]
+    pub sheet: String,
+    pub sprite: usize,
+    // Seconds before it disappears, it stays until collected when unset
+    #[derivative(Default(value = "Some(10.0)"))]
+    pub lifetime: Option<f32>,
+    pub effects: Vec<PickupEffect>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct LootEntry {
+    #[derivative(Default(value = "Pickup::Item(String::from(HEART))"))]
+    pub pickup: Pickup,
+    // Relative to the other entries in the table
+    #[derivative(Default(value = "1"))]
+    pub weight: u32,
+    // Chance of anything dropping once the entry is picked, from 0 to 1
+    #[derivative(Default(value = "1.0"))]
+    pub chance: f32,
+    // Inclusive range of how many drop
+    #[derivative(Default(value = "(1, 1)"))]
+    pub count: (u32, u32),
+    // Ranges of the starting velocity, in pixels per frame
+    #[derivative(Default(value = "(-2.0, 2.0)"))]
+    pub x_speed: (f32, f32),
+    #[derivative(Default(value = "(7.0, 15.0)"))]
+    pub y_speed: (f32, f32),
+}
+
+impl LootEntry {
+    // Velocities of everything that drops, `max` limiting how many can
+    pub fn roll<R: Rng>(&self, rng: &mut R, max: u32) -> Vec<Vec2> {
+        if self.chance < 1.0 && !rng.gen_bool(self.chance.max(0.0) as f64) {
+            return Vec::new();
+        }
+        let count = count_in(rng, self.count).min(max);
+        (0..count)
+            .map(|_| Vec2::new(speed_in(rng, self.x_speed), speed_in(rng, self.y_speed)))
+            .collect()
+    }
+}
+
+// Picks an entry by weight for each roll
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct LootTable {
+    #[derivative(Default(value = "(1, 1)"))]
+    pub rolls: (u32, u32),
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> Vec<(Pickup, Vec2)> {
+        let weights = match WeightedIndex::new(self.entries.iter().map(|entry| entry.weight)) {
+            Ok(weights) => weights,
+            Err(_) => return Vec::new(),
+        };
+        let mut drops = Vec::new();
+        for _ in 0..count_in(rng, self.rolls) {
+            let entry = &self.entries[weights.sample(rng)];
+            drops.extend(
+                entry
+                    .roll(rng, std::u32::MAX)
+                    .into_iter()
+                    .map(|vel| (entry.pickup.clone(), vel)),
+            );
+        }
+        drops
+    }
+}
+
+fn default_hurt_drop() -> LootEntry {
+    LootEntry {
+        pickup: Pickup::Item(String::from(VEGGIE)),
+        count: (2, 2),
+        x_speed: (-4.0, 4.0),
+        ..LootEntry::default()
+    }
+}
+
+// Pickup kinds and loot tables from `loot/loot.ron`, by name
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct LootLibrary {
+    // Used by `Pickup::Item`
+    pub pickups: BTreeMap<String, PickupDef>,
+    pub tables: BTreeMap<String, LootTable>,
+    // What the player drops when hurt, no more than they've gathered
+    #[derivative(Default(value = "default_hurt_drop()"))]
+    pub hurt: LootEntry,
+}
+
+impl LootLibrary {
+    pub fn load(world: &World) -> LootLibrary {
+        let path = world.read_resource::<AssetsDir>().0.join(LOOT);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open loot {:?}: {}", path, e);
+                return LootLibrary::default();
+            }
+        };
+        match ron::de::from_reader(file) {
+            Ok(library) => library,
+            Err(e) => {
+                error!("Failed to parse loot {:?}: {}", path, e);
+                LootLibrary::default()
+            }
+        }
+    }
+
+    pub fn table(&self, name: &str) -> Option<&LootTable> {
+        let table = self.tables.get(name);
+        if table.is_none() {
+            error!("Unknown loot table {:?}", name);
+        }
+        table
+    }
+}
+
+// Name of the loot table dropped on death
+#[derive(Clone, Debug)]
+pub struct Loot(pub String);
+
+impl Component for Loot {
+    type Storage = DenseVecStorage<Self>;
+}
+
+fn count_in<R: Rng>(rng: &mut R, (low, high): (u32, u32)) -> u32 {
+    match low < high {
+        true => rng.gen_range(low, high + 1),
+        false => low,
+    }
+}
+
+fn speed_in<R: Rng>(rng: &mut R, (low, high): (f32, f32)) -> f32 {
+    match low < high {
+        true => rng.gen_range(low, high),
+        false => low,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn table() -> LootTable {
+        LootTable {
+            rolls: (1, 3),
+            entries: vec![
+                LootEntry {
+                    pickup: Pickup::Item(String::from("coin")),
+                    weight: 3,
+                    count: (1, 3),
+                    ..LootEntry::default()
+                },
+                LootEntry {
+                    pickup: Pickup::Item(String::from(HEART)),
+                    chance: 0.5,
+                    ..LootEntry::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn roll_is_the_same_for_the_same_seed() {
+        let table = table();
+        for seed in 0..20 {
+            let first = table.roll(&mut StdRng::seed_from_u64(seed));
+            let second = table.roll(&mut StdRng::seed_from_u64(seed));
+            assert_eq!(first, second);
+        }
+    }
+
+    #[test]
+    fn roll_stays_in_range() {
+        let table = table();
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let drops = table.roll(&mut rng);
+            // Up to three rolls of up to three each
+            assert!(drops.len() <= 9);
+            for (_, vel) in drops {
+                assert!(vel.x >= -2.0 && vel.x < 2.0);
+                assert!(vel.y >= 7.0 && vel.y < 15.0);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_table_drops_nothing() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(LootTable::default().roll(&mut rng).is_empty());
+        let nothing = LootTable {
+            rolls: (0, 0),
+            ..table()
+        };
+        assert!(nothing.roll(&mut rng).is_empty());
+    }
+}
@@ -25,6 +25,7 @@ use amethyst::{
         types::DefaultBackend,
         ImageFormat, RenderingBundle, SpriteSheet, Texture,
     },
+    tiles::{FlatEncoder, RenderTiles2D},
     ui::{RenderUi, UiBundle, UiCreator, UiEvent, UiFinder, UiText},
     utils::{
         application_root_dir,
@@ -33,18 +34,34 @@ use amethyst::{
     Error, Logger,
 };
 
+mod abilities;
 mod animations;
 mod audio;
+mod auto_tile;
 mod bundles;
 mod components;
+mod dialogue;
 mod events;
+mod factions;
+mod flags;
 mod level;
+mod loot;
+mod mechanisms;
+mod navigation;
+mod particles;
+mod projectiles;
+mod rng;
+mod scripting;
+mod settings;
 mod states;
 mod systems;
+mod tile_chunks;
+mod triggers;
 mod ui;
 mod utils;
 use crate::{
-    components::physics::PlatformCuboid,
+    audio::MusicSystem,
+    components::{graphics::ChunkTile, physics::PlatformCuboid},
     level::Level,
     states::{
         loading::LoadingState,
@@ -79,15 +96,23 @@ fn main() -> amethyst::Result<()> {
                         .with_clear([0.34, 0.36, 0.52, 1.0]),
                 )
                 .with_plugin(RenderFlat2D::default())
+                .with_plugin(RenderTiles2D::<ChunkTile, FlatEncoder>::default())
                 .with_plugin(RenderUi::default())
                 .with_plugin(RenderDebugLines::default()),
         )?
-        .with(Processor::<Level>::new(), "", &[]);
+        .with(Processor::<Level>::new(), "", &[])
+        .with(MusicSystem, "music_system", &[]);
     let assets_dir = app_root.join("assets");
 
+    // `--seed <number>` makes every level start play out the same
+    let seed = std::env::args()
+        .skip_while(|arg| arg != "--seed")
+        .nth(1)
+        .and_then(|seed| seed.parse::<u64>().ok());
+
     let mut game = CoreApplication::<_, MyEvents, MyEventReader>::new(
         assets_dir,
-        LoadingState::default(),
+        LoadingState::with_seed(seed),
         game_data,
     )?;
     game.run();
@@ -0,0 +1,323 @@
+use crate::{
+    components::{
+        editor::{EditorFlag, TileLayer},
+        game::{Dormant, Health, Inventory, Player},
+        physics::{PlatformCollisionPoints, PlatformCuboid, Position, RTreeEntity},
+    },
+    events::Events,
+    navigation::NavGraph,
+    ui::UiStack,
+};
+use amethyst::{
+    core::{
+        shrev::{EventChannel, ReaderId},
+        Hidden,
+    },
+    derive::SystemDesc,
+    ecs::{
+        prelude::{Component, DenseVecStorage},
+        Entities, Entity, Join, Read, ReadStorage, System, SystemData, World, WorldExt, Write,
+        WriteStorage,
+    },
+    input::StringBindings,
+    renderer::SpriteRender,
+};
+use bami::Input;
+use log::info;
+use rstar::RTree;
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec2;
+
+// How close the player has to be to a locked door to use a key on it
+const KEY_REACH: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum DoorChange {
+    Open,
+    Close,
+    Toggle,
+}
+
+// A solid that can be opened. Closed doors are in the collision tree, open ones are hidden.
+#[derive(Clone, Debug)]
+pub struct Door {
+    // Key the player needs to open it, only switches and triggers can when unset
+    pub key: Option<String>,
+    pub link: Option<u32>,
+    pub open: bool,
+}
+
+impl Component for Door {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Clone, Debug)]
+pub struct Switch {
+    pub link: u32,
+    pub plate: bool,
+    pub on: bool,
+    pub half_size: Vec2,
+    // Shown while off, the next sprite in the sheet is shown while on
+    pub base_sprite: usize,
+}
+
+impl Component for Switch {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// The entity's entry in the collision tree, if it's on a layer that collides
+fn solid(
+    entity: Entity,
+    pos: &Position,
+    cuboid: &PlatformCuboid,
+    layer: &TileLayer,
+) -> Option<RTreeEntity> {
+    match layer {
+        TileLayer::Middle => Some(RTreeEntity::new(pos.0, cuboid.to_vec2(), entity)),
+        _ => None,
+    }
+}
+
+// Tree entries of the solids among the entities, open doors aren't solid
+fn solids(world: &World, entities: &[Entity]) -> Vec<RTreeEntity> {
+    let doors = world.read_storage::<Door>();
+    let positions = world.read_storage::<Position>();
+    let cuboids = world.read_storage::<PlatformCuboid>();
+    let layers = world.read_storage::<TileLayer>();
+    entities
+        .iter()
+        .filter_map(
+            |entity| match doors.get(*entity).map_or(false, |door| door.open) {
+                true => None,
+                false => solid(
+                    *entity,
+                    positions.get(*entity)?,
+                    cuboids.get(*entity)?,
+                    layers.get(*entity)?,
+                ),
+            },
+        )
+        .collect()
+}
+
+// Takes the solids about to be deleted, like doors and spawned tiles, out of the collision tree
+// and puts the new ones in, rather than recalculating all of it
+pub(crate) fn swap_solids(world: &mut World, removed: &[Entity], added: &[Entity]) {
+    let old_solids = solids(world, removed);
+    let new_solids = solids(world, added);
+    if old_solids.is_empty() && new_solids.is_empty() {
+        return;
+    }
+    let nav = {
+        let mut tree = world.write_resource::<RTree<RTreeEntity>>();
+        for solid in old_solids.iter() {
+            tree.remove(solid);
+        }
+        for solid in new_solids {
+            tree.insert(solid);
+        }
+        NavGraph::build(&tree)
+    };
+    world.insert(nav);
+}
+
+fn overlaps(pos: Vec2, half_size: Vec2, other_pos: Vec2, other_half_size: Vec2) -> bool {
+    (pos.x - other_pos.x).abs() <= half_size.x + other_half_size.x
+        && (pos.y - other_pos.y).abs() <= half_size.y + other_half_size.y
+}
+
+#[derive(SystemDesc)]
+pub struct SwitchSystem;
+
+impl<'s> System<'s> for SwitchSystem {
+    type SystemData = (
+        WriteStorage<'s, Switch>,
+        WriteStorage<'s, SpriteRender>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, PlatformCollisionPoints>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Dormant>,
+        ReadStorage<'s, EditorFlag>,
+        Read<'s, Input<StringBindings>>,
+        Read<'s, UiStack>,
+        Write<'s, EventChannel<Events>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut switches,
+            mut sprites,
+            positions,
+            coll_points,
+            players,
+            healths,
+            dormants,
+            editor_flags,
+            input,
+            ui_stack,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        // Same as talking, so levers are used the way people are spoken to
+        let interacting = input.axes.single_press(&String::from("vertical")).axis < 0.0
+            && !ui_stack.is_blocking_all_input();
+        // Living actors can hold plates down, only the player can pull levers
+        let actors: Vec<(Vec2, Vec2, bool)> = (
+            &positions,
+            &coll_points,
+            &healths,
+            (&players).maybe(),
+            !&dormants,
+            !&editor_flags,
+        )
+            .join()
+            .filter(|(_, _, health, _, _, _)| health.alive())
+            .map(|(pos, points, _, player, _, _)| {
+                (pos.0, points.half_size, player.map_or(false, |p| p.0))
+            })
+            .collect();
+
+        for (switch, pos, sprite, _) in
+            (&mut switches, &positions, &mut sprites, !&editor_flags).join()
+        {
+            let mut touching = actors.iter().filter(|(actor_pos, half_size, _)| {
+                overlaps(pos.0, switch.half_size, *actor_pos, *half_size)
+            });
+            let on = match switch.plate {
+                true => touching.next().is_some(),
+                false => switch.on ^ (interacting && touching.any(|(_, _, player)| *player)),
+            };
+            if on == switch.on {
+                continue;
+            }
+            switch.on = on;
+            sprite.sprite_number = switch.base_sprite + on as usize;
+            events.single_write(Events::ChangeDoors(switch.link, DoorChange::Toggle));
+        }
+    }
+}
+
+#[derive(SystemDesc)]
+#[system_desc(name(DoorSystemDesc))]
+pub struct DoorSystem {
+    #[system_desc(event_channel_reader)]
+    reader: ReaderId<Events>,
+}
+
+impl DoorSystem {
+    pub(crate) fn new(reader: ReaderId<Events>) -> Self {
+        Self { reader }
+    }
+}
+
+impl<'s> System<'s> for DoorSystem {
+    type SystemData = (
+        WriteStorage<'s, Door>,
+        WriteStorage<'s, Inventory>,
+        WriteStorage<'s, Hidden>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, PlatformCuboid>,
+        ReadStorage<'s, TileLayer>,
+        ReadStorage<'s, PlatformCollisionPoints>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, EditorFlag>,
+        Write<'s, RTree<RTreeEntity>>,
+        Write<'s, NavGraph>,
+        Entities<'s>,
+        Read<'s, EventChannel<Events>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut doors,
+            mut inventories,
+            mut hiddens,
+            positions,
+            cuboids,
+            layers,
+            coll_points,
+            players,
+            editor_flags,
+            mut rtree,
+            mut nav,
+            entities,
+            event_channel,
+        ): Self::SystemData,
+    ) {
+        let mut changes = Vec::new();
+        for event in event_channel.read(&mut self.reader) {
+            if let Events::ChangeDoors(link, change) = event {
+                changes.push((*link, *change));
+            }
+        }
+
+        let mut changed = false;
+        for (entity, door, pos, cuboid, layer, _) in (
+            &entities,
+            &mut doors,
+            &positions,
+            &cuboids,
+            &layers,
+            !&editor_flags,
+        )
+            .join()
+        {
+            let mut open = door.open;
+            for (link, change) in changes.iter() {
+                if door.link == Some(*link) {
+                    open = match change {
+                        DoorChange::Open => true,
+                        DoorChange::Close => false,
+                        DoorChange::Toggle => !open,
+                    };
+                }
+            }
+            // The player uses up a key by walking into a locked door
+            if let (false, Some(key)) = (open, &door.key) {
+                for (player, player_pos, points, inventory) in
+                    (&players, &positions, &coll_points, &mut inventories).join()
+                {
+                    let reach = cuboid.to_vec2() + Vec2::new(KEY_REACH, KEY_REACH);
+                    if player.0
+                        && overlaps(pos.0, reach, player_pos.0, points.half_size)
+                        && inventory.take_key(key)
+                    {
+                        info!("Opened a door with key {}", key);
+                        open = true;
+                        break;
+                    }
+                }
+            }
+            if open == door.open {
+                continue;
+            }
+            door.open = open;
+            changed = true;
+
+            // Only this door's entry changes, so the rest of the tree is left alone
+            let solid = solid(entity, pos, cuboid, layer);
+            match open {
+                true => {
+                    if let Some(solid) = solid {
+                        rtree.remove(&solid);
+                    }
+                    hiddens
+                        .insert(entity, Hidden)
+                        .expect("Failed to hide open door");
+                }
+                false => {
+                    if let Some(solid) = solid {
+                        rtree.insert(solid);
+                    }
+                    hiddens.remove(entity);
+                }
+            }
+        }
+        if changed {
+            *nav = NavGraph::build(&rtree);
+        }
+    }
+}
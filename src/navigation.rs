@@ -0,0 +1,310 @@
+use crate::{
+    components::physics::RTreeEntity,
+    states::pizzatopia::{TILE_HEIGHT, TILE_WIDTH},
+};
+use rstar::{RTree, AABB};
+use std::cmp::Ordering;
+use ultraviolet::Vec2;
+
+// Upwards speed of an actor's jump, enough to clear a little over a tile and a half
+pub const JUMP_SPEED: f32 = 13.0;
+// How far up and across a jump can get an actor between surfaces
+pub const MAX_JUMP_HEIGHT: f32 = TILE_HEIGHT * 1.5;
+pub const MAX_JUMP_DISTANCE: f32 = TILE_WIDTH * 1.5;
+// Every hop costs this much on top of its distance, so paths don't bounce around needlessly
+const LINK_COST: f32 = TILE_WIDTH;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NavLinkKind {
+    Jump,
+    // Walking off the edge
+    Drop,
+}
+
+#[derive(Clone, Debug)]
+pub struct NavLink {
+    pub to: usize,
+    pub kind: NavLinkKind,
+    // Where to leave the surface from, heading in `direction`
+    pub exit_x: f32,
+    pub direction: f32,
+    cost: f32,
+}
+
+// The walkable top of a run of tiles
+#[derive(Clone, Debug)]
+pub struct NavSurface {
+    pub left: f32,
+    pub right: f32,
+    pub y: f32,
+    pub links: Vec<NavLink>,
+}
+
+impl NavSurface {
+    fn contains_x(&self, x: f32) -> bool {
+        x >= self.left && x <= self.right
+    }
+}
+
+// Where an actor following a path should go next
+#[derive(Clone, Copy, Debug)]
+pub struct NavStep {
+    pub x: f32,
+    // Which way to keep going once at `x`
+    pub direction: f32,
+    pub jump: bool,
+}
+
+// How the level's platforms connect, rebuilt alongside the collision tree
+#[derive(Clone, Debug, Default)]
+pub struct NavGraph {
+    pub surfaces: Vec<NavSurface>,
+}
+
+impl NavGraph {
+    pub fn build(rtree: &RTree<RTreeEntity>) -> NavGraph {
+        // The top of every tile, minus the parts other tiles sit on
+        let mut tops: Vec<(f32, f32, f32)> = Vec::new();
+        for tile in rtree.iter() {
+            let y = tile.pos.y + tile.half_size.y;
+            let (left, right) = (tile.pos.x - tile.half_size.x, tile.pos.x + tile.half_size.x);
+            let mut spans = vec![(left, right)];
+            let above = AABB::from_corners([left, y + 1.0], [right, y + 1.0]);
+            for other in rtree.locate_in_envelope_intersecting(&above) {
+                let cut_left = other.pos.x - other.half_size.x;
+                let cut_right = other.pos.x + other.half_size.x;
+                spans = spans
+                    .into_iter()
+                    .flat_map(|(left, right)| {
+                        let mut rest = Vec::new();
+                        if cut_left > left {
+                            rest.push((left, cut_left.min(right)));
+                        }
+                        if cut_right < right {
+                            rest.push((cut_right.max(left), right));
+                        }
+                        rest
+                    })
+                    .collect();
+            }
+            tops.extend(
+                spans
+                    .into_iter()
+                    .filter(|(left, right)| right - left > 1.0)
+                    .map(|(left, right)| (y, left, right)),
+            );
+        }
+
+        // Join up neighbouring tops at the same height
+        tops.sort_by(|a, b| {
+            (a.0, a.1)
+                .partial_cmp(&(b.0, b.1))
+                .unwrap_or(Ordering::Equal)
+        });
+        let mut surfaces: Vec<NavSurface> = Vec::new();
+        for (y, left, right) in tops {
+            match surfaces.last_mut() {
+                Some(last) if (last.y - y).abs() < 1.0 && left <= last.right + 1.0 => {
+                    last.right = last.right.max(right);
+                }
+                _ => surfaces.push(NavSurface {
+                    left,
+                    right,
+                    y,
+                    links: Vec::new(),
+                }),
+            }
+        }
+
+        for from in 0..surfaces.len() {
+            for to in 0..surfaces.len() {
+                if from == to {
+                    continue;
+                }
+                if let Some(link) = Self::link(&surfaces[from], &surfaces[to], to) {
+                    surfaces[from].links.push(link);
+                }
+            }
+        }
+        NavGraph { surfaces }
+    }
+
+    // The cheapest way from one surface onto another, if there is one
+    fn link(from: &NavSurface, to: &NavSurface, index: usize) -> Option<NavLink> {
+        let rise = to.y - from.y;
+        if rise > MAX_JUMP_HEIGHT {
+            return None;
+        }
+        let beside = TILE_WIDTH / 2.0;
+        // (exit_x, direction, gap)
+        let mut exits = Vec::new();
+        // Off either end, when `to` carries on past it without hanging over our heads
+        if to.right > from.right && (rise <= 0.0 || to.left >= from.right - 1.0) {
+            exits.push((from.right, 1.0, (to.left - from.right).max(0.0)));
+        }
+        if to.left < from.left && (rise <= 0.0 || to.right <= from.left + 1.0) {
+            exits.push((from.left, -1.0, (from.left - to.right).max(0.0)));
+        }
+        // Up from beside a platform above the middle of this one
+        if rise > 0.0 {
+            if from.contains_x(to.left - beside) {
+                exits.push((to.left - beside, 1.0, 0.0));
+            }
+            if from.contains_x(to.right + beside) {
+                exits.push((to.right + beside, -1.0, 0.0));
+            }
+        }
+
+        exits
+            .into_iter()
+            .filter(|(_, _, gap)| *gap <= MAX_JUMP_DISTANCE)
+            .map(|(exit_x, direction, gap)| NavLink {
+                to: index,
+                kind: match rise > 0.0 || gap > TILE_WIDTH / 4.0 {
+                    true => NavLinkKind::Jump,
+                    false => NavLinkKind::Drop,
+                },
+                exit_x,
+                direction,
+                cost: gap + rise.abs() + LINK_COST,
+            })
+            .min_by(|a, b| a.cost.partial_cmp(&b.cost).unwrap_or(Ordering::Equal))
+    }
+
+    // The highest surface at or below the point
+    pub fn surface_below(&self, point: Vec2) -> Option<usize> {
+        self.surfaces
+            .iter()
+            .enumerate()
+            .filter(|(_, surface)| surface.contains_x(point.x) && surface.y <= point.y)
+            .max_by(|(_, a), (_, b)| a.y.partial_cmp(&b.y).unwrap_or(Ordering::Equal))
+            .map(|(i, _)| i)
+    }
+
+    // The first link on the cheapest path between the surfaces
+    fn first_link(&self, start: usize, goal: usize) -> Option<&NavLink> {
+        let count = self.surfaces.len();
+        let mut costs = vec![std::f32::INFINITY; count];
+        // The link from `start` each surface was first reached through
+        let mut first: Vec<Option<&NavLink>> = vec![None; count];
+        let mut done = vec![false; count];
+        costs[start] = 0.0;
+        loop {
+            let current = (0..count)
+                .filter(|i| !done[*i] && costs[*i].is_finite())
+                .min_by(|a, b| costs[*a].partial_cmp(&costs[*b]).unwrap_or(Ordering::Equal))?;
+            if current == goal {
+                return first[goal];
+            }
+            done[current] = true;
+            for link in self.surfaces[current].links.iter() {
+                let cost = costs[current] + link.cost;
+                if cost < costs[link.to] {
+                    costs[link.to] = cost;
+                    first[link.to] = match current == start {
+                        true => Some(link),
+                        false => first[current],
+                    };
+                }
+            }
+        }
+    }
+
+    // Where an actor at `from` should head to get to `to`, both being the centres of actors
+    pub fn next_step(&self, from: Vec2, to: Vec2) -> Option<NavStep> {
+        let start = self.surface_below(from)?;
+        let goal = self.surface_below(to)?;
+        if start == goal {
+            return Some(NavStep {
+                x: to.x,
+                direction: (to.x - from.x).signum(),
+                jump: false,
+            });
+        }
+        self.first_link(start, goal).map(|link| NavStep {
+            x: link.exit_x,
+            direction: link.direction,
+            jump: link.kind == NavLinkKind::Jump,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::ecs::{Builder, World, WorldExt};
+
+    // Rows of tiles as (first column, last column, row)
+    fn graph(rows: &[(i32, i32, i32)]) -> NavGraph {
+        let mut world = World::new();
+        let mut tiles = Vec::new();
+        for &(first, last, row) in rows {
+            for column in first..=last {
+                let pos = Vec2::new(column as f32 * TILE_WIDTH, row as f32 * TILE_HEIGHT);
+                let half_size = Vec2::new(TILE_WIDTH / 2.0, TILE_HEIGHT / 2.0);
+                tiles.push(RTreeEntity::new(
+                    pos,
+                    half_size,
+                    world.create_entity().build(),
+                ));
+            }
+        }
+        NavGraph::build(&RTree::bulk_load(tiles))
+    }
+
+    fn above(column: f32, row: f32) -> Vec2 {
+        Vec2::new(column * TILE_WIDTH, (row + 1.0) * TILE_HEIGHT)
+    }
+
+    #[test]
+    fn neighbouring_tiles_make_one_surface() {
+        let graph = graph(&[(0, 9, 0)]);
+        assert_eq!(graph.surfaces.len(), 1);
+        let surface = &graph.surfaces[0];
+        assert_eq!(surface.left, -TILE_WIDTH / 2.0);
+        assert_eq!(surface.right, 9.5 * TILE_WIDTH);
+        assert_eq!(surface.y, TILE_HEIGHT / 2.0);
+    }
+
+    #[test]
+    fn covered_tiles_are_not_walkable() {
+        // A block sitting in the middle of the floor
+        let graph = graph(&[(0, 9, 0), (5, 5, 1)]);
+        let floor_y = TILE_HEIGHT / 2.0;
+        let floors = graph.surfaces.iter().filter(|s| s.y == floor_y).count();
+        assert_eq!(floors, 2);
+    }
+
+    #[test]
+    fn same_surface_walks_straight_there() {
+        let graph = graph(&[(0, 9, 0)]);
+        let step = graph.next_step(above(1.0, 0.0), above(8.0, 0.0)).unwrap();
+        assert_eq!(step.x, 8.0 * TILE_WIDTH);
+        assert_eq!(step.direction, 1.0);
+        assert!(!step.jump);
+    }
+
+    #[test]
+    fn jumps_up_onto_a_ledge() {
+        let graph = graph(&[(0, 9, 0), (6, 9, 1)]);
+        let step = graph.next_step(above(1.0, 0.0), above(8.0, 1.0)).unwrap();
+        assert!(step.jump);
+        assert_eq!(step.direction, 1.0);
+        assert!(step.x <= 5.5 * TILE_WIDTH);
+    }
+
+    #[test]
+    fn walks_off_a_ledge() {
+        let graph = graph(&[(0, 9, 0), (6, 9, 1)]);
+        let step = graph.next_step(above(8.0, 1.0), above(1.0, 0.0)).unwrap();
+        assert!(!step.jump);
+        assert_eq!(step.direction, -1.0);
+        assert_eq!(step.x, 5.5 * TILE_WIDTH);
+    }
+
+    #[test]
+    fn too_high_has_no_path() {
+        let graph = graph(&[(0, 9, 0), (6, 9, 4)]);
+        assert!(graph.next_step(above(1.0, 0.0), above(8.0, 4.0)).is_none());
+    }
+}
@@ -0,0 +1,382 @@
+use crate::{
+    components::{
+        game::{CollisionEvent, TimedExistence},
+        graphics::{Scale, SPRITE_SHEET_TILES},
+        physics::Position,
+    },
+    events::{DamageEvent, PlayerEvent},
+    states::{
+        loading::{get_sprite_sheet, AssetsDir},
+        pizzatopia::DEPTH_PROJECTILES,
+    },
+};
+use amethyst::{
+    core::{
+        shrev::{EventChannel, ReaderId},
+        timing::Time,
+        Transform,
+    },
+    derive::SystemDesc,
+    ecs::{
+        prelude::{Component, DenseVecStorage},
+        Entities, Join, LazyUpdate, Read, ReadExpect, ReadStorage, System, SystemData, World,
+        WorldExt, WriteStorage,
+    },
+    prelude::Builder,
+    renderer::{palette::Srgba, resources::Tint, SpriteRender, Transparent},
+};
+use derivative::Derivative;
+use log::error;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::File};
+use ultraviolet::Vec2;
+
+pub const PARTICLE_EMITTERS: &str = "particles/emitters.ron";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ParticleEffect {
+    Land,
+    Hit,
+    Death,
+    Pickup,
+    Block,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct EmitterDef {
+    #[derivative(Default(value = "String::from(SPRITE_SHEET_TILES)"))]
+    pub sheet: String,
+    // Sprites the particles go through over their lifetime
+    #[derivative(Default(value = "vec![5]"))]
+    pub frames: Vec<usize>,
+    #[derivative(Default(value = "0.1"))]
+    pub scale: f32,
+    // Particles spawned at once when the emitter is created
+    pub burst: u32,
+    // Particles per second, for as long as the emitter lasts
+    pub rate: f32,
+    // Seconds the emitter keeps spawning particles for, 0 for a single burst
+    pub duration: f32,
+    // Seconds, picked between the two
+    #[derivative(Default(value = "(0.5, 0.5)"))]
+    pub lifetime: (f32, f32),
+    // Pixels per second, picked between the two
+    #[derivative(Default(value = "(100.0, 100.0)"))]
+    pub speed: (f32, f32),
+    // Degrees, 0 is right and 90 is up
+    #[derivative(Default(value = "90.0"))]
+    pub direction: f32,
+    // Degrees either side of `direction` a particle can go
+    #[derivative(Default(value = "180.0"))]
+    pub spread: f32,
+    // Pixels per second squared, pulling downwards
+    pub gravity: f32,
+    // Red, green, blue and alpha blended over the particle's lifetime
+    #[derivative(Default(value = "vec![(1.0, 1.0, 1.0, 1.0), (1.0, 1.0, 1.0, 0.0)]"))]
+    pub colors: Vec<(f32, f32, f32, f32)>,
+}
+
+// Every emitter in `particles/emitters.ron`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ParticleEmitters(pub BTreeMap<ParticleEffect, EmitterDef>);
+
+impl ParticleEmitters {
+    pub fn load(world: &World) -> ParticleEmitters {
+        let path = world.read_resource::<AssetsDir>().0.join(PARTICLE_EMITTERS);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open particle emitters {:?}: {}", path, e);
+                return ParticleEmitters::default();
+            }
+        };
+        match ron::de::from_reader(file) {
+            Ok(emitters) => emitters,
+            Err(e) => {
+                error!("Failed to parse particle emitters {:?}: {}", path, e);
+                ParticleEmitters::default()
+            }
+        }
+    }
+}
+
+// Keeps spawning particles at its position until its `TimedExistence` runs out
+pub struct ParticleEmitter {
+    pub def: EmitterDef,
+    // Fractional particles owed from previous frames
+    pub accumulator: f32,
+}
+
+impl Component for ParticleEmitter {
+    type Storage = DenseVecStorage<Self>;
+}
+
+pub struct Particle {
+    // Pixels per second
+    pub velocity: Vec2,
+    pub gravity: f32,
+    pub age: f32,
+    pub lifetime: f32,
+    pub frames: Vec<usize>,
+    pub colors: Vec<(f32, f32, f32, f32)>,
+}
+
+impl Component for Particle {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl Particle {
+    fn progress(&self) -> f32 {
+        (self.age / self.lifetime).max(0.0).min(1.0)
+    }
+
+    fn sprite(&self) -> usize {
+        let index = (self.progress() * self.frames.len() as f32) as usize;
+        *self
+            .frames
+            .get(index.min(self.frames.len().saturating_sub(1)))
+            .unwrap_or(&0)
+    }
+
+    fn color(&self) -> Srgba {
+        let (r, g, b, a) = match self.colors.len() {
+            0 => (1.0, 1.0, 1.0, 1.0),
+            1 => self.colors[0],
+            len => {
+                let position = self.progress() * (len - 1) as f32;
+                let index = (position as usize).min(len - 2);
+                let t = position - index as f32;
+                let (from, to) = (self.colors[index], self.colors[index + 1]);
+                (
+                    from.0 + (to.0 - from.0) * t,
+                    from.1 + (to.1 - from.1) * t,
+                    from.2 + (to.2 - from.2) * t,
+                    from.3 + (to.3 - from.3) * t,
+                )
+            }
+        };
+        Srgba::new(r, g, b, a)
+    }
+}
+
+fn random_between(rng: &mut impl Rng, range: (f32, f32)) -> f32 {
+    match range.0 < range.1 {
+        true => rng.gen_range(range.0, range.1),
+        false => range.0,
+    }
+}
+
+pub fn spawn_particles(world: &mut World, def: &EmitterDef, pos: Vec2, count: u32) {
+    let sprite_sheet = match get_sprite_sheet(world, &def.sheet) {
+        Some(sprite_sheet) => sprite_sheet,
+        None => return,
+    };
+    let mut rng = rand::thread_rng();
+    for _ in 0..count {
+        let angle =
+            (def.direction + random_between(&mut rng, (-def.spread, def.spread))).to_radians();
+        let speed = random_between(&mut rng, def.speed);
+        let particle = Particle {
+            velocity: Vec2::new(angle.cos() * speed, angle.sin() * speed),
+            gravity: def.gravity,
+            age: 0.0,
+            lifetime: random_between(&mut rng, def.lifetime).max(0.01),
+            frames: def.frames.clone(),
+            colors: def.colors.clone(),
+        };
+
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(pos.x, pos.y, DEPTH_PROJECTILES);
+        let sprite_render = SpriteRender {
+            sprite_sheet: sprite_sheet.clone(),
+            sprite_number: particle.sprite(),
+        };
+
+        world
+            .create_entity()
+            .with(transform)
+            .with(sprite_render)
+            .with(Position(pos))
+            .with(Scale(Vec2::new(def.scale, def.scale)))
+            .with(Tint(particle.color()))
+            .with(Transparent)
+            .with(TimedExistence(particle.lifetime))
+            .with(particle)
+            .build();
+    }
+}
+
+// Spawns the effect's burst, and an emitter for the rest if it lasts a while
+pub fn spawn_particle_effect(world: &mut World, effect: ParticleEffect, pos: Vec2) {
+    let def = match world.read_resource::<ParticleEmitters>().0.get(&effect) {
+        Some(def) => def.clone(),
+        None => return,
+    };
+    spawn_particles(world, &def, pos, def.burst);
+    if def.duration > 0.0 && def.rate > 0.0 {
+        world
+            .create_entity()
+            .with(Position(pos))
+            .with(TimedExistence(def.duration))
+            .with(ParticleEmitter {
+                def,
+                accumulator: 0.0,
+            })
+            .build();
+    }
+}
+
+#[derive(SystemDesc)]
+pub struct ParticleEmitterSystem;
+
+impl<'s> System<'s> for ParticleEmitterSystem {
+    type SystemData = (
+        WriteStorage<'s, ParticleEmitter>,
+        ReadStorage<'s, Position>,
+        Read<'s, LazyUpdate>,
+        Read<'s, Time>,
+    );
+
+    fn run(&mut self, (mut emitters, positions, lazy, time): Self::SystemData) {
+        for (emitter, position) in (&mut emitters, &positions).join() {
+            emitter.accumulator += emitter.def.rate * time.delta_seconds();
+            let count = emitter.accumulator as u32;
+            if count == 0 {
+                continue;
+            }
+            emitter.accumulator -= count as f32;
+            let def = emitter.def.clone();
+            let pos = position.0;
+            lazy.exec_mut(move |world| spawn_particles(world, &def, pos, count));
+        }
+    }
+}
+
+#[derive(SystemDesc)]
+pub struct ParticleSystem;
+
+impl<'s> System<'s> for ParticleSystem {
+    type SystemData = (
+        WriteStorage<'s, Particle>,
+        WriteStorage<'s, Position>,
+        WriteStorage<'s, SpriteRender>,
+        WriteStorage<'s, Tint>,
+        Read<'s, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (mut particles, mut positions, mut sprites, mut tints, time): Self::SystemData,
+    ) {
+        let delta = time.delta_seconds();
+        for (particle, position, sprite, tint) in
+            (&mut particles, &mut positions, &mut sprites, &mut tints).join()
+        {
+            particle.age += delta;
+            particle.velocity.y -= particle.gravity * delta;
+            position.0 += particle.velocity * delta;
+            sprite.sprite_number = particle.sprite();
+            tint.0 = particle.color();
+        }
+    }
+}
+
+// Turns gameplay events into particle effects, like `AudioEventSystem` does for sounds
+#[derive(SystemDesc)]
+#[system_desc(name(ParticleEventSystemDesc))]
+pub struct ParticleEventSystem {
+    #[system_desc(event_channel_reader)]
+    collision_reader: ReaderId<CollisionEvent>,
+    #[system_desc(event_channel_reader)]
+    player_reader: ReaderId<PlayerEvent>,
+    #[system_desc(event_channel_reader)]
+    damage_reader: ReaderId<DamageEvent>,
+}
+
+impl ParticleEventSystem {
+    pub(crate) fn new(
+        collision_reader: ReaderId<CollisionEvent>,
+        player_reader: ReaderId<PlayerEvent>,
+        damage_reader: ReaderId<DamageEvent>,
+    ) -> Self {
+        Self {
+            collision_reader,
+            player_reader,
+            damage_reader,
+        }
+    }
+}
+
+impl<'s> System<'s> for ParticleEventSystem {
+    type SystemData = (
+        Read<'s, EventChannel<CollisionEvent>>,
+        Read<'s, EventChannel<PlayerEvent>>,
+        Read<'s, EventChannel<DamageEvent>>,
+        ReadStorage<'s, Position>,
+        Entities<'s>,
+        Read<'s, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            collision_channel,
+            player_channel,
+            damage_channel,
+            positions,
+            entities,
+            lazy,
+        ): Self::SystemData,
+    ) {
+        let mut effects = Vec::new();
+        let pos_of = |id: u32| positions.get(entities.entity(id)).map(|pos| pos.0);
+
+        // A killing blow shows the death effect instead of the hit one
+        let damage_events: Vec<&DamageEvent> =
+            damage_channel.read(&mut self.damage_reader).collect();
+        for event in damage_events.iter() {
+            match event {
+                DamageEvent::Damaged { id, pos, .. } => {
+                    let died = damage_events.iter().any(|other| match other {
+                        DamageEvent::Died { id: dead, .. } => dead == id,
+                        _ => false,
+                    });
+                    let effect = match died {
+                        true => ParticleEffect::Death,
+                        false => ParticleEffect::Hit,
+                    };
+                    effects.push((effect, *pos));
+                }
+                DamageEvent::Died { .. } => {}
+            }
+        }
+        for event in collision_channel.read(&mut self.collision_reader) {
+            match event {
+                CollisionEvent::ProjectileBlock(id) => {
+                    effects.push((ParticleEffect::Block, pos_of(*id)));
+                }
+                CollisionEvent::ItemCollect(_, item_id) => {
+                    effects.push((ParticleEffect::Pickup, pos_of(*item_id)));
+                }
+                _ => {}
+            }
+        }
+        for event in player_channel.read(&mut self.player_reader) {
+            match event {
+                PlayerEvent::Land(pos) => {
+                    effects.push((ParticleEffect::Land, Some(*pos)));
+                }
+                _ => {}
+            }
+        }
+
+        for (effect, pos) in effects {
+            if let Some(pos) = pos {
+                lazy.exec_mut(move |world| spawn_particle_effect(world, effect, pos));
+            }
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use crate::{
+    components::{
+        editor::EditorFlag,
+        game::{CollisionEvent, Dormant, Health, Team},
+        graphics::SPRITE_SHEET_TILES,
+        physics::{Orientation, Position, Velocity},
+    },
+    events::Events,
+    factions::Factions,
+    states::{
+        loading::AssetsDir,
+        pizzatopia::{TILE_HEIGHT, TILE_WIDTH},
+    },
+};
+use amethyst::{
+    core::{
+        shrev::{EventChannel, ReaderId},
+        timing::Time,
+    },
+    derive::SystemDesc,
+    ecs::{
+        prelude::{Component, DenseVecStorage},
+        Entities, Join, Read, ReadStorage, System, SystemData, World, WorldExt, Write,
+        WriteStorage,
+    },
+};
+use derivative::Derivative;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::File, ops::Mul};
+use ultraviolet::Vec2;
+
+pub const PROJECTILES: &str = "projectiles/projectiles.ron";
+// Used by anything that fires without naming a kind
+pub const DEFAULT_PROJECTILE: &str = "default";
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct ProjectileDef {
+    #[derivative(Default(value = "String::from(SPRITE_SHEET_TILES)"))]
+    pub sheet: String,
+    #[derivative(Default(value = "5"))]
+    pub sprite: usize,
+    #[derivative(Default(value = "Vec2::new(TILE_WIDTH / 3.0, TILE_HEIGHT / 3.0)"))]
+    pub size: Vec2,
+    // Pixels per frame, for shooters that aim it themselves
+    #[derivative(Default(value = "12.0"))]
+    pub speed: f32,
+    // Pulls it down every frame, actors fall with 0.4
+    pub gravity: f32,
+    // Seconds before it goes away on its own
+    #[derivative(Default(value = "10.0"))]
+    pub lifetime: f32,
+    #[derivative(Default(value = "1"))]
+    pub damage: u32,
+    #[derivative(Default(value = "Vec2::new(6.0, 3.0)"))]
+    pub knockback: Vec2,
+    // Enemies it goes through before it's used up
+    pub pierce: u32,
+    // Degrees per second it turns towards the nearest enemy, 0 to fly straight
+    pub homing: f32,
+    #[derivative(Default(value = "600.0"))]
+    pub homing_range: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum FirePattern {
+    Single,
+    // `count` projectiles fanned out evenly over `angle` degrees
+    Spread { count: u32, angle: f32 },
+    // `count` projectiles one after the other, `interval` seconds apart
+    Burst { count: u32, interval: f32 },
+    // `count` projectiles evenly around a circle, turned `step` degrees further every volley
+    Spiral { count: u32, step: f32 },
+}
+
+impl Default for FirePattern {
+    fn default() -> Self {
+        FirePattern::Single
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Aim {
+    // The way the shooter is facing
+    Forward,
+    // The nearest enemy within range, holding fire when there's none
+    AtTarget,
+}
+
+impl Default for Aim {
+    fn default() -> Self {
+        Aim::Forward
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct ShooterDef {
+    #[derivative(Default(value = "String::from(DEFAULT_PROJECTILE)"))]
+    pub projectile: String,
+    pub pattern: FirePattern,
+    pub aim: Aim,
+    // Seconds between volleys
+    #[derivative(Default(value = "2.0"))]
+    pub cooldown: f32,
+    #[derivative(Default(value = "800.0"))]
+    pub range: f32,
+}
+
+// Every projectile kind and shooter in `projectiles/projectiles.ron`, by name
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectileLibrary {
+    pub projectiles: BTreeMap<String, ProjectileDef>,
+    pub shooters: BTreeMap<String, ShooterDef>,
+}
+
+impl ProjectileLibrary {
+    pub fn load(world: &World) -> ProjectileLibrary {
+        let path = world.read_resource::<AssetsDir>().0.join(PROJECTILES);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open projectiles {:?}: {}", path, e);
+                return ProjectileLibrary::default();
+            }
+        };
+        match ron::de::from_reader(file) {
+            Ok(library) => library,
+            Err(e) => {
+                error!("Failed to parse projectiles {:?}: {}", path, e);
+                ProjectileLibrary::default()
+            }
+        }
+    }
+
+    pub fn projectile(&self, name: &str) -> ProjectileDef {
+        match self.projectiles.get(name) {
+            Some(def) => def.clone(),
+            None => {
+                if name != DEFAULT_PROJECTILE {
+                    error!("Unknown projectile {:?}", name);
+                }
+                ProjectileDef::default()
+            }
+        }
+    }
+}
+
+// How a projectile moves and what it's hit so far
+#[derive(Clone, Debug, Default)]
+pub struct ProjectileStats {
+    pub gravity: f32,
+    pub homing: f32,
+    pub homing_range: f32,
+    pub pierce: u32,
+    pub(crate) hit: Vec<u32>,
+}
+
+impl ProjectileStats {
+    pub fn new(def: &ProjectileDef) -> ProjectileStats {
+        ProjectileStats {
+            gravity: def.gravity,
+            homing: def.homing,
+            homing_range: def.homing_range,
+            pierce: def.pierce,
+            hit: Vec::new(),
+        }
+    }
+}
+
+impl Component for ProjectileStats {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Fires the named shooter from the library, either on its own or when asked to
+#[derive(Clone, Debug, Default)]
+pub struct Shooter {
+    pub name: String,
+    // Fire whenever ready rather than waiting for `pull`
+    pub automatic: bool,
+    pub(crate) pull: bool,
+    pub(crate) cooldown: f32,
+    // Current angle of a spiral, in degrees
+    pub(crate) spiral: f32,
+    pub(crate) burst_left: u32,
+    pub(crate) burst_timer: f32,
+    pub(crate) burst_aim: Vec2,
+}
+
+impl Shooter {
+    pub fn new(name: &str, automatic: bool) -> Shooter {
+        Shooter {
+            name: String::from(name),
+            automatic,
+            ..Shooter::default()
+        }
+    }
+
+    // Asks to fire this frame, false when it can't
+    pub fn pull_trigger(&mut self) -> bool {
+        let ready = self.cooldown <= 0.0 && self.burst_left == 0;
+        self.pull |= ready;
+        ready
+    }
+}
+
+impl Component for Shooter {
+    type Storage = DenseVecStorage<Self>;
+}
+
+fn rotate(vec: Vec2, degrees: f32) -> Vec2 {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    Vec2::new(vec.x * cos - vec.y * sin, vec.x * sin + vec.y * cos)
+}
+
+// Directions of the projectiles fired at once, `aim` being a unit vector
+fn volley(pattern: &FirePattern, aim: Vec2, spiral: f32) -> Vec<Vec2> {
+    match pattern {
+        FirePattern::Single | FirePattern::Burst { .. } => vec![aim],
+        FirePattern::Spread { count, angle } => match *count {
+            0 => Vec::new(),
+            1 => vec![aim],
+            count => (0..count)
+                .map(|i| rotate(aim, -angle / 2.0 + angle * i as f32 / (count - 1) as f32))
+                .collect(),
+        },
+        FirePattern::Spiral { count, .. } => (0..*count)
+            .map(|i| {
+                rotate(
+                    Vec2::new(1.0, 0.0),
+                    spiral + 360.0 * i as f32 / *count as f32,
+                )
+            })
+            .collect(),
+    }
+}
+
+// Position of the nearest living enemy of `team` within `range`
+fn nearest_enemy(
+    targets: &[(Vec2, Team)],
+    factions: &Factions,
+    pos: Vec2,
+    team: &Team,
+    range: f32,
+) -> Option<Vec2> {
+    targets
+        .iter()
+        .filter(|(target, target_team)| {
+            factions.hostile(team, target_team) && (*target - pos).mag() <= range
+        })
+        .map(|(target, _)| *target)
+        .min_by(|a, b| {
+            (*a - pos)
+                .mag_sq()
+                .partial_cmp(&(*b - pos).mag_sq())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+fn living_targets(
+    positions: &ReadStorage<Position>,
+    teams: &ReadStorage<Team>,
+    healths: &ReadStorage<Health>,
+) -> Vec<(Vec2, Team)> {
+    (positions, teams, healths)
+        .join()
+        .filter(|(_, _, health)| health.alive())
+        .map(|(pos, team, _)| (pos.0, *team))
+        .collect()
+}
+
+#[derive(SystemDesc)]
+pub struct ShooterSystem;
+
+impl<'s> System<'s> for ShooterSystem {
+    type SystemData = (
+        WriteStorage<'s, Shooter>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, Orientation>,
+        ReadStorage<'s, Team>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Dormant>,
+        ReadStorage<'s, EditorFlag>,
+        Read<'s, ProjectileLibrary>,
+        Read<'s, Factions>,
+        Write<'s, EventChannel<Events>>,
+        Read<'s, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut shooters,
+            positions,
+            orientations,
+            teams,
+            healths,
+            dormants,
+            editor_flags,
+            library,
+            factions,
+            mut events_channel,
+            time,
+        ): Self::SystemData,
+    ) {
+        let targets = living_targets(&positions, &teams, &healths);
+        for (shooter, pos, orientation, team, health, _, _) in (
+            &mut shooters,
+            &positions,
+            (&orientations).maybe(),
+            &teams,
+            (&healths).maybe(),
+            !&dormants,
+            !&editor_flags,
+        )
+            .join()
+        {
+            let pulled = shooter.automatic || shooter.pull;
+            shooter.pull = false;
+            shooter.cooldown = (shooter.cooldown - time.delta_seconds()).max(0.0);
+            if health.map_or(false, |health| !health.alive()) {
+                continue;
+            }
+            let def = match library.shooters.get(&shooter.name) {
+                Some(def) => def,
+                None => continue,
+            };
+            let speed = library.projectile(&def.projectile).speed;
+            let mut fire = |direction: Vec2| {
+                events_channel.single_write(Events::FireProjectile(
+                    def.projectile.clone(),
+                    pos.0,
+                    direction.mul(speed),
+                    *team,
+                ));
+            };
+
+            if pulled && shooter.cooldown <= 0.0 && shooter.burst_left == 0 {
+                let aim = match def.aim {
+                    Aim::Forward => match orientation.map_or(1.0, |o| o.vec.x) < 0.0 {
+                        true => Some(Vec2::new(-1.0, 0.0)),
+                        false => Some(Vec2::new(1.0, 0.0)),
+                    },
+                    Aim::AtTarget => nearest_enemy(&targets, &factions, pos.0, team, def.range)
+                        .map(|target| (target - pos.0).normalized()),
+                };
+                if let Some(aim) = aim {
+                    shooter.cooldown = def.cooldown;
+                    match def.pattern {
+                        FirePattern::Burst { count, .. } => {
+                            shooter.burst_left = count;
+                            shooter.burst_timer = 0.0;
+                            shooter.burst_aim = aim;
+                        }
+                        FirePattern::Spiral { step, .. } => {
+                            volley(&def.pattern, aim, shooter.spiral)
+                                .into_iter()
+                                .for_each(&mut fire);
+                            shooter.spiral = (shooter.spiral + step) % 360.0;
+                        }
+                        _ => volley(&def.pattern, aim, shooter.spiral)
+                            .into_iter()
+                            .for_each(&mut fire),
+                    }
+                }
+            }
+
+            if shooter.burst_left > 0 {
+                shooter.burst_timer -= time.delta_seconds();
+                if shooter.burst_timer <= 0.0 {
+                    fire(shooter.burst_aim);
+                    shooter.burst_left -= 1;
+                    shooter.burst_timer = match def.pattern {
+                        FirePattern::Burst { interval, .. } => interval,
+                        _ => 0.0,
+                    };
+                }
+            }
+        }
+    }
+}
+
+// Gravity and homing
+#[derive(SystemDesc)]
+pub struct ProjectileSystem;
+
+impl<'s> System<'s> for ProjectileSystem {
+    type SystemData = (
+        WriteStorage<'s, Velocity>,
+        ReadStorage<'s, ProjectileStats>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, Team>,
+        ReadStorage<'s, Health>,
+        Read<'s, Factions>,
+        Read<'s, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (mut velocities, stats, positions, teams, healths, factions, time): Self::SystemData,
+    ) {
+        let targets = living_targets(&positions, &teams, &healths);
+        for (velocity, stats, pos, team) in (&mut velocities, &stats, &positions, &teams).join() {
+            velocity.0.y -= stats.gravity * time.time_scale();
+            if stats.homing <= 0.0 {
+                continue;
+            }
+            if let Some(target) =
+                nearest_enemy(&targets, &factions, pos.0, team, stats.homing_range)
+            {
+                let to_target = target - pos.0;
+                let difference = (to_target.y.atan2(to_target.x)
+                    - velocity.0.y.atan2(velocity.0.x))
+                .to_degrees();
+                // Shortest way around
+                let difference = (difference + 540.0) % 360.0 - 180.0;
+                let max_turn = stats.homing * time.delta_seconds();
+                velocity.0 = rotate(velocity.0, difference.max(-max_turn).min(max_turn));
+            }
+        }
+    }
+}
+
+// Uses up projectiles as they hit things, each enemy only counting once
+#[derive(SystemDesc)]
+#[system_desc(name(ProjectileHitSystemDesc))]
+pub struct ProjectileHitSystem {
+    #[system_desc(event_channel_reader)]
+    reader: ReaderId<CollisionEvent>,
+}
+
+impl ProjectileHitSystem {
+    pub(crate) fn new(reader: ReaderId<CollisionEvent>) -> Self {
+        Self { reader }
+    }
+}
+
+impl<'s> System<'s> for ProjectileHitSystem {
+    type SystemData = (
+        WriteStorage<'s, ProjectileStats>,
+        ReadStorage<'s, Health>,
+        Read<'s, EventChannel<CollisionEvent>>,
+        Entities<'s>,
+    );
+
+    fn run(&mut self, (mut stats, healths, event_channel, entities): Self::SystemData) {
+        for event in event_channel.read(&mut self.reader) {
+            if let CollisionEvent::EnemyCollision(hitee, hitter, _, _) = event {
+                let projectile = entities.entity(*hitter);
+                let stats = match stats.get_mut(projectile) {
+                    Some(stats) => stats,
+                    None => continue,
+                };
+                let alive = healths
+                    .get(entities.entity(*hitee))
+                    .map_or(false, |health| health.alive());
+                if !alive || stats.hit.contains(hitee) {
+                    continue;
+                }
+                stats.hit.push(*hitee);
+                if stats.hit.len() as u32 == stats.pierce + 1 {
+                    if entities.delete(projectile).is_err() {
+                        error!("Failed to delete spent projectile");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use log::info;
+use rand::{rngs::StdRng, Error, RngCore, SeedableRng};
+
+// Where all gameplay randomness comes from. It's reseeded whenever a level starts, so a run can
+// be played again from the seed in the log. Cosmetic randomness like particles and screen shake
+// doesn't use it, so effects can't change how a run plays out.
+pub struct WorldRng {
+    rng: StdRng,
+    // What the current level started with
+    pub seed: u64,
+    // Used for every level start when set, otherwise each gets a fresh seed
+    pub fixed_seed: Option<u64>,
+}
+
+impl WorldRng {
+    pub fn new(fixed_seed: Option<u64>) -> WorldRng {
+        let mut rng = WorldRng {
+            rng: StdRng::seed_from_u64(0),
+            seed: 0,
+            fixed_seed,
+        };
+        rng.reseed();
+        rng
+    }
+
+    pub fn reseed(&mut self) {
+        self.seed = self.fixed_seed.unwrap_or_else(rand::random);
+        self.rng = StdRng::seed_from_u64(self.seed);
+        info!("Seeded the world RNG with {}", self.seed);
+    }
+}
+
+impl Default for WorldRng {
+    fn default() -> Self {
+        WorldRng::new(None)
+    }
+}
+
+impl RngCore for WorldRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    // Rolls a drop count and a spawn position, the way gameplay would
+    fn play(rng: &mut WorldRng) -> Vec<(u32, f32, f32)> {
+        (0..10)
+            .map(|_| {
+                (
+                    rng.gen_range(0, 5),
+                    rng.gen_range(-2.0, 2.0),
+                    rng.gen_range(7.0, 15.0),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_plays_the_same() {
+        let first = play(&mut WorldRng::new(Some(42)));
+        let second = play(&mut WorldRng::new(Some(42)));
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn different_seeds_play_differently() {
+        let first = play(&mut WorldRng::new(Some(1)));
+        let second = play(&mut WorldRng::new(Some(2)));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn reseeding_starts_over() {
+        let mut rng = WorldRng::new(Some(7));
+        let first = play(&mut rng);
+        rng.reseed();
+        assert_eq!(rng.seed, 7);
+        assert_eq!(play(&mut rng), first);
+    }
+}
@@ -0,0 +1,617 @@
+use crate::{
+    components::{
+        game::{CollisionEvent, DamageKind, Dormant, Health, Player, Team},
+        physics::{Collidee, CollisionSideOfBlock, Grounded, Orientation, Position, Velocity},
+    },
+    events::{DamageEvent, Events},
+    projectiles::DEFAULT_PROJECTILE,
+    states::loading::AssetsDir,
+};
+use amethyst::{
+    core::{
+        shrev::{EventChannel, ReaderId},
+        timing::Time,
+    },
+    derive::SystemDesc,
+    ecs::{
+        prelude::{Component, DenseVecStorage, Entity},
+        Entities, Join, Read, ReadExpect, ReadStorage, System, SystemData, World, Write,
+        WriteStorage,
+    },
+};
+use log::{error, info};
+use rhai::{Dynamic, Engine, FuncArgs, ImmutableString, Map, Scope, AST, FLOAT, INT};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+use ultraviolet::Vec2;
+
+// Operations a single callback can run before it's stopped, so a runaway loop can't hang the game
+const SCRIPT_MAX_OPERATIONS: u64 = 100_000;
+const SCRIPT_MAX_CALL_LEVELS: usize = 32;
+const SCRIPT_MAX_COLLECTION_SIZE: usize = 1024;
+
+// A Rhai script driving an actor. It can define any of:
+//   fn on_spawn(e)             once, before its first update
+//   fn update(e, dt)           every frame
+//   fn on_collision(e, side)   touching a platform, side is "left", "right", "top" or "bottom"
+//   fn on_hit(e, damage)       took damage, after resistances
+pub struct Script {
+    // Path relative to the assets directory
+    pub path: String,
+    // Whatever the script stored with `e.set`, kept between frames
+    pub(crate) memory: Map,
+    // Set after an error so a broken script doesn't flood the log every frame
+    pub(crate) failed: bool,
+    // Whether `on_spawn` has run, the actor gets a fresh script whenever the level resets
+    pub(crate) spawned: bool,
+}
+
+impl Script {
+    pub fn new(path: &str) -> Script {
+        Script {
+            path: String::from(path),
+            memory: Map::new(),
+            failed: false,
+            spawned: false,
+        }
+    }
+}
+
+impl Component for Script {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Clone)]
+struct ScriptEntityData {
+    entity: Entity,
+    pos: Vec2,
+    vel: Vec2,
+    facing: Vec2,
+    health: u32,
+    // Health the script took away, dealt as damage once it's done
+    hurt: u32,
+    max_health: u32,
+    team: Team,
+    grounded: bool,
+    player: Option<Vec2>,
+    memory: Map,
+    events: Vec<Events>,
+}
+
+impl ScriptEntityData {
+    fn fire(&mut self, kind: &str, vel_x: FLOAT, vel_y: FLOAT) {
+        let event = Events::FireProjectile(
+            String::from(kind),
+            self.pos,
+            Vec2::new(vel_x as f32, vel_y as f32),
+            self.team,
+        );
+        self.events.push(event);
+    }
+}
+
+// The `e` handed to scripts. Copies of the actor's components, written back once the script
+// is done with them, so scripts never touch the world directly.
+#[derive(Clone)]
+pub struct ScriptEntity(Arc<Mutex<ScriptEntityData>>);
+
+impl ScriptEntity {
+    fn lock(&self) -> MutexGuard<ScriptEntityData> {
+        self.0.lock().expect("Script entity was poisoned")
+    }
+}
+
+fn team_name(team: &Team) -> ImmutableString {
+    match team {
+        Team::GoodGuys => "good".into(),
+        Team::BadGuys => "bad".into(),
+        Team::Neutral => "neutral".into(),
+        Team::Individual(id) => format!("individual {}", id).into(),
+    }
+}
+
+fn team_from_name(name: &str) -> Option<Team> {
+    match name {
+        "good" => Some(Team::GoodGuys),
+        "bad" => Some(Team::BadGuys),
+        "neutral" => Some(Team::Neutral),
+        _ => name
+            .strip_prefix("individual ")
+            .and_then(|id| id.parse().ok())
+            .map(Team::Individual),
+    }
+}
+
+fn side_name(side: &CollisionSideOfBlock) -> ImmutableString {
+    match side {
+        CollisionSideOfBlock::Left => "left".into(),
+        CollisionSideOfBlock::Right => "right".into(),
+        CollisionSideOfBlock::Top => "top".into(),
+        CollisionSideOfBlock::Bottom => "bottom".into(),
+    }
+}
+
+fn create_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+    engine.set_max_call_levels(SCRIPT_MAX_CALL_LEVELS);
+    engine.set_max_string_size(SCRIPT_MAX_COLLECTION_SIZE);
+    engine.set_max_array_size(SCRIPT_MAX_COLLECTION_SIZE);
+    engine.set_max_map_size(SCRIPT_MAX_COLLECTION_SIZE);
+    engine.on_print(|text| info!("{}", text));
+
+    engine.register_type_with_name::<ScriptEntity>("Entity");
+    engine.register_get_set(
+        "x",
+        |e: &mut ScriptEntity| e.lock().pos.x as FLOAT,
+        |e: &mut ScriptEntity, x: FLOAT| e.lock().pos.x = x as f32,
+    );
+    engine.register_get_set(
+        "y",
+        |e: &mut ScriptEntity| e.lock().pos.y as FLOAT,
+        |e: &mut ScriptEntity, y: FLOAT| e.lock().pos.y = y as f32,
+    );
+    engine.register_get_set(
+        "vel_x",
+        |e: &mut ScriptEntity| e.lock().vel.x as FLOAT,
+        |e: &mut ScriptEntity, x: FLOAT| e.lock().vel.x = x as f32,
+    );
+    engine.register_get_set(
+        "vel_y",
+        |e: &mut ScriptEntity| e.lock().vel.y as FLOAT,
+        |e: &mut ScriptEntity, y: FLOAT| e.lock().vel.y = y as f32,
+    );
+    // 1 for right, -1 for left
+    engine.register_get_set(
+        "facing",
+        |e: &mut ScriptEntity| e.lock().facing.x as FLOAT,
+        |e: &mut ScriptEntity, facing: FLOAT| {
+            e.lock().facing.x = match facing < 0.0 {
+                true => -1.0,
+                false => 1.0,
+            }
+        },
+    );
+    engine.register_get_set(
+        "health",
+        |e: &mut ScriptEntity| e.lock().health as INT,
+        |e: &mut ScriptEntity, health: INT| {
+            let mut data = e.lock();
+            let health = health.max(0) as u32;
+            match health < data.health {
+                true => data.hurt += data.health - health,
+                false => data.hurt -= data.hurt.min(health - data.health),
+            }
+            data.health = health;
+        },
+    );
+    engine.register_get_set(
+        "team",
+        |e: &mut ScriptEntity| team_name(&e.lock().team),
+        |e: &mut ScriptEntity, name: ImmutableString| match team_from_name(&name) {
+            Some(team) => e.lock().team = team,
+            None => error!("Scripts can't join unknown team {:?}", name),
+        },
+    );
+    engine.register_get("max_health", |e: &mut ScriptEntity| {
+        e.lock().max_health as INT
+    });
+    engine.register_get("grounded", |e: &mut ScriptEntity| e.lock().grounded);
+    engine.register_get("has_player", |e: &mut ScriptEntity| {
+        e.lock().player.is_some()
+    });
+    engine.register_get("player_x", |e: &mut ScriptEntity| {
+        e.lock().player.map_or(0.0, |pos| pos.x) as FLOAT
+    });
+    engine.register_get("player_y", |e: &mut ScriptEntity| {
+        e.lock().player.map_or(0.0, |pos| pos.y) as FLOAT
+    });
+    engine.register_fn("get", |e: &mut ScriptEntity, key: ImmutableString| {
+        e.lock()
+            .memory
+            .get(&key)
+            .cloned()
+            .unwrap_or_else(|| Dynamic::from(()))
+    });
+    engine.register_fn(
+        "set",
+        |e: &mut ScriptEntity, key: ImmutableString, value: Dynamic| {
+            e.lock().memory.insert(key, value);
+        },
+    );
+    engine.register_fn(
+        "fire_projectile",
+        |e: &mut ScriptEntity, vel_x: FLOAT, vel_y: FLOAT| {
+            e.lock().fire(DEFAULT_PROJECTILE, vel_x, vel_y);
+        },
+    );
+    // Any kind from the projectile library
+    engine.register_fn(
+        "fire_projectile",
+        |e: &mut ScriptEntity, kind: ImmutableString, vel_x: FLOAT, vel_y: FLOAT| {
+            e.lock().fire(&kind, vel_x, vel_y);
+        },
+    );
+    // Offset from the actor, in the direction it's facing. The box follows the actor around and
+    // is flipped along with it.
+    engine.register_fn(
+        "damage_box",
+        |e: &mut ScriptEntity, x: FLOAT, y: FLOAT, width: FLOAT, height: FLOAT| {
+            let mut data = e.lock();
+            let event = Events::CreateDamageBox(
+                Some(data.entity),
+                Vec2::new(x as f32, y as f32),
+                Vec2::new(width as f32, height as f32),
+                data.team,
+            );
+            data.events.push(event);
+        },
+    );
+    engine
+}
+
+fn load_script(engine: &Engine, assets_dir: &Path, path: &str) -> Option<AST> {
+    let path = assets_dir.join(path);
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) => {
+            error!("Failed to open script {:?}: {}", path, e);
+            return None;
+        }
+    };
+    match engine.compile(&source) {
+        Ok(ast) => Some(ast),
+        Err(e) => {
+            error!("Failed to parse script {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+// False if the script broke
+fn run_callback(
+    engine: &Engine,
+    ast: &AST,
+    path: &str,
+    callback: &str,
+    args: impl FuncArgs,
+) -> bool {
+    let mut values: Vec<Dynamic> = Vec::new();
+    args.parse(&mut values);
+    // Scripts only define the callbacks they need
+    let defined = ast
+        .iter_functions()
+        .any(|f| f.name == callback && f.params.len() == values.len());
+    if !defined {
+        return true;
+    }
+    let mut scope = Scope::new();
+    match engine.call_fn_dynamic(&mut scope, ast, true, callback, None, values) {
+        Ok(_) => true,
+        Err(e) => {
+            error!("Script {:?} failed in {}: {}", path, callback, e);
+            false
+        }
+    }
+}
+
+// Puts what the script changed back on the actor. Lost health isn't taken here, it's returned
+// to be dealt as a hit so that dying still goes through the usual hooks.
+fn copy_back(
+    data: ScriptEntityData,
+    script: &mut Script,
+    pos: &mut Position,
+    vel: &mut Velocity,
+    orientation: &mut Orientation,
+    health: &mut Health,
+    team: &mut Team,
+) -> (u32, Vec<Events>) {
+    pos.0 = data.pos;
+    vel.0 = data.vel;
+    orientation.vec = data.facing;
+    health.current = (data.health + data.hurt).min(health.max);
+    *team = data.team;
+    script.memory = data.memory;
+    (data.hurt, data.events)
+}
+
+#[derive(SystemDesc)]
+#[system_desc(name(ScriptSystemDesc))]
+pub struct ScriptSystem {
+    #[system_desc(event_channel_reader)]
+    damage_reader: ReaderId<DamageEvent>,
+    #[system_desc(skip)]
+    engine: Engine,
+    // Compiled scripts by path, `None` when they couldn't be loaded
+    #[system_desc(skip)]
+    scripts: BTreeMap<String, Option<AST>>,
+}
+
+impl ScriptSystem {
+    pub(crate) fn new(damage_reader: ReaderId<DamageEvent>) -> Self {
+        Self {
+            damage_reader,
+            engine: create_engine(),
+            scripts: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'s> System<'s> for ScriptSystem {
+    type SystemData = (
+        WriteStorage<'s, Script>,
+        WriteStorage<'s, Position>,
+        WriteStorage<'s, Velocity>,
+        WriteStorage<'s, Orientation>,
+        WriteStorage<'s, Health>,
+        WriteStorage<'s, Team>,
+        ReadStorage<'s, Collidee>,
+        ReadStorage<'s, Grounded>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Dormant>,
+        Read<'s, EventChannel<DamageEvent>>,
+        Write<'s, EventChannel<CollisionEvent>>,
+        Write<'s, EventChannel<Events>>,
+        ReadExpect<'s, AssetsDir>,
+        Read<'s, Time>,
+        Entities<'s>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut scripts,
+            mut positions,
+            mut velocities,
+            mut orientations,
+            mut healths,
+            mut teams,
+            collidees,
+            groundeds,
+            players,
+            dormants,
+            damage_channel,
+            mut collision_channel,
+            mut events_channel,
+            assets_dir,
+            time,
+            entities,
+        ): Self::SystemData,
+    ) {
+        // Damage actors took since the last frame
+        let mut hits = HashMap::new();
+        for event in damage_channel.read(&mut self.damage_reader) {
+            if let DamageEvent::Damaged { id, amount, .. } = event {
+                hits.insert(*id, *amount);
+            }
+        }
+
+        let player = (&players, &positions)
+            .join()
+            .find(|(player, _)| player.0)
+            .map(|(_, pos)| pos.0);
+        let dt = time.delta_seconds() as FLOAT;
+        let engine = &self.engine;
+        let loaded = &mut self.scripts;
+
+        for (entity, script, pos, vel, orientation, health, team, _) in (
+            &entities,
+            &mut scripts,
+            &mut positions,
+            &mut velocities,
+            &mut orientations,
+            &mut healths,
+            &mut teams,
+            !&dormants,
+        )
+            .join()
+        {
+            if script.failed || !health.alive() {
+                continue;
+            }
+            let ast = loaded
+                .entry(script.path.clone())
+                .or_insert_with(|| load_script(engine, &assets_dir.0, &script.path));
+            let ast = match ast {
+                Some(ast) => ast,
+                None => {
+                    script.failed = true;
+                    continue;
+                }
+            };
+
+            let e = ScriptEntity(Arc::new(Mutex::new(ScriptEntityData {
+                entity,
+                pos: pos.0,
+                vel: vel.0,
+                facing: orientation.vec,
+                health: health.current,
+                hurt: 0,
+                max_health: health.max,
+                team: *team,
+                grounded: groundeds.get(entity).map_or(false, |grounded| grounded.0),
+                player,
+                memory: std::mem::take(&mut script.memory),
+                events: Vec::new(),
+            })));
+
+            let path = &script.path;
+            let mut ok = true;
+            if !script.spawned {
+                script.spawned = true;
+                ok = run_callback(engine, ast, path, "on_spawn", (e.clone(),));
+            }
+            ok = ok && run_callback(engine, ast, path, "update", (e.clone(), dt));
+            if let Some(collidee) = collidees.get(entity) {
+                for details in collidee.horizontal.iter().chain(collidee.vertical.iter()) {
+                    let side = side_name(&details.side);
+                    ok = ok && run_callback(engine, ast, path, "on_collision", (e.clone(), side));
+                }
+            }
+            if let Some(damage) = hits.get(&entity.id()) {
+                let damage = *damage as INT;
+                ok = ok && run_callback(engine, ast, path, "on_hit", (e.clone(), damage));
+            }
+            script.failed = !ok;
+
+            let data = e.lock().clone();
+            let (hurt, events) = copy_back(data, script, pos, vel, orientation, health, team);
+            if hurt > 0 {
+                let id = entity.id();
+                let hit = CollisionEvent::EnemyCollision(id, id, hurt, DamageKind::Scripted);
+                collision_channel.single_write(hit);
+            }
+            for event in events {
+                events_channel.single_write(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::ecs::{Builder, WorldExt};
+
+    fn run(source: &str, callback: &str, args: impl FuncArgs) -> bool {
+        let engine = create_engine();
+        let ast = engine.compile(source).expect("Test script didn't parse");
+        run_callback(&engine, &ast, "test", callback, args)
+    }
+
+    fn script_entity() -> ScriptEntity {
+        let mut world = World::new();
+        ScriptEntity(Arc::new(Mutex::new(ScriptEntityData {
+            entity: world.create_entity().build(),
+            pos: Vec2::new(1.0, 2.0),
+            vel: Vec2::zero(),
+            facing: Vec2::new(1.0, 0.0),
+            health: 3,
+            hurt: 0,
+            max_health: 4,
+            team: Team::BadGuys,
+            grounded: true,
+            player: None,
+            memory: Map::new(),
+            events: Vec::new(),
+        })))
+    }
+
+    #[test]
+    fn missing_callbacks_are_skipped() {
+        assert!(run("fn on_hit(e, damage) {}", "update", (1 as INT,)));
+        // Same name, but not the callback's arguments
+        assert!(run(
+            "fn update() { throw \"called\"; }",
+            "update",
+            (1 as INT,)
+        ));
+    }
+
+    #[test]
+    fn missing_functions_inside_callbacks_are_errors() {
+        assert!(!run("fn update(dt) { helper(dt) }", "update", (1 as INT,)));
+        assert!(!run(
+            "fn update(dt) { update_later(dt) }",
+            "update",
+            (1 as INT,)
+        ));
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped() {
+        assert!(!run("fn update(dt) { loop {} }", "update", (1 as INT,)));
+        assert!(!run("fn update(dt) { update(dt) }", "update", (1 as INT,)));
+        let long_string = "fn update(dt) { let s = \"\"; for i in range(0, 2000) { s += \"a\"; } }";
+        assert!(!run(long_string, "update", (1 as INT,)));
+        let long_array = "fn update(dt) { let a = []; for i in range(0, 2000) { a.push(i); } }";
+        assert!(!run(long_array, "update", (1 as INT,)));
+        assert!(run(
+            "fn update(dt) { for i in range(0, 100) {} }",
+            "update",
+            (1 as INT,)
+        ));
+    }
+
+    #[test]
+    fn changes_are_copied_back() {
+        let e = script_entity();
+        let source = "fn update(e, dt) {
+            e.x = 5.0;
+            e.vel_y = -2.0;
+            e.facing = -3.0;
+            e.team = \"good\";
+            e.set(\"seen\", true);
+            e.fire_projectile(1.0, 0.0);
+        }";
+        assert!(run(source, "update", (e.clone(), 0.1 as FLOAT)));
+
+        let mut script = Script::new("test");
+        let mut pos = Position(Vec2::new(1.0, 2.0));
+        let mut vel = Velocity::default();
+        let mut orientation = Orientation::default();
+        let mut health = Health::new(4);
+        health.current = 3;
+        let mut team = Team::BadGuys;
+        let data = e.lock().clone();
+        let (hurt, events) = copy_back(
+            data,
+            &mut script,
+            &mut pos,
+            &mut vel,
+            &mut orientation,
+            &mut health,
+            &mut team,
+        );
+        assert_eq!(pos.0, Vec2::new(5.0, 2.0));
+        assert_eq!(vel.0, Vec2::new(0.0, -2.0));
+        assert_eq!(orientation.vec, Vec2::new(-1.0, 0.0));
+        assert_eq!(team, Team::GoodGuys);
+        assert!(script.memory.contains_key("seen"));
+        assert_eq!(hurt, 0);
+        assert_eq!(health.current, 3);
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn lost_health_is_dealt_as_damage() {
+        let e = script_entity();
+        assert!(run(
+            "fn update(e) { e.health = 0; }",
+            "update",
+            (e.clone(),)
+        ));
+        let mut health = Health::new(4);
+        health.current = 3;
+        let data = e.lock().clone();
+        let (hurt, _) = copy_back(
+            data,
+            &mut Script::new("test"),
+            &mut Position(Vec2::zero()),
+            &mut Velocity::default(),
+            &mut Orientation::default(),
+            &mut health,
+            &mut Team::BadGuys,
+        );
+        assert_eq!(hurt, 3);
+        // Still alive until the hit lands
+        assert_eq!(health.current, 3);
+    }
+
+    #[test]
+    fn healing_makes_up_for_lost_health_first() {
+        let e = script_entity();
+        assert!(run(
+            "fn update(e) { e.health -= 2; e.health += 3; }",
+            "update",
+            (e.clone(),)
+        ));
+        let data = e.lock().clone();
+        assert_eq!(data.hurt, 0);
+        assert_eq!(data.health, 4);
+    }
+}
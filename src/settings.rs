@@ -0,0 +1,61 @@
+use crate::audio::AudioSettings;
+use amethyst::utils::application_root_dir;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::{fs::File, io::Write, path::PathBuf};
+
+pub const SETTINGS_FILE: &str = "settings.ron";
+
+// Player preferences, kept in `config/settings.ron` between runs
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub audio: AudioSettings,
+}
+
+impl Settings {
+    fn path() -> PathBuf {
+        application_root_dir()
+            .unwrap()
+            .join("config")
+            .join(SETTINGS_FILE)
+    }
+
+    pub fn load() -> Settings {
+        let path = Self::path();
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(_) => {
+                warn!("No settings found at {:?}, using defaults", path);
+                return Settings::default();
+            }
+        };
+        match ron::de::from_reader(file) {
+            Ok(settings) => settings,
+            Err(e) => {
+                error!("Failed to parse settings {:?}: {}", path, e);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let path = Self::path();
+        let config = ron::ser::PrettyConfig::default();
+        let serialized = match ron::ser::to_string_pretty(self, config) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("Failed to serialize settings: {:?}", e);
+                return;
+            }
+        };
+        match File::create(&path) {
+            Ok(mut file) => {
+                if let Err(e) = file.write_all(serialized.as_bytes()) {
+                    error!("Failed to write settings {:?}: {}", path, e);
+                }
+            }
+            Err(e) => error!("Failed to create settings {:?}: {}", path, e),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     audio::{initialise_audio, Sounds},
+    auto_tile::{recalculate_auto_tiles_around, AutoTileTarget},
     components::{
         editor::{
             CursorState, CursorWasInThisEntity, EditorCursor, EditorFlag, InsertionGameObject,
@@ -10,7 +11,10 @@ use crate::{
             CameraTarget, CollisionEvent, Health, Invincibility, Player, SerializedObject,
             SerializedObjectType, SpriteRenderData,
         },
-        graphics::{AnimationCounter, PulseAnimation, Scale, SpriteSheetType},
+        graphics::{
+            AnimationCounter, InTileChunk, PulseAnimation, Scale, SpriteSheetId, TileChunkTint,
+            SPRITE_SHEET_SNAP, SPRITE_SHEET_TILES,
+        },
         physics::{
             Collidee, CollisionSideOfBlock, GravityDirection, Grounded, PlatformCollisionPoints,
             PlatformCuboid, Position, Sticky, Velocity,
@@ -33,6 +37,7 @@ use crate::{
         graphics::{CursorColorUpdateSystem, CursorSpriteUpdateSystem, PulseAnimationSystem},
         physics::CollisionDirection,
     },
+    tile_chunks::set_chunk_tile,
     ui::{
         current_actions::CurrentActionsUi,
         file_picker::FilePickerUi,
@@ -92,6 +97,8 @@ pub(crate) struct Editor<'a, 'b> {
     test_text: Option<Entity>,
     dispatcher: Option<Dispatcher<'a, 'b>>,
     prev_camera_target: CameraTarget,
+    // The game's ui, put back when the editor closes
+    prev_ui_stack: Option<UiStack>,
 }
 
 impl Default for Editor<'_, '_> {
@@ -100,6 +107,7 @@ impl Default for Editor<'_, '_> {
             test_text: None,
             dispatcher: None,
             prev_camera_target: CameraTarget::default(),
+            prev_ui_stack: None,
         }
     }
 }
@@ -122,6 +130,7 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Editor<'_, '_> {
         ui_stack
             .stack
             .push(Box::new(EditorFieldUiComponents::new(data.world)));
+        self.prev_ui_stack = data.world.remove::<UiStack>();
         data.world.insert(ui_stack);
 
         // setup dispatcher
@@ -160,6 +169,8 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Editor<'_, '_> {
             to_remove = ui_component.entities_to_remove(to_remove);
         }
         Self::remove_entities(to_remove, data.world);
+        data.world
+            .insert(self.prev_ui_stack.take().unwrap_or_default());
     }
 
     fn handle_event(
@@ -184,6 +195,7 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Editor<'_, '_> {
             );
         }
 
+        let mut auto_tile_target = None;
         if let MyEvents::App(event) = &event {
             data.world.write_resource::<UiStack>().handle_ui_events(
                 data.world,
@@ -199,8 +211,10 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Editor<'_, '_> {
                         &mut serialized_object,
                         false,
                     );
+                    auto_tile_target = AutoTileTarget::from_serialized_object(&serialized_object);
                 }
                 Events::DeleteGameObject(id) => {
+                    auto_tile_target = AutoTileTarget::from_editor_entity(data.world, *id);
                     Self::delete_entity(data.world, *id);
                 }
                 Events::SaveLevel => {
@@ -213,7 +227,7 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Editor<'_, '_> {
                     ]);
                 }
                 Events::ChangeInsertionGameObject(id) => {
-                    let mod_id = id % 2;
+                    let mod_id = id % 5;
                     match mod_id {
                         0 => {
                             data.world
@@ -224,7 +238,39 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Editor<'_, '_> {
                             result.object_type = SerializedObjectType::Player {
                                 is_player: Player(false),
                             };
-                            result.sprite = Some(SpriteRenderData::new(SpriteSheetType::Snap, 0));
+                            result.sprite = Some(SpriteRenderData::new(SPRITE_SHEET_SNAP, 0));
+                            data.world.insert(InsertionGameObject(result));
+                        }
+                        2 => {
+                            let mut result: SerializedObject = SerializedObject::default();
+                            result.object_type = SerializedObjectType::Door {
+                                link: None,
+                                open: false,
+                            };
+                            result.size =
+                                Some(ultraviolet::Vec2::new(TILE_WIDTH / 2.0, TILE_HEIGHT * 2.0));
+                            result.sprite = Some(SpriteRenderData::new(SPRITE_SHEET_TILES, 3));
+                            data.world.insert(InsertionGameObject(result));
+                        }
+                        3 => {
+                            let mut result: SerializedObject = SerializedObject::default();
+                            result.object_type = SerializedObjectType::Switch {
+                                link: 0,
+                                plate: false,
+                            };
+                            result.size =
+                                Some(ultraviolet::Vec2::new(TILE_WIDTH / 2.0, TILE_HEIGHT / 2.0));
+                            result.sprite = Some(SpriteRenderData::new(SPRITE_SHEET_TILES, 0));
+                            data.world.insert(InsertionGameObject(result));
+                        }
+                        4 => {
+                            let mut result: SerializedObject = SerializedObject::default();
+                            result.object_type = SerializedObjectType::GravityZone {
+                                gravity: CollisionDirection::FromBottom,
+                            };
+                            result.size =
+                                Some(ultraviolet::Vec2::new(TILE_WIDTH * 2.0, TILE_HEIGHT * 2.0));
+                            result.sprite = Some(SpriteRenderData::new(SPRITE_SHEET_TILES, 3));
                             data.world.insert(InsertionGameObject(result));
                         }
                         _ => {
@@ -255,6 +301,9 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Editor<'_, '_> {
 
         // Necessary to record changes made to entities by events
         data.world.maintain();
+        if let Some(target) = auto_tile_target {
+            recalculate_auto_tiles_around(data.world, &target);
+        }
 
         // Escape isn't pressed, so we stay in this `State`.
         Trans::None
@@ -269,22 +318,7 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Editor<'_, '_> {
             dispatcher.dispatch(&data.world);
         }
 
-        let mut index = 0;
-        let mut remove_indices = Vec::new();
-        for ui in &mut data.world.write_resource::<UiStack>().stack {
-            ui.update(data.world);
-            if ui.should_destroy() {
-                remove_indices.insert(0, index);
-            }
-            index += 1;
-        }
-        let mut entities = Vec::new();
-        for index in remove_indices {
-            entities =
-                data.world.read_resource::<UiStack>().stack[index].entities_to_remove(entities);
-            data.world.write_resource::<UiStack>().stack.remove(index);
-        }
-        Self::remove_entities(entities, data.world);
+        UiStack::update(data.world);
 
         Trans::None
     }
@@ -351,11 +385,21 @@ impl<'a, 'b> Editor<'a, 'b> {
             "pan_system",
             &["camera_target_system"],
         );
+        dispatcher_builder.add(
+            systems::graphics::CameraRotationSystem,
+            "camera_rotation_system",
+            &["camera_target_system"],
+        );
         dispatcher_builder.add(
             systems::graphics::TransformUpdateSystem,
             "transform_update_system",
             &["pan_system"],
         );
+        dispatcher_builder.add(
+            systems::graphics::TileChunkCullingSystem,
+            "tile_chunk_culling_system",
+            &["pan_system"],
+        );
 
         dispatcher_builder
             .with_pool((*world.read_resource::<ArcThreadPool>()).clone())
@@ -395,6 +439,7 @@ impl<'a, 'b> Editor<'a, 'b> {
                 .insert(entity, Tint(Srgba::new(1.0, 1.0, 1.0, transparency)))
                 .expect("Error inserting Tint to entity in editor mode");
         }
+        world.insert(TileChunkTint(Srgba::new(1.0, 1.0, 1.0, transparency)));
     }
 
     pub fn set_editor_entities_layer_transparency(world: &mut World, transparency: f32) {
@@ -455,6 +500,31 @@ impl<'a, 'b> Editor<'a, 'b> {
         // Get the editor entity
         let editor_entity = world.entities().entity(id);
 
+        // Static tiles are drawn by their chunk, so clear them from it
+        if world.read_storage::<InTileChunk>().contains(editor_entity) {
+            let pos = world
+                .read_storage::<Position>()
+                .get(editor_entity)
+                .unwrap()
+                .0;
+            let size = world
+                .read_storage::<SizeForEditorGrid>()
+                .get(editor_entity)
+                .unwrap()
+                .0;
+            let layer = *world
+                .read_storage::<TileLayer>()
+                .get(editor_entity)
+                .unwrap();
+            let sheet = world
+                .read_storage::<SpriteSheetId>()
+                .get(editor_entity)
+                .unwrap()
+                .0
+                .clone();
+            set_chunk_tile(world, pos, size, layer, &sheet, None);
+        }
+
         // Delete the instance entity using editor entity
         if let Some(instance_id) = world.read_storage::<InstanceEntityId>().get(editor_entity) {
             if let Some(instance_id) = instance_id.0 {
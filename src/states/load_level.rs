@@ -1,6 +1,6 @@
 use crate::{
     audio::initialise_audio,
-    components::{graphics::SpriteSheetType, physics::PlatformCuboid},
+    components::physics::PlatformCuboid,
     level::Level,
     states::pizzatopia::{MyEvents, Pizzatopia},
     ui::{
@@ -1,7 +1,18 @@
 use crate::{
+    abilities::AbilityLibrary,
+    animations::AnimationLibrary,
     audio::initialise_audio,
-    components::{graphics::SpriteSheetType, physics::PlatformCuboid},
+    auto_tile::AutoTileRules,
+    components::{ai::Behaviours, graphics::SPRITE_SHEET_TILES, physics::PlatformCuboid},
+    dialogue::Dialogues,
+    factions::Factions,
+    flags::Flags,
     level::Level,
+    loot::LootLibrary,
+    particles::ParticleEmitters,
+    projectiles::ProjectileLibrary,
+    rng::WorldRng,
+    settings::Settings,
     states::{
         load_level::LoadLevelState,
         pizzatopia::{MyEvents, Pizzatopia},
@@ -44,34 +55,86 @@ use amethyst::{
     winit::Event,
 };
 use bami::Input;
-use log::warn;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
+    fs::File,
     path::{Path, PathBuf},
     time::Duration,
 };
 
+pub const SPRITE_SHEET_MANIFEST: &str = "texture/sprite_sheets.ron";
+
 pub struct AssetsDir(pub PathBuf);
 
 #[derive(Default)]
 pub struct DrawDebugLines(pub bool);
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SpriteSheetManifestEntry {
+    pub id: String,
+    // Path relative to the assets directory, without the `.png`/`.ron` extension
+    pub path: String,
+    // Rules keyed by tile group. Tiles placed with a group get their sprites picked from the
+    // neighbours in the same group in the editor.
+    #[serde(default)]
+    pub auto_tile: BTreeMap<String, AutoTileRules>,
+}
+
+// Lists every sprite sheet the game knows about, so adding art doesn't require touching the code
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SpriteSheetManifest {
+    pub sprite_sheets: Vec<SpriteSheetManifestEntry>,
+}
+
+impl SpriteSheetManifest {
+    pub fn load(assets_dir: &Path) -> SpriteSheetManifest {
+        let path = assets_dir.join(SPRITE_SHEET_MANIFEST);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open sprite sheet manifest {:?}: {}", path, e);
+                return SpriteSheetManifest::default();
+            }
+        };
+        match ron::de::from_reader(file) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                error!("Failed to parse sprite sheet manifest {:?}: {}", path, e);
+                SpriteSheetManifest::default()
+            }
+        }
+    }
+}
+
 pub struct LoadingState {
     /// Tracks loaded assets.
     progress_counter: ProgressCounter,
+    /// Seeds the world RNG at every level start when set.
+    seed: Option<u64>,
 }
 
 impl Default for LoadingState {
     fn default() -> Self {
         LoadingState {
             progress_counter: ProgressCounter::default(),
+            seed: None,
+        }
+    }
+}
+
+impl LoadingState {
+    pub fn with_seed(seed: Option<u64>) -> Self {
+        LoadingState {
+            seed,
+            ..LoadingState::default()
         }
     }
 }
 
 impl<'s> State<GameData<'s, 's>, MyEvents> for LoadingState {
     fn on_start(&mut self, data: StateData<'_, GameData<'s, 's>>) {
-        initialise_audio(data.world);
         let platform_size_prefab_handle =
             data.world.exec(|loader: PrefabLoader<'_, PlatformCuboid>| {
                 loader.load("prefab/tile_size.ron", RonFormat, ())
@@ -84,6 +147,8 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for LoadingState {
 
         data.world
             .insert(AssetsDir(application_root_dir().unwrap().join("assets")));
+        data.world.insert(Settings::load());
+        initialise_audio(data.world);
         let font_handle: Handle<FontAsset> = data.world.read_resource::<Loader>().load(
             "font/LibreBaskerville-Bold.ttf",
             TtfFormat,
@@ -92,26 +157,31 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for LoadingState {
         );
         data.world.insert(font_handle);
         data.world
-            .insert(BTreeMap::<u8, Handle<SpriteSheet>>::new());
-
-        self.add_new_sprite_sheet(data.world, "texture/tiles", SpriteSheetType::Tiles as u8);
-        self.add_new_sprite_sheet(data.world, "texture/walk", SpriteSheetType::Didi as u8);
-        self.add_new_sprite_sheet(
-            data.world,
-            "texture/rolling_hills_bg",
-            SpriteSheetType::RollingHillsBg as u8,
-        );
-        self.add_new_sprite_sheet(
-            data.world,
-            "texture/spritesheet2",
-            SpriteSheetType::Snap as u8,
-        );
-        self.add_new_sprite_sheet(data.world, "texture/ui", SpriteSheetType::Ui as u8);
-        self.add_new_sprite_sheet(
-            data.world,
-            "texture/animation",
-            SpriteSheetType::Animation as u8,
-        );
+            .insert(BTreeMap::<String, Handle<SpriteSheet>>::new());
+
+        let manifest = SpriteSheetManifest::load(&data.world.read_resource::<AssetsDir>().0);
+        for entry in manifest.sprite_sheets.iter() {
+            self.add_new_sprite_sheet(data.world, &entry.path, &entry.id);
+        }
+        data.world.insert(manifest);
+        let animation_library = AnimationLibrary::load(data.world);
+        data.world.insert(animation_library);
+        let particle_emitters = ParticleEmitters::load(data.world);
+        data.world.insert(particle_emitters);
+        let dialogues = Dialogues::load(data.world);
+        data.world.insert(dialogues);
+        let behaviours = Behaviours::load(data.world);
+        data.world.insert(behaviours);
+        let projectiles = ProjectileLibrary::load(data.world);
+        data.world.insert(projectiles);
+        let abilities = AbilityLibrary::load(data.world);
+        data.world.insert(abilities);
+        let factions = Factions::load(data.world);
+        data.world.insert(factions);
+        let loot = LootLibrary::load(data.world);
+        data.world.insert(loot);
+        data.world.insert(Flags::default());
+        data.world.insert(WorldRng::new(self.seed));
 
         data.world.insert(FilePickerFilename::new(
             "level0.ron".to_string(),
@@ -178,11 +248,28 @@ pub fn get_image_texure_config() -> ImageTextureConfig {
 }
 
 impl LoadingState {
-    fn add_new_sprite_sheet(&mut self, world: &mut World, name: &str, sheet_number: u8) {
+    fn add_new_sprite_sheet(&mut self, world: &mut World, name: &str, sheet_id: &str) {
         let name = String::from(name);
         let sprites = load_spritesheet(name.clone(), world, &mut self.progress_counter);
         world
-            .write_resource::<BTreeMap<u8, Handle<SpriteSheet>>>()
-            .insert(sheet_number, sprites);
+            .write_resource::<BTreeMap<String, Handle<SpriteSheet>>>()
+            .insert(String::from(sheet_id), sprites);
+    }
+}
+
+/// Look up a loaded sprite sheet by its manifest ID.
+/// Falls back to the tiles sheet (and logs) when the manifest has no such sheet or it failed to
+/// load, so the entity still shows up. Only `None` when the tiles sheet is missing too.
+pub fn get_sprite_sheet(world: &World, sheet_id: &str) -> Option<Handle<SpriteSheet>> {
+    let sprite_sheets = world.read_resource::<BTreeMap<String, Handle<SpriteSheet>>>();
+    match sprite_sheets.get(sheet_id) {
+        Some(sheet) => Some(sheet.clone()),
+        None => {
+            error!(
+                "Unknown sprite sheet {:?}, using {:?} instead",
+                sheet_id, SPRITE_SHEET_TILES
+            );
+            sprite_sheets.get(SPRITE_SHEET_TILES).cloned()
+        }
     }
 }
@@ -1,34 +1,49 @@
-use crate::components::game::{Block, Drops, PicksThingsUp, Pickup, Talks};
+use crate::components::game::{
+    Block, Inventory, OnDeath, PicksThingsUp, Pickup, Resistances, Talks,
+};
 use crate::components::graphics::Pan;
 use crate::{
+    abilities::{Abilities, AbilitySystem},
     animations::AnimationId,
     audio::{initialise_audio, Sounds},
     bundles::{GameLogicBundle, GraphicsBundle},
     components::{
+        ai::BehaviourAi,
         editor::{EditorFlag, InstanceEntityId, SizeForEditorGrid, TileLayer},
         entity_builder::entity_builder,
         game::{
-            CameraTarget, CollisionEvent, Health, Invincibility, Player, Resettable,
-            SerializedObject, SerializedObjectType, Tile,
+            Activatable, CameraTarget, CollisionEvent, Dormant, Health, HitReaction, Invincibility,
+            Knockback, Player, Resettable, SerializedObject, SerializedObjectType, Tile,
+        },
+        graphics::{
+            AnimationCounter, AnimationStateMachine, CameraLimit, CameraRotation, CameraShake,
+            CharacterAnimationsId, InTileChunk, SpriteSheetId, TileChunk, TileGroup,
         },
-        graphics::{AnimationCounter, CameraLimit, SpriteSheetType},
         physics::{
-            Collidee, CollisionSideOfBlock, GravityDirection, Grounded, PlatformCollisionPoints,
-            PlatformCuboid, Position, Sticky, Velocity,
+            Collidee, CollisionSideOfBlock, GravityDirection, GravityZone, Grounded,
+            PlatformCollisionPoints, PlatformCuboid, Position, Sticky, Velocity,
         },
     },
     events::Events,
     level::Level,
+    loot::Loot,
+    mechanisms::{swap_solids, Door, DoorSystemDesc, Switch, SwitchSystem},
+    particles::{Particle, ParticleEmitter, ParticleEmitterSystem, ParticleSystem},
+    projectiles::{ProjectileStats, ProjectileSystem, Shooter, ShooterSystem},
+    scripting::{Script, ScriptSystemDesc},
     states::{editor::Editor, loading::DrawDebugLines},
     systems,
     systems::{
         console::ConsoleInputSystem,
         game::AnimationCounterSystem,
         graphics::CollisionDebugLinesSystem,
-        physics::{CollisionDirection, DuckTransferSystem},
+        physics::{CollisionDirection, DuckTransferSystem, GravityZoneSystemDesc},
     },
+    triggers::{Spawned, Trigger, TriggerSystem},
     ui::{
+        dialogue::DialogueUi,
         file_picker::{FilePickerButton, FilePickerUi},
+        hud::{initialize_hud, Hud, HudSystem},
         tile_characteristics::EditorButton,
         UiStack,
     },
@@ -45,7 +60,7 @@ use amethyst::{
         frame_limiter::FrameRateLimitStrategy,
         shrev::{EventChannel, ReaderId},
         transform::Transform,
-        ArcThreadPool, EventReader, Time,
+        ArcThreadPool, EventReader, SystemDesc, Time,
     },
     derive::EventReader,
     ecs::prelude::{Component, DenseVecStorage, Dispatcher, DispatcherBuilder, Entity, Join},
@@ -114,6 +129,7 @@ impl Pizzatopia<'_, '_> {
         initialise_camera(world);
         Level::load_level(world);
         entity_builder::initialize_background(world);
+        initialize_hud(world);
     }
 }
 
@@ -124,12 +140,18 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Pizzatopia<'_, '_> {
         data.world.register::<Resettable>();
         data.world.register::<EditorFlag>();
         data.world.register::<CameraTarget>();
-        data.world.register::<SpriteSheetType>();
+        data.world.register::<SpriteSheetId>();
+        data.world.register::<TileGroup>();
+        data.world.register::<CharacterAnimationsId>();
+        data.world.register::<AnimationStateMachine>();
         data.world.register::<Tile>();
         data.world.register::<Block>();
         data.world.register::<Pickup>();
         data.world.register::<PicksThingsUp>();
-        data.world.register::<Drops>();
+        data.world.register::<Inventory>();
+        data.world.register::<OnDeath>();
+        data.world.register::<Resistances>();
+        data.world.register::<Loot>();
         // Created in Pizzatopia and system in Editor
         data.world.register::<SizeForEditorGrid>();
         // Created in Pizzatopia and system in Editor
@@ -138,6 +160,26 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Pizzatopia<'_, '_> {
         data.world.register::<FilePickerButton>();
         data.world.register::<TileLayer>();
         data.world.register::<Talks>();
+        data.world.register::<TileChunk>();
+        data.world.register::<InTileChunk>();
+        data.world.register::<Activatable>();
+        data.world.register::<Dormant>();
+        data.world.register::<Knockback>();
+        data.world.register::<HitReaction>();
+        data.world.register::<CameraShake>();
+        data.world.register::<CameraRotation>();
+        data.world.register::<ParticleEmitter>();
+        data.world.register::<Particle>();
+        data.world.register::<Trigger>();
+        data.world.register::<Spawned>();
+        data.world.register::<Script>();
+        data.world.register::<BehaviourAi>();
+        data.world.register::<ProjectileStats>();
+        data.world.register::<Shooter>();
+        data.world.register::<Abilities>();
+        data.world.register::<Door>();
+        data.world.register::<Switch>();
+        data.world.register::<GravityZone>();
 
         // setup dispatcher
         let mut dispatcher = Pizzatopia::create_pizzatopia_dispatcher(data.world);
@@ -154,9 +196,19 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Pizzatopia<'_, '_> {
 
     fn on_stop(&mut self, data: StateData<'_, GameData<'s, 's>>) {
         data.world.delete_all();
+        data.world.insert(UiStack::default());
+    }
+
+    fn on_pause(&mut self, data: StateData<'_, GameData<'s, 's>>) {
+        data.world
+            .read_resource::<Hud>()
+            .set_hidden(data.world, true);
     }
 
     fn on_resume(&mut self, data: StateData<'_, GameData<'s, 's>>) {
+        data.world
+            .read_resource::<Hud>()
+            .set_hidden(data.world, false);
         Level::calculate_camera_limits(data.world);
         Level::recalculate_collision_tree(data.world);
     }
@@ -187,9 +239,10 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Pizzatopia<'_, '_> {
                 Events::Reset => {
                     println!("Resetting map...");
                     Level::reinitialize_level(data.world);
+                    data.world.write_resource::<Hud>().elapsed = 0.0;
                 }
-                Events::FireProjectile(pos, vel, team) => {
-                    entity_builder::initialize_projectile(data.world, pos, vel, team);
+                Events::FireProjectile(kind, pos, vel, team) => {
+                    entity_builder::initialize_projectile(data.world, kind, pos, vel, team);
                 }
                 Events::CreateDamageBox(parent, pos, size, team) => {
                     entity_builder::initialize_damage_box(
@@ -200,6 +253,50 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Pizzatopia<'_, '_> {
                         team,
                     );
                 }
+                Events::SpawnGameObject(serialized_object) => {
+                    if let Some(id) = entity_builder::initialize_serialized_object(
+                        data.world,
+                        serialized_object,
+                        true,
+                    ) {
+                        let entity = data.world.entities().entity(id);
+                        data.world
+                            .write_storage::<Spawned>()
+                            .insert(entity, Spawned)
+                            .expect("Failed to mark spawned object");
+                        swap_solids(data.world, &[], &[entity]);
+                    }
+                }
+                Events::SpawnPickup(kind, pos) => {
+                    let id = entity_builder::initialize_pickup(
+                        data.world,
+                        pos,
+                        &Vec2::zero(),
+                        kind.clone(),
+                    );
+                    let entity = data.world.entities().entity(id);
+                    data.world
+                        .write_storage::<Spawned>()
+                        .insert(entity, Spawned)
+                        .expect("Failed to mark spawned pickup");
+                }
+                Events::ShowDialogue(name) => {
+                    DialogueUi::open_named(data.world, name);
+                }
+                Events::Warp(pos) => {
+                    for (player, position, velocity) in (
+                        &data.world.read_storage::<Player>(),
+                        &mut data.world.write_storage::<Position>(),
+                        &mut data.world.write_storage::<Velocity>(),
+                    )
+                        .join()
+                    {
+                        if player.0 {
+                            position.0 = *pos;
+                            velocity.0 = Vec2::zero();
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -216,6 +313,7 @@ impl<'s> State<GameData<'s, 's>, MyEvents> for Pizzatopia<'_, '_> {
         if let Some(dispatcher) = self.dispatcher.as_mut() {
             dispatcher.dispatch(&mut data.world);
         }
+        UiStack::update(data.world);
 
         if self.fps_display.is_none() {
             data.world.exec(|finder: UiFinder<'_>| {
@@ -256,6 +354,8 @@ fn initialise_camera(world: &mut World) {
         .with(CameraTarget::Player)
         .with(CameraLimit::default())
         .with(pan)
+        .with(CameraShake::default())
+        .with(CameraRotation::default())
         .build();
 }
 
@@ -301,6 +401,12 @@ impl<'a, 'b> Pizzatopia<'a, 'b> {
             "player_input_system",
             &["apply_gravity_system"],
         );
+        dispatcher_builder.add(AbilitySystem, "ability_system", &["player_input_system"]);
+        dispatcher_builder.add(
+            systems::game::HitStopSystem,
+            "hit_stop_system",
+            &["player_input_system"],
+        );
         dispatcher_builder.add(
             systems::ai::BasicWalkAiSystem,
             "basic_walk_ai_system",
@@ -316,11 +422,35 @@ impl<'a, 'b> Pizzatopia<'a, 'b> {
             "basic_attack_ai_system",
             &["player_input_system"],
         );
+        dispatcher_builder.add(
+            ScriptSystemDesc::default().build(world),
+            "script_system",
+            &["player_input_system"],
+        );
+        dispatcher_builder.add(
+            systems::ai::BehaviourSystem,
+            "behaviour_system",
+            &["player_input_system"],
+        );
+        dispatcher_builder.add(
+            ShooterSystem,
+            "shooter_system",
+            &["behaviour_system", "basic_shoot_ai_system"],
+        );
+        dispatcher_builder.add(
+            ProjectileSystem,
+            "projectile_system",
+            &["player_input_system"],
+        );
         dispatcher_builder.add(
             systems::physics::PlatformCollisionSystem,
             "platform_collision_system",
             &[
                 "basic_walk_ai_system",
+                "ability_system",
+                "script_system",
+                "behaviour_system",
+                "projectile_system",
                 "apply_gravity_system",
                 "actor_collision_system",
             ],
@@ -351,12 +481,32 @@ impl<'a, 'b> Pizzatopia<'a, 'b> {
             &["child_position_system"],
         );
 
+        dispatcher_builder.add(TriggerSystem, "trigger_system", &["apply_sticky_system"]);
+        dispatcher_builder.add(SwitchSystem, "switch_system", &["apply_sticky_system"]);
+        dispatcher_builder.add(
+            GravityZoneSystemDesc::default().build(world),
+            "gravity_zone_system",
+            &["apply_sticky_system"],
+        );
+        dispatcher_builder.add(
+            DoorSystemDesc::default().build(world),
+            "door_system",
+            &["trigger_system", "switch_system"],
+        );
+
         dispatcher_builder.add(
             systems::game::TimedExistenceSystem,
             "timed_existence_system",
             &[],
         );
 
+        dispatcher_builder.add(ParticleEmitterSystem, "particle_emitter_system", &[]);
+        dispatcher_builder.add(
+            ParticleSystem,
+            "particle_system",
+            &["particle_emitter_system"],
+        );
+
         dispatcher_builder.add(
             AnimationCounterSystem,
             "animation_counter_system",
@@ -377,6 +527,11 @@ impl<'a, 'b> Pizzatopia<'a, 'b> {
             "lerper_system",
             &["camera_target_system"],
         );
+        dispatcher_builder.add(
+            systems::graphics::CameraRotationSystem,
+            "camera_rotation_system",
+            &["camera_target_system"],
+        );
         dispatcher_builder.add(
             systems::graphics::CameraEdgeClampSystem,
             "camera_edge_clamp_system",
@@ -388,6 +543,17 @@ impl<'a, 'b> Pizzatopia<'a, 'b> {
             &["camera_edge_clamp_system"],
         );
 
+        dispatcher_builder.add(
+            systems::game::ActivationSystem,
+            "activation_system",
+            &["camera_edge_clamp_system"],
+        );
+        dispatcher_builder.add(
+            systems::graphics::TileChunkCullingSystem,
+            "tile_chunk_culling_system",
+            &["camera_edge_clamp_system"],
+        );
+
         dispatcher_builder.add(
             systems::graphics::SpriteUpdateSystem,
             "sprite_update_system",
@@ -398,19 +564,24 @@ impl<'a, 'b> Pizzatopia<'a, 'b> {
             "animated_tile_system",
             &["sprite_update_system"],
         );
+        dispatcher_builder.add(
+            systems::graphics::AnimationStateSystem,
+            "animation_state_system",
+            &["sprite_update_system"],
+        );
 
         AnimationBundle::<AnimationId, Transform>::new(
             "animation_control_system",
             "sampler_interpolation_system",
         )
-        .with_dep(&["animated_tile_system"])
+        .with_dep(&["animated_tile_system", "animation_state_system"])
         .build(world, &mut dispatcher_builder)
         .expect("Failed to register animation bundle in pizzatopia");
         AnimationBundle::<AnimationId, SpriteRender>::new(
             "sprite_animation_control_system",
             "sprite_sampler_interpolation_system",
         )
-        .with_dep(&["animated_tile_system"])
+        .with_dep(&["animated_tile_system", "animation_state_system"])
         .build(world, &mut dispatcher_builder)
         .expect("Failed to register sprite animation bundle in pizzatopia");
         GameLogicBundle::default()
@@ -419,7 +590,7 @@ impl<'a, 'b> Pizzatopia<'a, 'b> {
         dispatcher_builder.add(
             systems::graphics::TransformUpdateSystem,
             "transform_update_system",
-            &["sprite_sampler_interpolation_system"],
+            &["sprite_sampler_interpolation_system", "particle_system"],
         );
         dispatcher_builder.add(
             systems::graphics::UiTransformUpdateSystem,
@@ -431,6 +602,17 @@ impl<'a, 'b> Pizzatopia<'a, 'b> {
             "dead_draw_update_system",
             &["transform_update_system"],
         );
+        dispatcher_builder.add(HudSystem, "hud_system", &["player_events_system"]);
+        dispatcher_builder.add(
+            systems::graphics::CameraShakeSystem,
+            "camera_shake_system",
+            &["transform_update_system"],
+        );
+        dispatcher_builder.add(
+            systems::graphics::HitFlashSystem,
+            "hit_flash_system",
+            &["enemy_collision_system"],
+        );
 
         dispatcher_builder
             .with_pool((*world.read_resource::<ArcThreadPool>()).clone())
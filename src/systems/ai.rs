@@ -1,12 +1,22 @@
 use crate::{
     components::{
-        game::{CameraTarget, CollisionEvent, Health, Invincibility, Player, Team},
+        ai::{BehaviourAi, BehaviourNode, Behaviours},
+        game::{CameraTarget, CollisionEvent, Dormant, Health, Invincibility, Player, Team},
+        graphics::AnimationStateMachine,
         graphics::{AnimationCounter, CameraLimit},
-        physics::{Collidee, GravityDirection, PlatformCuboid, Position, Velocity},
+        physics::{
+            Collidee, GravityDirection, PlatformCollisionPoints, PlatformCuboid, Position,
+            RTreeEntity, Velocity,
+        },
     },
     events::{Events, PlayerEvent},
+    factions::Factions,
+    navigation::{NavGraph, JUMP_SPEED},
+    projectiles::Shooter,
     states::pizzatopia::{TILE_HEIGHT, TILE_WIDTH},
-    systems::physics::{gravitationally_de_adapted_velocity, CollisionDirection},
+    systems::physics::{
+        gravitationally_adapted_velocity, gravitationally_de_adapted_velocity, CollisionDirection,
+    },
 };
 use amethyst::{
     core::{
@@ -17,7 +27,7 @@ use amethyst::{
     },
     derive::SystemDesc,
     ecs::{
-        Entities, Join, LazyUpdate, Read, ReadStorage, System, SystemData, World, Write,
+        Entities, Entity, Join, LazyUpdate, Read, ReadStorage, System, SystemData, World, Write,
         WriteStorage,
     },
     renderer::{Camera, ImageFormat, SpriteRender, SpriteSheet, SpriteSheetFormat, Texture},
@@ -25,18 +35,15 @@ use amethyst::{
 
 use crate::components::ai::BasicAttackAi;
 use crate::components::physics::{Grounded, MoveIntent, Orientation};
-use crate::{
-    audio::{play_damage_sound, Sounds},
-    components::{
-        ai::{BasicShootAi, BasicWalkAi},
-        editor::{EditorCursor, EditorFlag},
-    },
+use crate::components::{
+    ai::{BasicShootAi, BasicWalkAi},
+    editor::{EditorCursor, EditorFlag},
 };
+use rstar::{RTree, AABB};
 use std::ops::Mul;
 use ultraviolet::{Lerp, Vec2};
 
 const WALK_SPEED: f32 = 4.0;
-const PROJECTILE_SPEED: f32 = 12.0;
 
 #[derive(SystemDesc)]
 pub struct BasicWalkAiSystem;
@@ -49,24 +56,61 @@ impl<'s> System<'s> for BasicWalkAiSystem {
         WriteStorage<'s, BasicWalkAi>,
         ReadStorage<'s, Collidee>,
         ReadStorage<'s, Grounded>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, PlatformCollisionPoints>,
+        ReadStorage<'s, GravityDirection>,
+        ReadStorage<'s, Dormant>,
+        Read<'s, RTree<RTreeEntity>>,
         Read<'s, Time>,
     );
 
     fn run(
         &mut self,
-        (mut velocities, mut orientations, mut move_intents, mut ai, collidees, groundeds, time): Self::SystemData,
+        (
+            mut velocities,
+            mut orientations,
+            mut move_intents,
+            mut ai,
+            collidees,
+            groundeds,
+            positions,
+            collision_points,
+            gravities,
+            dormants,
+            rtree,
+            time,
+        ): Self::SystemData,
     ) {
-        for (velocity, orientation, intent, ai, collidee, grounded) in (
+        for (velocity, orientation, intent, ai, collidee, grounded, pos, points, gravity, _) in (
             &mut velocities,
             &mut orientations,
             &mut move_intents,
             &mut ai,
             &collidees,
             (&groundeds).maybe(),
+            &positions,
+            &collision_points,
+            (&gravities).maybe(),
+            !&dormants,
         )
             .join()
         {
-            if collidee.horizontal.is_some() {
+            let grounded = grounded.map_or(false, |grounded| grounded.0);
+            let gravity = gravity.cloned().unwrap_or_default();
+            let direction = ai.orientation.vec.x;
+            let step = ai.jump_steps
+                && grounded
+                && step_ahead(&rtree, pos.0, points.half_size, &gravity, direction);
+            if step {
+                let mut grav_vel = gravitationally_de_adapted_velocity(&velocity.0, &gravity);
+                grav_vel.y = JUMP_SPEED;
+                velocity.0 = gravitationally_adapted_velocity(&grav_vel, &gravity);
+            } else if hit_wall(collidee, &gravity) {
+                ai.orientation.vec.x *= -1.0;
+            } else if ai.turn_at_ledges
+                && grounded
+                && !ground_ahead(&rtree, pos.0, points.half_size, &gravity, direction)
+            {
                 ai.orientation.vec.x *= -1.0;
             }
             if collidee.vertical.is_some() {
@@ -76,14 +120,13 @@ impl<'s> System<'s> for BasicWalkAiSystem {
             orientation.vec = ai.orientation.vec;
             intent.vec = ai.orientation.vec;
 
-            if let Some(grounded) = grounded {
-                if grounded.0 {
-                    let target = ai.orientation.vec.mul(WALK_SPEED);
-                    let result = velocity
-                        .0
-                        .lerp(Vec2::new(target.x, 0.0), time.delta_seconds() * 4.0);
-                    velocity.0.x = result.x;
-                }
+            // Walk along the ground, whichever way gravity pulls
+            if grounded {
+                let target = ai.orientation.vec.mul(WALK_SPEED);
+                let mut grav_vel = gravitationally_de_adapted_velocity(&velocity.0, &gravity);
+                let result = grav_vel.lerp(Vec2::new(target.x, 0.0), time.delta_seconds() * 4.0);
+                grav_vel.x = result.x;
+                velocity.0 = gravitationally_adapted_velocity(&grav_vel, &gravity);
             }
         }
     }
@@ -94,62 +137,588 @@ pub struct BasicShootAiSystem;
 
 impl<'s> System<'s> for BasicShootAiSystem {
     type SystemData = (
-        WriteStorage<'s, BasicShootAi>,
-        ReadStorage<'s, Position>,
-        ReadStorage<'s, Orientation>,
+        ReadStorage<'s, BasicShootAi>,
+        WriteStorage<'s, Shooter>,
+        ReadStorage<'s, Dormant>,
+    );
+
+    fn run(&mut self, (shoot_ai, mut shooters, dormants): Self::SystemData) {
+        // The shooter does the firing, so its cooldown and pattern apply
+        for (_, shooter, _) in (&shoot_ai, &mut shooters, !&dormants).join() {
+            shooter.pull_trigger();
+        }
+    }
+}
+
+#[derive(SystemDesc)]
+pub struct BasicAttackAiSystem;
+
+impl<'s> System<'s> for BasicAttackAiSystem {
+    type SystemData = (
+        WriteStorage<'s, BasicAttackAi>,
         ReadStorage<'s, Team>,
+        ReadStorage<'s, Dormant>,
         Write<'s, EventChannel<Events>>,
         Read<'s, Time>,
+        Entities<'s>,
     );
 
     fn run(
         &mut self,
-        (mut shoot_ai, positions, orientations, teams, mut events_channel, time): Self::SystemData,
+        (mut shoot_ai, teams, dormants, mut events_channel, time, entities): Self::SystemData,
     ) {
-        for (shoot, pos, orientation, team) in
-            (&mut shoot_ai, &positions, &orientations, &teams).join()
-        {
+        for (shoot, team, entity, _) in (&mut shoot_ai, &teams, &entities, !&dormants).join() {
             shoot.counter += time.delta_seconds();
 
-            let velocity = orientation.vec.mul(PROJECTILE_SPEED);
             if shoot.counter > 2.0 {
                 shoot.counter = 0.0;
 
-                let mut pos = pos.0;
-                pos.y += match rand::random() {
-                    true => TILE_HEIGHT / 4.0,
-                    false => -TILE_HEIGHT / 4.,
-                };
-                events_channel.single_write(Events::FireProjectile(pos, velocity, team.clone()));
+                let parent = Some(entity);
+                let pos = Vec2::new(TILE_WIDTH / 1.5, TILE_HEIGHT / 4.);
+                let size = Vec2::new(TILE_WIDTH, TILE_HEIGHT / 4.);
+                events_channel.single_write(Events::CreateDamageBox(parent, pos, size, *team));
+            }
+        }
+    }
+}
+
+// Distance in front of an actor's feet that's checked for ground or walls
+const PROBE_DISTANCE: f32 = TILE_WIDTH / 4.0;
+
+// Whether any solid tile covers the point
+pub(crate) fn solid_at(rtree: &RTree<RTreeEntity>, point: Vec2) -> bool {
+    rtree
+        .locate_in_envelope_intersecting(&AABB::from_point([point.x, point.y]))
+        .next()
+        .is_some()
+}
+
+// Whether a straight line between the two points misses every solid tile
+pub(crate) fn line_of_sight(rtree: &RTree<RTreeEntity>, from: Vec2, to: Vec2) -> bool {
+    let envelope = AABB::from_corners([from.x, from.y], [to.x, to.y]);
+    !rtree
+        .locate_in_envelope_intersecting(&envelope)
+        .any(|tile| segment_hits_box(from, to, tile.pos, tile.half_size))
+}
+
+// Slab test of the segment against the box
+fn segment_hits_box(from: Vec2, to: Vec2, center: Vec2, half_size: Vec2) -> bool {
+    let delta = to - from;
+    let (mut t_min, mut t_max) = (0.0f32, 1.0f32);
+    for &(start, delta, min, max) in [
+        (
+            from.x,
+            delta.x,
+            center.x - half_size.x,
+            center.x + half_size.x,
+        ),
+        (
+            from.y,
+            delta.y,
+            center.y - half_size.y,
+            center.y + half_size.y,
+        ),
+    ]
+    .iter()
+    {
+        if delta.abs() < std::f32::EPSILON {
+            if start < min || start > max {
+                return false;
+            }
+            continue;
+        }
+        let (t1, t2) = ((min - start) / delta, (max - start) / delta);
+        t_min = t_min.max(t1.min(t2));
+        t_max = t_max.min(t1.max(t2));
+        if t_min > t_max {
+            return false;
+        }
+    }
+    true
+}
+
+// Half size of the actor along and across its gravity, collision boxes don't turn with it
+fn local_half_size(half_size: Vec2, gravity: &GravityDirection) -> Vec2 {
+    match gravity.0.is_horizontal() {
+        true => Vec2::new(half_size.y, half_size.x),
+        false => half_size,
+    }
+}
+
+// Whether the actor walks into a wall, which is a vertical collision when gravity is sideways
+fn hit_wall(collidee: &Collidee, gravity: &GravityDirection) -> bool {
+    match gravity.0.is_horizontal() {
+        true => collidee.vertical.is_some(),
+        false => collidee.horizontal.is_some(),
+    }
+}
+
+// Whether solid ground is at `offset` from the actor, with "down" being where gravity pulls
+fn solid_at_local(
+    rtree: &RTree<RTreeEntity>,
+    pos: Vec2,
+    gravity: &GravityDirection,
+    offset: Vec2,
+) -> bool {
+    solid_at(
+        rtree,
+        pos + gravitationally_adapted_velocity(&offset, gravity),
+    )
+}
+
+// Whether there's ground just in front of the feet of an actor facing `direction`
+pub(crate) fn ground_ahead(
+    rtree: &RTree<RTreeEntity>,
+    pos: Vec2,
+    half_size: Vec2,
+    gravity: &GravityDirection,
+    direction: f32,
+) -> bool {
+    let half_size = local_half_size(half_size, gravity);
+    let probe = Vec2::new(
+        direction.signum() * (half_size.x + PROBE_DISTANCE),
+        -half_size.y - PROBE_DISTANCE,
+    );
+    solid_at_local(rtree, pos, gravity, probe)
+}
+
+// Whether there's something solid just in front of an actor facing `direction`
+pub(crate) fn wall_ahead(
+    rtree: &RTree<RTreeEntity>,
+    pos: Vec2,
+    half_size: Vec2,
+    gravity: &GravityDirection,
+    direction: f32,
+) -> bool {
+    let half_size = local_half_size(half_size, gravity);
+    let probe = Vec2::new(direction.signum() * (half_size.x + PROBE_DISTANCE), 0.0);
+    solid_at_local(rtree, pos, gravity, probe)
+}
+
+// Whether the wall just in front of an actor facing `direction` is low enough to jump onto
+pub(crate) fn step_ahead(
+    rtree: &RTree<RTreeEntity>,
+    pos: Vec2,
+    half_size: Vec2,
+    gravity: &GravityDirection,
+    direction: f32,
+) -> bool {
+    let half_size = local_half_size(half_size, gravity);
+    let x = direction.signum() * (half_size.x + PROBE_DISTANCE);
+    let feet = -half_size.y;
+    let solid = |y: f32| solid_at_local(rtree, pos, gravity, Vec2::new(x, y));
+    solid(feet + PROBE_DISTANCE)
+        && !solid(feet + TILE_HEIGHT + PROBE_DISTANCE)
+        && !solid(feet + TILE_HEIGHT + half_size.y * 2.0)
+}
+
+// What a behaviour tree can see of its actor and the world this frame
+struct BehaviourContext<'a> {
+    entity: Entity,
+    pos: Vec2,
+    half_size: Vec2,
+    gravity: GravityDirection,
+    team: Team,
+    grounded: bool,
+    touching_wall: bool,
+    target: Option<Vec2>,
+    // None without a shooter
+    shooter_ready: Option<bool>,
+    rtree: &'a RTree<RTreeEntity>,
+    nav: &'a NavGraph,
+}
+
+// What the tree decided to do
+#[derive(Default)]
+struct BehaviourOutput {
+    // Walking speed, signed by direction
+    walk: f32,
+    facing: f32,
+    jump: Option<f32>,
+    attacking: bool,
+    fire: bool,
+    events: Vec<Events>,
+}
+
+fn run_behaviour(
+    node: &BehaviourNode,
+    context: &BehaviourContext,
+    ai: &mut BehaviourAi,
+    output: &mut BehaviourOutput,
+) -> bool {
+    // Ahead and behind are along the ground the actor stands on
+    let towards_target = context.target.map(|target| {
+        let local = gravitationally_de_adapted_velocity(&(target - context.pos), &context.gravity);
+        match local.x < 0.0 {
+            true => -1.0,
+            false => 1.0,
+        }
+    });
+    match node {
+        BehaviourNode::Sequence(children) => children
+            .iter()
+            .all(|child| run_behaviour(child, context, ai, output)),
+        BehaviourNode::Selector(children) => children
+            .iter()
+            .any(|child| run_behaviour(child, context, ai, output)),
+        BehaviourNode::Invert(child) => !run_behaviour(child, context, ai, output),
+        BehaviourNode::Succeed(child) => {
+            run_behaviour(child, context, ai, output);
+            true
+        }
+
+        BehaviourNode::TargetWithin(distance) => context
+            .target
+            .map_or(false, |target| (target - context.pos).mag() <= *distance),
+        BehaviourNode::TargetInSight => context.target.map_or(false, |target| {
+            line_of_sight(context.rtree, context.pos, target)
+        }),
+        BehaviourNode::TargetAhead => towards_target == Some(output.facing),
+        BehaviourNode::LedgeAhead => {
+            context.grounded
+                && !ground_ahead(
+                    context.rtree,
+                    context.pos,
+                    context.half_size,
+                    &context.gravity,
+                    output.facing,
+                )
+        }
+        BehaviourNode::WallAhead => {
+            context.touching_wall
+                || wall_ahead(
+                    context.rtree,
+                    context.pos,
+                    context.half_size,
+                    &context.gravity,
+                    output.facing,
+                )
+        }
+        BehaviourNode::StepAhead => step_ahead(
+            context.rtree,
+            context.pos,
+            context.half_size,
+            &context.gravity,
+            output.facing,
+        ),
+        BehaviourNode::Grounded => context.grounded,
+
+        BehaviourNode::Patrol {
+            speed,
+            turn_at_ledges,
+        } => {
+            let wall = context.touching_wall
+                || wall_ahead(
+                    context.rtree,
+                    context.pos,
+                    context.half_size,
+                    &context.gravity,
+                    output.facing,
+                );
+            let ledge = *turn_at_ledges
+                && context.grounded
+                && !ground_ahead(
+                    context.rtree,
+                    context.pos,
+                    context.half_size,
+                    &context.gravity,
+                    output.facing,
+                );
+            if wall || ledge {
+                output.facing *= -1.0;
+            }
+            output.walk = output.facing * speed;
+            true
+        }
+        BehaviourNode::Chase { speed } => match towards_target {
+            Some(direction) => {
+                output.facing = direction;
+                output.walk = direction * speed;
+                true
+            }
+            None => false,
+        },
+        BehaviourNode::PathToTarget { speed } => {
+            let step = match context.target {
+                Some(target) => context.nav.next_step(context.pos, target),
+                None => None,
+            };
+            match step {
+                Some(step) => {
+                    let arrived = (step.x - context.pos.x).abs() <= PROBE_DISTANCE;
+                    let direction = match arrived {
+                        true => step.direction,
+                        false => (step.x - context.pos.x).signum(),
+                    };
+                    if direction != 0.0 {
+                        output.facing = direction;
+                    }
+                    output.walk = direction * speed;
+                    let step_up = context.grounded
+                        && step_ahead(
+                            context.rtree,
+                            context.pos,
+                            context.half_size,
+                            &context.gravity,
+                            output.facing,
+                        );
+                    if (arrived && step.jump && context.grounded) || step_up {
+                        output.jump = Some(JUMP_SPEED);
+                    }
+                    true
+                }
+                None => false,
+            }
+        }
+        BehaviourNode::Flee { speed } => match towards_target {
+            Some(direction) => {
+                output.facing = -direction;
+                output.walk = -direction * speed;
+                true
             }
+            None => false,
+        },
+        BehaviourNode::Idle => {
+            output.walk = 0.0;
+            true
+        }
+        BehaviourNode::TurnAround => {
+            output.facing *= -1.0;
+            true
         }
+        BehaviourNode::FaceTarget => match towards_target {
+            Some(direction) => {
+                output.facing = direction;
+                true
+            }
+            None => false,
+        },
+        BehaviourNode::Fire => match context.shooter_ready {
+            Some(true) => {
+                output.fire = true;
+                true
+            }
+            _ => false,
+        },
+        BehaviourNode::Melee { cooldown } => match ai.melee_cooldown <= 0.0 {
+            true => {
+                ai.melee_cooldown = *cooldown;
+                // Flipped to the way we're facing along with the actor
+                let pos = Vec2::new(TILE_WIDTH / 1.5, TILE_HEIGHT / 4.);
+                let size = Vec2::new(TILE_WIDTH, TILE_HEIGHT / 4.);
+                output.events.push(Events::CreateDamageBox(
+                    Some(context.entity),
+                    pos,
+                    size,
+                    context.team,
+                ));
+                output.attacking = true;
+                true
+            }
+            false => false,
+        },
+        BehaviourNode::Jump(strength) => match context.grounded {
+            true => {
+                output.jump = Some(*strength);
+                true
+            }
+            false => false,
+        },
     }
 }
 
 #[derive(SystemDesc)]
-pub struct BasicAttackAiSystem;
+pub struct BehaviourSystem;
 
-impl<'s> System<'s> for BasicAttackAiSystem {
+impl<'s> System<'s> for BehaviourSystem {
     type SystemData = (
-        WriteStorage<'s, BasicAttackAi>,
+        WriteStorage<'s, BehaviourAi>,
+        WriteStorage<'s, Velocity>,
+        WriteStorage<'s, Orientation>,
+        WriteStorage<'s, MoveIntent>,
+        WriteStorage<'s, AnimationStateMachine>,
+        WriteStorage<'s, Shooter>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, PlatformCollisionPoints>,
         ReadStorage<'s, Team>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Collidee>,
+        ReadStorage<'s, Grounded>,
+        ReadStorage<'s, GravityDirection>,
+        ReadStorage<'s, Dormant>,
+        Read<'s, Behaviours>,
+        Read<'s, RTree<RTreeEntity>>,
+        Read<'s, NavGraph>,
+        Read<'s, Factions>,
         Write<'s, EventChannel<Events>>,
         Read<'s, Time>,
         Entities<'s>,
     );
 
-    fn run(&mut self, (mut shoot_ai, teams, mut events_channel, time, entities): Self::SystemData) {
-        for (shoot, team, entity) in (&mut shoot_ai, &teams, &entities).join() {
-            shoot.counter += time.delta_seconds();
+    fn run(
+        &mut self,
+        (
+            mut behaviour_ais,
+            mut velocities,
+            mut orientations,
+            mut move_intents,
+            mut animation_states,
+            mut shooters,
+            positions,
+            collision_points,
+            teams,
+            healths,
+            collidees,
+            groundeds,
+            gravities,
+            dormants,
+            behaviours,
+            rtree,
+            nav,
+            factions,
+            mut events_channel,
+            time,
+            entities,
+        ): Self::SystemData,
+    ) {
+        // Anything living, projectiles and damage boxes don't count
+        let targets: Vec<(Entity, Vec2, Team)> = (&entities, &positions, &teams, &healths)
+            .join()
+            .filter(|(_, _, _, health)| health.alive())
+            .map(|(entity, pos, team, _)| (entity, pos.0, *team))
+            .collect();
 
-            if shoot.counter > 2.0 {
-                shoot.counter = 0.0;
+        for (ai, velocity, orientation, intent, pos, points, team, health, entity, _) in (
+            &mut behaviour_ais,
+            &mut velocities,
+            &mut orientations,
+            &mut move_intents,
+            &positions,
+            &collision_points,
+            &teams,
+            &healths,
+            &entities,
+            !&dormants,
+        )
+            .join()
+        {
+            if !health.alive() {
+                continue;
+            }
+            let tree = match behaviours.0.get(&ai.name) {
+                Some(tree) => tree,
+                None => continue,
+            };
+            ai.melee_cooldown = (ai.melee_cooldown - time.delta_seconds()).max(0.0);
 
-                let parent = Some(entity);
-                let pos = Vec2::new(TILE_WIDTH / 1.5, TILE_HEIGHT / 4.);
-                let size = Vec2::new(TILE_WIDTH, TILE_HEIGHT / 4.);
-                events_channel.single_write(Events::CreateDamageBox(parent, pos, size, *team));
+            let gravity = gravities.get(entity).cloned().unwrap_or_default();
+            let context = BehaviourContext {
+                entity,
+                pos: pos.0,
+                half_size: points.half_size,
+                gravity,
+                team: *team,
+                grounded: groundeds.get(entity).map_or(false, |grounded| grounded.0),
+                touching_wall: collidees
+                    .get(entity)
+                    .map_or(false, |collidee| hit_wall(collidee, &gravity)),
+                // Never itself, even on a team that's hostile to its own members
+                target: targets
+                    .iter()
+                    .filter(|(target, _, target_team)| {
+                        *target != entity && factions.hostile(team, target_team)
+                    })
+                    .map(|(_, target, _)| *target)
+                    .min_by(|a, b| {
+                        let (a, b) = ((*a - pos.0).mag_sq(), (*b - pos.0).mag_sq());
+                        a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+                    }),
+                shooter_ready: shooters
+                    .get(entity)
+                    .map(|shooter| shooter.cooldown <= 0.0 && shooter.burst_left == 0),
+                rtree: &rtree,
+                nav: &nav,
+            };
+            let mut output = BehaviourOutput {
+                facing: match orientation.vec.x < 0.0 {
+                    true => -1.0,
+                    false => 1.0,
+                },
+                ..BehaviourOutput::default()
+            };
+            run_behaviour(tree, &context, ai, &mut output);
+
+            orientation.vec.x = output.facing;
+            intent.vec = match output.walk == 0.0 {
+                true => Vec2::zero(),
+                false => Vec2::new(output.walk.signum(), 0.0),
+            };
+            let mut grav_vel = gravitationally_de_adapted_velocity(&velocity.0, &gravity);
+            if context.grounded {
+                let result = grav_vel.lerp(
+                    Vec2::new(output.walk, grav_vel.y),
+                    time.delta_seconds() * 4.0,
+                );
+                grav_vel.x = result.x;
+            }
+            if let Some(strength) = output.jump {
+                grav_vel.y += strength;
+            }
+            velocity.0 = gravitationally_adapted_velocity(&grav_vel, &gravity);
+
+            if output.fire {
+                if let Some(shooter) = shooters.get_mut(entity) {
+                    shooter.pull_trigger();
+                }
+            }
+            if output.attacking {
+                if let Some(state) = animation_states.get_mut(entity) {
+                    state.attacking = true;
+                }
+            }
+            for event in output.events {
+                events_channel.single_write(event);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits(from: (f32, f32), to: (f32, f32)) -> bool {
+        let (center, half_size) = (Vec2::new(0.0, 0.0), Vec2::new(1.0, 1.0));
+        segment_hits_box(
+            Vec2::new(from.0, from.1),
+            Vec2::new(to.0, to.1),
+            center,
+            half_size,
+        )
+    }
+
+    #[test]
+    fn segment_through_the_box_hits() {
+        assert!(hits((-5.0, 0.0), (5.0, 0.0)));
+        assert!(hits((-5.0, -5.0), (5.0, 5.0)));
+        assert!(hits((0.0, -5.0), (0.0, 5.0)));
+    }
+
+    #[test]
+    fn segment_inside_the_box_hits() {
+        assert!(hits((-0.5, 0.0), (0.5, 0.0)));
+    }
+
+    #[test]
+    fn segment_past_the_box_misses() {
+        assert!(!hits((-5.0, 2.0), (5.0, 2.0)));
+        assert!(!hits((2.0, -5.0), (2.0, 5.0)));
+        assert!(!hits((-5.0, 0.0), (5.0, 10.0)));
+    }
+
+    #[test]
+    fn segment_stopping_short_misses() {
+        assert!(!hits((-5.0, 0.0), (-2.0, 0.0)));
+        assert!(!hits((5.0, 5.0), (2.0, 2.0)));
+    }
+}
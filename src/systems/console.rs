@@ -14,10 +14,16 @@ use amethyst::{
 };
 
 use crate::{
+    abilities::{Abilities, AbilityLibrary},
+    components::game::Player,
     events::{Events, PlayerEvent},
+    flags::Flags,
+    rng::WorldRng,
+    settings::Settings,
     utils::read_line_from_console,
 };
 use bami::Input;
+use log::{error, info};
 
 #[derive(SystemDesc)]
 pub struct ConsoleInputSystem;
@@ -27,9 +33,28 @@ impl<'s> System<'s> for ConsoleInputSystem {
         Read<'s, Input<StringBindings>>,
         Write<'s, EventChannel<Events>>,
         Write<'s, EventChannel<PlayerEvent>>,
+        Write<'s, Settings>,
+        Write<'s, Flags>,
+        ReadStorage<'s, Player>,
+        WriteStorage<'s, Abilities>,
+        Read<'s, AbilityLibrary>,
+        Write<'s, WorldRng>,
     );
 
-    fn run(&mut self, (input, mut events_channel, mut player_event_channel): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            input,
+            mut events_channel,
+            mut player_event_channel,
+            mut settings,
+            mut flags,
+            players,
+            mut abilities,
+            library,
+            mut rng,
+        ): Self::SystemData,
+    ) {
         let input_string;
 
         if input.actions.single_press(&"console".to_string()).is_down {
@@ -54,6 +79,79 @@ impl<'s> System<'s> for ConsoleInputSystem {
             "revive" => {
                 player_event_channel.single_write(PlayerEvent::Revive(5));
             }
+            // volume <master|music|sfx> <0.0 - 1.0>
+            "volume" => {
+                let volume = match args.get(2).map(|arg| arg.parse::<f32>()) {
+                    Some(Ok(volume)) => volume.max(0.0).min(1.0),
+                    _ => {
+                        error!("Usage: volume <master|music|sfx> <0.0 - 1.0>");
+                        return;
+                    }
+                };
+                match args[1] {
+                    "master" => settings.audio.master = volume,
+                    "music" => settings.audio.music = volume,
+                    "sfx" => settings.audio.sfx = volume,
+                    bus => {
+                        error!("Unknown volume bus {}", bus);
+                        return;
+                    }
+                }
+                info!("Set {} volume to {}", args[1], volume);
+                settings.save();
+            }
+            // flag [name] [value]
+            "flag" => match (args.get(1), args.get(2).map(|arg| arg.parse::<i32>())) {
+                (None, _) => {
+                    for (name, value) in flags.iter() {
+                        info!("{} = {}", name, value);
+                    }
+                }
+                (Some(name), None) => info!("{} = {}", name, flags.value(name)),
+                (Some(name), Some(Ok(value))) => {
+                    flags.set_value(name, value);
+                    info!("Set {} to {}", name, value);
+                }
+                (Some(_), Some(Err(_))) => error!("Usage: flag [name] [value]"),
+            },
+            // seed [number|random], takes effect from the next level start
+            "seed" => match args.get(1) {
+                None => info!("Seed is {}, fixed: {:?}", rng.seed, rng.fixed_seed),
+                Some(&"random") => {
+                    rng.fixed_seed = None;
+                    info!("Levels will start with random seeds");
+                }
+                Some(seed) => match seed.parse::<u64>() {
+                    Ok(seed) => {
+                        rng.fixed_seed = Some(seed);
+                        info!("Levels will start with seed {}", seed);
+                    }
+                    Err(_) => error!("Usage: seed [number|random]"),
+                },
+            },
+            // ability [name] [slot]
+            "ability" => {
+                for (player, abilities) in (&players, &mut abilities).join() {
+                    if !player.0 {
+                        continue;
+                    }
+                    match (args.get(1), args.get(2).map(|arg| arg.parse::<usize>())) {
+                        (None, _) => {
+                            info!("Equipped: {:?}", abilities.equipped);
+                            info!("Unlocked: {:?}", abilities.unlocked);
+                        }
+                        (Some(name), None) => abilities.unlock(&library, name),
+                        (Some(name), Some(Ok(slot))) => {
+                            abilities.unlock(&library, name);
+                            match abilities.equip(name, slot) {
+                                true => info!("Equipped {} in slot {}", name, slot),
+                                false => error!("Couldn't equip {} in slot {}", name, slot),
+                            }
+                        }
+                        (Some(_), Some(Err(_))) => error!("Usage: ability [name] [slot]"),
+                    }
+                }
+            }
             _ => {}
         }
     }
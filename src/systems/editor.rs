@@ -22,7 +22,7 @@ use crate::{
             InsertionGameObject, InstanceEntityId, RealCursorPosition, SizeForEditorGrid,
         },
         game::{Health, Player, SerializedObject, SerializedObjectType},
-        graphics::{Scale, SpriteSheetType},
+        graphics::Scale,
         physics::{GravityDirection, Grounded, PlatformCuboid, Position},
     },
     events::Events,
@@ -465,6 +465,12 @@ impl<'s> System<'s> for EditorButtonEventSystem {
                     editor_event_writer.single_write(EditorEvents::ChangeInsertionGameObject(0));
                 } else if input.actions.single_press(&"2".to_string()).is_down {
                     editor_event_writer.single_write(EditorEvents::ChangeInsertionGameObject(1));
+                } else if input.actions.single_press(&"3".to_string()).is_down {
+                    editor_event_writer.single_write(EditorEvents::ChangeInsertionGameObject(2));
+                } else if input.actions.single_press(&"4".to_string()).is_down {
+                    editor_event_writer.single_write(EditorEvents::ChangeInsertionGameObject(3));
+                } else if input.actions.single_press(&"5".to_string()).is_down {
+                    editor_event_writer.single_write(EditorEvents::ChangeInsertionGameObject(4));
                 }
             }
             CursorState::EditGameObject => {
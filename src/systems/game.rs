@@ -1,14 +1,18 @@
 use crate::{
+    abilities::{Abilities, AbilityLibrary},
     components::{
         game::{
-            CameraTarget, CollisionEvent, Health, Invincibility, Player, Projectile, Team,
-            TimedExistence,
+            Activatable, CameraTarget, CollisionEvent, DamageKind, Dormant, Health, HitReaction,
+            HitStop, Invincibility, Knockback, Player, Projectile, Team, TimedExistence,
         },
-        graphics::{AnimationCounter, CameraLimit},
+        graphics::{AnimationCounter, CameraLimit, CameraRotation, CameraShake},
         physics::{Collidee, GravityDirection, PlatformCuboid, Position, Velocity},
     },
-    events::PlayerEvent,
-    states::pizzatopia::{TILE_HEIGHT, TILE_WIDTH},
+    events::{DamageEvent, PlayerEvent},
+    flags::Flags,
+    loot::{Loot, LootLibrary, PickupEffect, HEART, VEGGIE},
+    rng::WorldRng,
+    states::pizzatopia::{CAM_HEIGHT, CAM_WIDTH, TILE_HEIGHT, TILE_WIDTH},
     systems::physics::{gravitationally_de_adapted_velocity, CollisionDirection},
 };
 use amethyst::{
@@ -16,34 +20,25 @@ use amethyst::{
         math::Vector3,
         shrev::{EventChannel, ReaderId},
         timing::Time,
-        SystemDesc, Transform,
+        Hidden, SystemDesc, Transform,
     },
     derive::SystemDesc,
     ecs::{
-        Entities, Join, LazyUpdate, Read, ReadStorage, System, SystemData, World, Write,
+        Entities, Entity, Join, LazyUpdate, Read, ReadStorage, System, SystemData, World, Write,
         WriteStorage,
     },
     renderer::{Camera, ImageFormat, SpriteRender, SpriteSheet, SpriteSheetFormat, Texture},
 };
 use log::{error, info, warn};
-use std::{cmp::min, ops::Deref};
-
-use amethyst::{
-    assets::AssetStorage,
-    audio::{output::Output, Source},
-    ecs::ReadExpect,
-};
+use std::ops::Deref;
 
+use crate::components::editor::{EditorCursor, EditorFlag};
 use crate::components::entity_builder::entity_builder::initialize_pickup;
-use crate::components::game::{Drops, PicksThingsUp, Pickup};
+use crate::components::game::{DeathHook, Inventory, OnDeath, PicksThingsUp, Pickup, Resistances};
 use crate::components::graphics::Pan;
 use crate::components::physics::{ChildTo, Orientation};
 use crate::events::Events;
-use crate::ui::ui_builder::initialize_ui_label;
-use crate::{
-    audio::{play_damage_sound, Sounds},
-    components::editor::{EditorCursor, EditorFlag},
-};
+use crate::ui::dialogue::DialogueUi;
 use amethyst::prelude::{Builder, WorldExt};
 use rand::{random, Rng};
 use std::ops::{Add, Mul};
@@ -73,12 +68,25 @@ impl<'s> System<'s> for EnemyCollisionSystem {
         WriteStorage<'s, Position>,
         WriteStorage<'s, PicksThingsUp>,
         WriteStorage<'s, Pickup>,
+        WriteStorage<'s, Abilities>,
+        WriteStorage<'s, Inventory>,
+        WriteStorage<'s, GravityDirection>,
+        Read<'s, AbilityLibrary>,
+        ReadStorage<'s, Resistances>,
+        ReadStorage<'s, OnDeath>,
+        ReadStorage<'s, Loot>,
+        Read<'s, LootLibrary>,
+        Write<'s, WorldRng>,
+        Write<'s, Flags>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Knockback>,
+        ReadStorage<'s, HitReaction>,
+        WriteStorage<'s, CameraShake>,
+        Write<'s, HitStop>,
         Entities<'s>,
         Read<'s, EventChannel<CollisionEvent>>,
+        Write<'s, EventChannel<DamageEvent>>,
         Read<'s, LazyUpdate>,
-        Read<'s, AssetStorage<Source>>,
-        ReadExpect<'s, Sounds>,
-        Option<Read<'s, Output>>,
     );
 
     fn run(
@@ -91,111 +99,114 @@ impl<'s> System<'s> for EnemyCollisionSystem {
             mut positions,
             mut pickers,
             picked,
+            mut abilities,
+            mut inventories,
+            mut gravities,
+            ability_library,
+            resistances,
+            on_deaths,
+            loots,
+            loot_library,
+            mut rng,
+            mut flags,
+            players,
+            knockbacks,
+            hit_reactions,
+            mut camera_shakes,
+            mut hit_stop,
             entities,
             event_channel,
+            mut damage_events,
             lazy,
-            storage,
-            sounds,
-            audio_output,
         ): Self::SystemData,
     ) {
         for event in event_channel.read(&mut self.reader) {
             match event {
-                CollisionEvent::EnemyCollision(entity_id, hitter, damage) => {
+                CollisionEvent::EnemyCollision(entity_id, hitter, damage, kind) => {
+                    let hitee_entity = entities.entity(*entity_id);
+                    let damage = resistances
+                        .get(hitee_entity)
+                        .map_or(*damage, |resistances| resistances.apply(*damage, *kind));
                     if let Some(iframes) = &mut invincibilities.get_mut(entities.entity(*entity_id))
                     {
                         if let Some(health) = &mut healths.get_mut(entities.entity(*entity_id)) {
-                            if health.0 > 0 && iframes.0 == 0.0 {
+                            if health.alive() && iframes.0 == 0.0 && damage > 0 {
                                 // Don't deal more damage than the character has hp
-                                let dmg = min(*damage, health.0);
-                                health.0 -= dmg;
+                                let dmg = health.hurt(damage);
                                 iframes.0 += IFRAMES_PER_HIT;
-                                play_damage_sound(
-                                    &*sounds,
-                                    &storage,
-                                    audio_output.as_ref().map(|o| o.deref()),
-                                );
 
-                                if dmg > 0 {
-                                    let entity = entities.entity(*entity_id);
+                                // Players lose some of what they've gathered
+                                let entity = entities.entity(*entity_id);
+                                let player = players.get(entity).map_or(false, |player| player.0);
+                                if let Some(picker) = pickers.get_mut(entity).filter(|_| player) {
+                                    let hurt = &loot_library.hurt;
+                                    let drops: Vec<(Pickup, Vec2)> = hurt
+                                        .roll(&mut *rng, picker.amount_gathered)
+                                        .into_iter()
+                                        .map(|vel| (hurt.pickup.clone(), vel))
+                                        .collect();
+                                    picker.amount_gathered -= drops.len() as u32;
                                     if let Some(pos) = positions.get(entity) {
-                                        let pos_clone = pos.clone();
+                                        let pos = pos.0.add(Vec2::new(0.0, TILE_HEIGHT));
                                         lazy.exec_mut(move |world| {
-                                            let player = {
-                                                let storage = world.read_storage::<Player>();
-                                                let player =
-                                                    storage.get(entity).unwrap_or(&Player(false));
-                                                player.0
-                                            };
-                                            if player {
-                                                let drops = {
-                                                    let mut storage =
-                                                        world.write_storage::<PicksThingsUp>();
-                                                    let mut def = PicksThingsUp::default();
-                                                    let picked_up =
-                                                        storage.get_mut(entity).unwrap_or(&mut def);
-                                                    let total_dropped;
-                                                    if picked_up.amount_gathered <= 2 {
-                                                        total_dropped = picked_up.amount_gathered;
-                                                        picked_up.amount_gathered = 0;
-                                                    } else {
-                                                        total_dropped = 2;
-                                                        picked_up.amount_gathered -= 2;
-                                                    }
-                                                    total_dropped
-                                                };
-                                                for _i in 0..drops {
-                                                    let mut rng = rand::thread_rng();
-                                                    let x_vel: f32 = rng.gen_range(-4.0, 4.0);
-                                                    let y_vel: f32 = rng.gen_range(7.0, 15.0);
-                                                    initialize_pickup(
-                                                        world,
-                                                        &pos_clone
-                                                            .0
-                                                            .add(Vec2::new(0.0, TILE_HEIGHT)),
-                                                        &Vec2::new(x_vel, y_vel),
-                                                        Pickup::Veggie,
-                                                    );
-                                                }
-                                            }
+                                            drop_pickups(world, pos, drops);
                                         });
                                     }
                                 }
 
-                                warn!("Health is now {}", health.0);
-                                if health.0 == 0 {
+                                if let Some(reaction) = hit_reactions.get(hitee_entity) {
+                                    hit_stop.add(reaction.hit_stop);
+                                    for shake in (&mut camera_shakes).join() {
+                                        shake.add(reaction.shake);
+                                    }
+                                }
+
+                                warn!(
+                                    "Health is now {} after {} {:?} damage",
+                                    health.current, dmg, kind
+                                );
+                                let pos = positions.get(hitee_entity).map(|pos| pos.0);
+                                damage_events.single_write(DamageEvent::Damaged {
+                                    id: *entity_id,
+                                    amount: dmg,
+                                    pos,
+                                });
+                                if !health.alive() {
+                                    damage_events.single_write(DamageEvent::Died {
+                                        id: *entity_id,
+                                        pos,
+                                    });
                                     let entity = entities.entity(*entity_id);
-                                    if let Some(pos) = positions.get_mut(entity) {
-                                        let pos_clone = pos.clone();
-                                        lazy.exec_mut(move |world| {
-                                            let drops = {
-                                                let drops = world.read_storage::<Drops>();
-                                                let amount = drops.get(entity).unwrap_or(&Drops(0));
-                                                amount.0
-                                            };
-                                            for _i in 0..drops {
-                                                let mut rng = rand::thread_rng();
-                                                let x_vel: f32 = rng.gen_range(-2.0, 2.0);
-                                                let y_vel: f32 = rng.gen_range(7.0, 15.0);
-                                                initialize_pickup(
-                                                    world,
-                                                    &pos_clone.0,
-                                                    &Vec2::new(x_vel, y_vel),
-                                                    Pickup::Heart,
-                                                );
-                                            }
-                                        });
-                                        pos.0.y = -999.;
+                                    if let Some(pos) = positions.get(entity) {
+                                        let table = loots
+                                            .get(entity)
+                                            .and_then(|loot| loot_library.table(&loot.0));
+                                        if let Some(table) = table {
+                                            let (drops, pos) = (table.roll(&mut *rng), pos.0);
+                                            lazy.exec_mut(move |world| {
+                                                drop_pickups(world, pos, drops);
+                                            });
+                                        }
+                                        if let Some(on_death) = on_deaths.get(entity) {
+                                            let (hooks, pos) = (on_death.0.clone(), pos.0);
+                                            lazy.exec_mut(move |world| {
+                                                run_death_hooks(world, &hooks, pos);
+                                            });
+                                        }
                                     }
+                                    lazy.exec_mut(move |world| lay_to_rest(world, entity));
                                 }
 
                                 let hitter = entities.entity(*hitter);
                                 let hitee = entities.entity(*entity_id);
-                                if let Some(vel) = velocities.get_mut(hitee) {
+                                // Falls hurt without anything to be knocked away from
+                                let knocked = velocities.get_mut(hitee).filter(|_| hitter != hitee);
+                                if let Some(vel) = knocked {
                                     let hitter_pos = positions.get(hitter).unwrap().clone();
                                     let hitee_pos = positions.get(hitee).unwrap().clone();
                                     let going_right = hitter_pos.0.x < hitee_pos.0.x;
-                                    let knock_back = Vec2::new(8., 4.);
+                                    let knock_back =
+                                        knockbacks.get(hitter).cloned().unwrap_or_default().0;
                                     vel.0 = match going_right {
                                         true => knock_back,
                                         false => knock_back.mul(Vec2::new(-1., 1.0)),
@@ -221,18 +232,60 @@ impl<'s> System<'s> for EnemyCollisionSystem {
                 CollisionEvent::ItemCollect(character_id, item_id) => {
                     if let Some(picked) = picked.get(entities.entity(*item_id)) {
                         match picked {
-                            Pickup::Veggie => {
-                                let picker =
-                                    pickers.get_mut(entities.entity(*character_id)).unwrap();
-                                picker.amount_gathered += 1;
-                                info!("Picked up item! New amount: {:?}", picker.amount_gathered);
+                            Pickup::Item(name) => {
+                                let character = entities.entity(*character_id);
+                                let effects = loot_library
+                                    .pickups
+                                    .get(name)
+                                    .map_or(&[][..], |def| &def.effects[..]);
+                                for effect in effects {
+                                    match effect {
+                                        PickupEffect::Gather(amount) => {
+                                            if let Some(picker) = pickers.get_mut(character) {
+                                                picker.amount_gathered += *amount;
+                                            }
+                                        }
+                                        PickupEffect::Heal(amount) => {
+                                            if let Some(health) = healths.get_mut(character) {
+                                                health.heal(*amount);
+                                            }
+                                        }
+                                        PickupEffect::Invincible(seconds) => {
+                                            if let Some(iframes) =
+                                                invincibilities.get_mut(character)
+                                            {
+                                                iframes.0 = iframes.0.max(*seconds);
+                                            }
+                                        }
+                                        PickupEffect::Unlock(ability) => {
+                                            if let Some(abilities) = abilities.get_mut(character) {
+                                                abilities.unlock(&ability_library, ability);
+                                            }
+                                        }
+                                        PickupEffect::SetFlag(flag) => flags.set(flag),
+                                        PickupEffect::AddValue(name, amount) => {
+                                            flags.add_value(name, *amount)
+                                        }
+                                        PickupEffect::FlipGravity => {
+                                            if let Some(gravity) = gravities.get_mut(character) {
+                                                gravity.0 = gravity.0.flipped();
+                                            }
+                                        }
+                                        PickupEffect::SetGravity(direction) => {
+                                            if let Some(gravity) = gravities.get_mut(character) {
+                                                gravity.0 = *direction;
+                                            }
+                                        }
+                                    }
+                                }
+                                info!("Picked up {}", name);
                             }
-                            Pickup::Heart => {
-                                if let Some(health) =
-                                    healths.get_mut(entities.entity(*character_id))
+                            Pickup::Key(name) => {
+                                if let Some(inventory) =
+                                    inventories.get_mut(entities.entity(*character_id))
                                 {
-                                    health.0 += 1;
-                                    info!("Picked up health! New health: {}", health.0);
+                                    inventory.add_key(name);
+                                    info!("Picked up key {}", name);
                                 }
                             }
                         }
@@ -241,18 +294,9 @@ impl<'s> System<'s> for EnemyCollisionSystem {
                         .delete(entities.entity(*item_id))
                         .expect("Failed to delete pickup");
                 }
-                CollisionEvent::Talk(text, id) => {
-                    let parent = entities.entity(*id);
-                    let text = text.clone();
-                    let offset = Vec2::new(0., TILE_HEIGHT / 1.5);
-                    lazy.exec_mut(move |world| {
-                        let builder = initialize_ui_label(world, text.clone(), 20.);
-                        builder
-                            .with(Position(offset.clone()))
-                            .with(ChildTo { parent, offset })
-                            .with(TimedExistence(2.0))
-                            .build();
-                    });
+                CollisionEvent::Talk(_, id) => {
+                    let speaker = entities.entity(*id);
+                    lazy.exec_mut(move |world| DialogueUi::talk_to(world, speaker));
                 }
                 CollisionEvent::Gift(heart, veggie, id) => {
                     let parent = entities.entity(*id);
@@ -272,19 +316,18 @@ impl<'s> System<'s> for EnemyCollisionSystem {
                             .clone();
                         let pos = Vec2::new(dir.vec.x * TILE_WIDTH / 2., TILE_HEIGHT / 4.)
                             .add(parent_pos);
-                        let mut rng = rand::thread_rng();
-                        if heart {
-                            let x_vel: f32 = rng.gen_range(0.0, 8.0);
-                            let y_vel: f32 = rng.gen_range(7.0, 15.0);
-                            let vel = Vec2::new(x_vel, y_vel).mul(dir.vec);
-                            initialize_pickup(world, &pos, &vel, Pickup::Heart);
-                        }
-                        if veggie {
-                            let x_vel: f32 = rng.gen_range(0.0, 8.0);
-                            let y_vel: f32 = rng.gen_range(7.0, 15.0);
-                            let vel = Vec2::new(x_vel, y_vel).mul(dir.vec);
-                            initialize_pickup(world, &pos, &vel, Pickup::Veggie);
+                        let mut gifts = Vec::new();
+                        {
+                            let mut rng = world.write_resource::<WorldRng>();
+                            let kinds = [(heart, HEART), (veggie, VEGGIE)];
+                            for (_, kind) in kinds.iter().filter(|(given, _)| *given) {
+                                let x_vel: f32 = rng.gen_range(0.0, 8.0);
+                                let y_vel: f32 = rng.gen_range(7.0, 15.0);
+                                let vel = Vec2::new(x_vel, y_vel).mul(dir.vec);
+                                gifts.push((Pickup::Item(String::from(*kind)), vel));
+                            }
                         }
+                        drop_pickups(world, pos, gifts);
                     });
                 }
             }
@@ -292,6 +335,233 @@ impl<'s> System<'s> for EnemyCollisionSystem {
     }
 }
 
+// Dead actors that can wake up again go dormant where they fell, anything else is moved off the map
+pub fn lay_to_rest(world: &mut World, entity: Entity) {
+    match world.read_storage::<Activatable>().contains(entity) {
+        true => {
+            world
+                .write_storage::<Dormant>()
+                .insert(entity, Dormant)
+                .expect("Failed to make dead entity dormant");
+            world
+                .write_storage::<Hidden>()
+                .insert(entity, Hidden)
+                .expect("Failed to hide dead entity");
+        }
+        false => {
+            if let Some(pos) = world.write_storage::<Position>().get_mut(entity) {
+                pos.0.y = -999.;
+            }
+        }
+    }
+}
+
+// Creates each pickup at `pos` with its own velocity
+pub fn drop_pickups(world: &mut World, pos: Vec2, drops: Vec<(Pickup, Vec2)>) {
+    for (kind, vel) in drops {
+        initialize_pickup(world, &pos, &vel, kind);
+    }
+}
+
+// `pos` is where the entity died
+pub fn run_death_hooks(world: &mut World, hooks: &[DeathHook], pos: Vec2) {
+    for hook in hooks {
+        match hook {
+            DeathHook::DropLoot(name) => {
+                let drops = world
+                    .read_resource::<LootLibrary>()
+                    .table(name)
+                    .map(|table| table.roll(&mut *world.write_resource::<WorldRng>()))
+                    .unwrap_or_default();
+                drop_pickups(world, pos, drops);
+            }
+            DeathHook::SetFlag(flag) => world.write_resource::<Flags>().set(flag),
+            DeathHook::AddValue(name, amount) => {
+                world.write_resource::<Flags>().add_value(name, *amount)
+            }
+            DeathHook::Spawn(serialized_object) => {
+                let mut serialized_object = serialized_object.clone();
+                serialized_object.pos = Some(pos);
+                world
+                    .write_resource::<EventChannel<Events>>()
+                    .single_write(Events::SpawnGameObject(serialized_object));
+            }
+        }
+    }
+}
+
+// Landing faster than this hurts
+pub const FALL_DAMAGE_SPEED: f32 = 28.0;
+// Every this much faster than `FALL_DAMAGE_SPEED` hurts one more
+pub const FALL_DAMAGE_STEP: f32 = 6.0;
+
+#[derive(SystemDesc)]
+pub struct FallDamageSystem;
+
+impl<'s> System<'s> for FallDamageSystem {
+    type SystemData = (
+        ReadStorage<'s, Collidee>,
+        ReadStorage<'s, GravityDirection>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Dormant>,
+        Entities<'s>,
+        Write<'s, EventChannel<CollisionEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (collidees, gravities, healths, dormants, entities, mut channel): Self::SystemData,
+    ) {
+        for (collidee, gravity, health, entity, _) in
+            (&collidees, &gravities, &healths, &entities, !&dormants).join()
+        {
+            // Whichever collision is with the ground, going by gravity
+            let (landing, before) = match CollisionDirection::is_horizontal(&gravity.0) {
+                true => (&collidee.horizontal, &collidee.prev_horizontal),
+                false => (&collidee.vertical, &collidee.prev_vertical),
+            };
+            // Only on the frame it lands
+            if !health.alive() || before.is_some() {
+                continue;
+            }
+            if let Some(landing) = landing {
+                let speed =
+                    -gravitationally_de_adapted_velocity(&landing.old_collider_vel, gravity).y;
+                if speed > FALL_DAMAGE_SPEED {
+                    let damage = 1 + ((speed - FALL_DAMAGE_SPEED) / FALL_DAMAGE_STEP) as u32;
+                    channel.single_write(CollisionEvent::EnemyCollision(
+                        entity.id(),
+                        entity.id(),
+                        damage,
+                        DamageKind::Fall,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+#[derive(SystemDesc)]
+pub struct HealthRegenSystem;
+
+impl<'s> System<'s> for HealthRegenSystem {
+    type SystemData = (
+        WriteStorage<'s, Health>,
+        ReadStorage<'s, Dormant>,
+        Read<'s, Time>,
+    );
+
+    fn run(&mut self, (mut healths, dormants, time): Self::SystemData) {
+        for (health, _) in (&mut healths, !&dormants).join() {
+            if !health.alive() || health.regen <= 0.0 || health.current >= health.max {
+                health.regen_progress = 0.0;
+                continue;
+            }
+            health.regen_progress += health.regen * time.delta_seconds();
+            let whole = health.regen_progress.floor();
+            health.heal(whole as u32);
+            health.regen_progress -= whole;
+        }
+    }
+}
+
+// How far outside the camera view entities stay awake
+pub const ACTIVATION_MARGIN: f32 = TILE_WIDTH * 4.0;
+
+#[derive(SystemDesc)]
+pub struct ActivationSystem;
+
+impl<'s> System<'s> for ActivationSystem {
+    type SystemData = (
+        WriteStorage<'s, Position>,
+        WriteStorage<'s, Velocity>,
+        WriteStorage<'s, Health>,
+        WriteStorage<'s, Invincibility>,
+        WriteStorage<'s, GravityDirection>,
+        WriteStorage<'s, Dormant>,
+        WriteStorage<'s, Hidden>,
+        WriteStorage<'s, Activatable>,
+        ReadStorage<'s, Camera>,
+        Entities<'s>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut positions,
+            mut velocities,
+            mut healths,
+            mut invincibilities,
+            mut gravities,
+            mut dormants,
+            mut hiddens,
+            mut activatables,
+            cameras,
+            entities,
+        ): Self::SystemData,
+    ) {
+        let mut camera_pos = Vec2::new(0., 0.);
+        for (position, _camera) in (&positions, &cameras).join() {
+            camera_pos = position.0;
+        }
+        let reach = Vec2::new(
+            CAM_WIDTH / 2.0 + ACTIVATION_MARGIN,
+            CAM_HEIGHT / 2.0 + ACTIVATION_MARGIN,
+        );
+
+        for (activatable, entity) in (&mut activatables, &entities).join() {
+            let dormant = dormants.contains(entity);
+            let dead = healths.get(entity).map_or(false, |health| !health.alive());
+            // Dormant respawners come back when their spawn point does, the rest where they are
+            let pos = match (dormant && activatable.respawn, positions.get(entity)) {
+                (true, _) => activatable.spawn_pos,
+                (false, Some(pos)) => pos.0,
+                (false, None) => continue,
+            };
+            let in_range =
+                (pos.x - camera_pos.x).abs() <= reach.x && (pos.y - camera_pos.y).abs() <= reach.y;
+            if dormant && !in_range {
+                activatable.left = true;
+            }
+            // The dead only come back by respawning
+            let can_wake = activatable.left && (!dead || activatable.respawn);
+            match (in_range, dormant) {
+                (true, true) if can_wake => {
+                    activatable.left = false;
+                    dormants.remove(entity);
+                    hiddens.remove(entity);
+                    if activatable.respawn {
+                        if let Some(pos) = positions.get_mut(entity) {
+                            pos.0 = activatable.spawn_pos;
+                        }
+                        if let Some(velocity) = velocities.get_mut(entity) {
+                            velocity.0 = Vec2::default();
+                        }
+                        if let Some(health) = healths.get_mut(entity) {
+                            health.current = health.max;
+                        }
+                        if let Some(invincibility) = invincibilities.get_mut(entity) {
+                            invincibility.0 = 0.0;
+                        }
+                        if let Some(gravity) = gravities.get_mut(entity) {
+                            gravity.0 = activatable.spawn_gravity;
+                        }
+                    }
+                }
+                (false, false) => {
+                    dormants
+                        .insert(entity, Dormant)
+                        .expect("Failed to make entity dormant");
+                    hiddens
+                        .insert(entity, Hidden)
+                        .expect("Failed to hide dormant entity");
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
 #[derive(SystemDesc)]
 pub struct TimedExistenceSystem;
 
@@ -359,30 +629,75 @@ impl<'s> System<'s> for InvincibilitySystem {
     }
 }
 
+// Freezes the game while a hit stop is running. Counts down in real time, since game time
+// doesn't pass while it's frozen.
+#[derive(SystemDesc)]
+pub struct HitStopSystem;
+
+impl<'s> System<'s> for HitStopSystem {
+    type SystemData = (Write<'s, HitStop>, Write<'s, Time>);
+
+    fn run(&mut self, (mut hit_stop, mut time): Self::SystemData) {
+        if hit_stop.active() {
+            hit_stop.seconds = (hit_stop.seconds - time.delta_real_seconds()).max(0.0);
+            match hit_stop.active() {
+                true => {
+                    // Slow motion and the like carry on once the stop is over
+                    if hit_stop.resume_scale.is_none() {
+                        hit_stop.resume_scale = Some(time.time_scale());
+                    }
+                    time.set_time_scale(0.0);
+                }
+                false => time.set_time_scale(hit_stop.resume_scale.take().unwrap_or(1.0)),
+            }
+        }
+    }
+}
+
 #[derive(SystemDesc)]
 pub struct CameraTargetSystem;
 
 impl<'s> System<'s> for CameraTargetSystem {
     type SystemData = (
         WriteStorage<'s, Pan>,
+        WriteStorage<'s, CameraRotation>,
         ReadStorage<'s, Camera>,
         ReadStorage<'s, Position>,
         ReadStorage<'s, CameraTarget>,
         ReadStorage<'s, Player>,
+        ReadStorage<'s, GravityDirection>,
         ReadStorage<'s, EditorCursor>,
         ReadStorage<'s, EditorFlag>,
     );
 
     fn run(
         &mut self,
-        (mut pans, cameras, positions, targets, players, cursors, editor_flag): Self::SystemData,
+        (
+            mut pans,
+            mut rotations,
+            cameras,
+            positions,
+            targets,
+            players,
+            gravities,
+            cursors,
+            editor_flag,
+        ): Self::SystemData,
     ) {
         let mut position = Vec2::default();
+        // Upright unless following the player's gravity
+        let mut angle = 0.0;
         for (_camera, target) in (&cameras, &targets).join() {
             match target {
                 CameraTarget::Player => {
-                    for (_player, player_pos, _) in (&players, &positions, !&editor_flag).join() {
+                    // Other actors are Players too, only the controlled one is followed
+                    for (_player, player_pos, gravity, _) in
+                        (&players, &positions, (&gravities).maybe(), !&editor_flag)
+                            .join()
+                            .filter(|(player, _, _, _)| player.0)
+                    {
                         position = player_pos.0;
+                        angle = gravity.map_or(0.0, |gravity| gravity.0.rotation());
                     }
                 }
                 CameraTarget::Cursor => {
@@ -398,6 +713,9 @@ impl<'s> System<'s> for CameraTargetSystem {
         for (mut pan, _camera) in (&mut pans, &cameras).join() {
             pan.destination = position;
         }
+        for (rotation, _camera) in (&mut rotations, &cameras).join() {
+            rotation.target = angle;
+        }
     }
 }
 
@@ -448,14 +766,130 @@ impl<'s> System<'s> for PlayerEventsSystem {
             for (mut health, mut invincibility) in (&mut healths, &mut invincibilities).join() {
                 match event {
                     PlayerEvent::Revive(new_health) => {
-                        if health.0 == 0 {
-                            health.0 = *new_health;
+                        if !health.alive() {
+                            health.heal(*new_health);
                             invincibility.0 = IFRAMES_PER_HIT;
                             info!("Player revived.");
                         }
                     }
+                    _ => {}
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::ecs::RunNow;
+
+    const SPAWN: Vec2 = Vec2 { x: 100.0, y: 0.0 };
+
+    // A camera at the origin and an actor spawned in front of it
+    fn level(respawn: bool) -> (World, Entity, Entity) {
+        let mut world = World::new();
+        ActivationSystem.setup(&mut world);
+        let camera = world
+            .create_entity()
+            .with(Position(Vec2::new(0.0, 0.0)))
+            .with(Camera::standard_2d(CAM_WIDTH, CAM_HEIGHT))
+            .build();
+        let actor = world
+            .create_entity()
+            .with(Position(SPAWN))
+            .with(Velocity::default())
+            .with(Health::new(3))
+            .with(Activatable {
+                respawn,
+                spawn_pos: SPAWN,
+                ..Activatable::default()
+            })
+            .build();
+        (world, camera, actor)
+    }
+
+    fn step(world: &mut World) {
+        ActivationSystem.run_now(world);
+        world.maintain();
+    }
+
+    fn kill(world: &mut World, actor: Entity) {
+        world
+            .write_storage::<Health>()
+            .get_mut(actor)
+            .unwrap()
+            .current = 0;
+        lay_to_rest(world, actor);
+        step(world);
+    }
+
+    fn move_camera(world: &mut World, camera: Entity, x: f32) {
+        world
+            .write_storage::<Position>()
+            .get_mut(camera)
+            .unwrap()
+            .0
+            .x = x;
+        step(world);
+    }
+
+    fn dormant(world: &World, actor: Entity) -> bool {
+        world.read_storage::<Dormant>().contains(actor)
+    }
+
+    #[test]
+    fn killed_respawner_comes_back_after_leaving_and_returning() {
+        let (mut world, camera, actor) = level(true);
+        step(&mut world);
+        assert!(!dormant(&world, actor));
+
+        kill(&mut world, actor);
+        // Not in front of the player
+        assert!(dormant(&world, actor));
+
+        move_camera(&mut world, camera, 10_000.0);
+        assert!(dormant(&world, actor));
+
+        move_camera(&mut world, camera, 0.0);
+        assert!(!dormant(&world, actor));
+        let health = world.read_storage::<Health>().get(actor).unwrap().clone();
+        assert_eq!(health.current, health.max);
+        assert_eq!(
+            world.read_storage::<Position>().get(actor).unwrap().0,
+            SPAWN
+        );
+    }
+
+    #[test]
+    fn killed_actor_stays_dead_without_respawn() {
+        let (mut world, camera, actor) = level(false);
+        kill(&mut world, actor);
+        move_camera(&mut world, camera, 10_000.0);
+        move_camera(&mut world, camera, 0.0);
+        assert!(dormant(&world, actor));
+    }
+
+    #[test]
+    fn wandering_respawner_wakes_when_its_spawn_point_returns() {
+        let (mut world, camera, actor) = level(true);
+        step(&mut world);
+        // Walked off far away from where it started, then the camera left too
+        world
+            .write_storage::<Position>()
+            .get_mut(actor)
+            .unwrap()
+            .0
+            .x = 50_000.0;
+        move_camera(&mut world, camera, 10_000.0);
+        assert!(dormant(&world, actor));
+        step(&mut world);
+
+        move_camera(&mut world, camera, 0.0);
+        assert!(!dormant(&world, actor));
+        assert_eq!(
+            world.read_storage::<Position>().get(actor).unwrap().0,
+            SPAWN
+        );
+    }
+}
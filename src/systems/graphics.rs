@@ -1,21 +1,31 @@
 use crate::{
-    animations::{AnimationAction, AnimationFactory, AnimationId, SamplerAction},
+    animations::{
+        AnimationAction, AnimationFactory, AnimationFacts, AnimationId, AnimationLibrary,
+        SamplerAction,
+    },
     components::{
         editor::{CursorState, EditorCursor, EditorCursorState, InsertionGameObject},
-        game::{Health, Player, SerializedObjectType, SpriteRenderData},
+        game::{
+            Dormant, Health, HitFlash, HitReaction, Invincibility, Player, SerializedObjectType,
+            SpriteRenderData,
+        },
         graphics::{
-            AnimationCounter, BackgroundParallax, CameraLimit, PulseAnimation, Scale,
-            SpriteSheetType,
+            AnimationCounter, AnimationStateMachine, BackgroundParallax, CameraLimit,
+            CameraRotation, CameraShake, PulseAnimation, Scale, TileChunk, CAMERA_SHAKE_TIME,
+            SPRITE_SHEET_TILES,
         },
         physics::{
-            Ducking, GravityDirection, PlatformCollisionPoints, PlatformCuboid, Position, Velocity,
+            Ducking, GravityDirection, Grounded, PlatformCollisionPoints, PlatformCuboid, Position,
+            Velocity,
         },
     },
+    navigation::NavGraph,
     states::{
         loading::DrawDebugLines,
         pizzatopia::{CAM_HEIGHT, CAM_WIDTH, DEPTH_UI, TILE_HEIGHT, TILE_WIDTH},
     },
     systems::physics::{gravitationally_de_adapted_velocity, CollisionDirection},
+    triggers::Trigger,
     ui::{
         tile_characteristics::{EditorFieldUiComponents, UiIndex},
         UiStack,
@@ -24,7 +34,7 @@ use crate::{
 use amethyst::{
     animation::*,
     assets::{AssetStorage, Handle},
-    core::{math::Vector3, timing::Time, SystemDesc, Transform},
+    core::{math::Vector3, timing::Time, Hidden, SystemDesc, Transform},
     derive::SystemDesc,
     ecs::{
         Entities, Join, Read, ReadExpect, ReadStorage, System, SystemData, World, Write,
@@ -43,10 +53,19 @@ use crate::components::game::AnimatedTileComp;
 use crate::components::graphics::Pan;
 use crate::components::physics::Orientation;
 use amethyst::ui::{ScaleMode, UiTransform};
+use log::error;
+use rand::Rng;
 use std::collections::BTreeMap;
+use std::f32::consts::PI;
 use std::ops::Sub;
 use ultraviolet::{Lerp, Vec2, Vec3};
 
+// Counter-clockwise, in radians
+fn rotate(vec: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(vec.x * cos - vec.y * sin, vec.x * sin + vec.y * cos)
+}
+
 #[derive(SystemDesc)]
 pub struct CameraEdgeClampSystem;
 
@@ -116,17 +135,21 @@ impl<'s> System<'s> for UiTransformUpdateSystem {
         WriteStorage<'s, UiTransform>,
         ReadStorage<'s, Position>,
         ReadStorage<'s, Camera>,
+        ReadStorage<'s, CameraRotation>,
     );
 
-    fn run(&mut self, (mut transforms, positions, cameras): Self::SystemData) {
+    fn run(&mut self, (mut transforms, positions, cameras, rotations): Self::SystemData) {
         let mut camera_pos = None;
-        for (position, _camera) in (&positions, &cameras).join() {
+        let mut camera_angle = 0.0;
+        for (position, _camera, rotation) in (&positions, &cameras, (&rotations).maybe()).join() {
             camera_pos = Some(position.0);
+            camera_angle = rotation.map_or(0.0, |rotation| rotation.angle);
         }
         if let Some(cam_pos) = camera_pos {
             for (transform, position) in (&mut transforms, &positions).join() {
                 transform.scale_mode = ScaleMode::Percent;
-                let new_pos = position.0.sub(cam_pos);
+                // Screen space turns the opposite way to the camera
+                let new_pos = rotate(position.0.sub(cam_pos), -camera_angle);
                 let new_pos_scaled = Vec2::new(new_pos.x / CAM_WIDTH, new_pos.y / CAM_HEIGHT);
                 transform.local_x = new_pos_scaled.x;
                 transform.local_y = new_pos_scaled.y;
@@ -143,17 +166,37 @@ impl<'s> System<'s> for CollisionDebugLinesSystem {
         ReadStorage<'s, Position>,
         ReadStorage<'s, PlatformCuboid>,
         ReadStorage<'s, PlatformCollisionPoints>,
+        ReadStorage<'s, Trigger>,
+        Read<'s, NavGraph>,
         Read<'s, DrawDebugLines>,
         Write<'s, DebugLines>,
     );
 
     fn run(
         &mut self,
-        (positions, platform_cuboids, collision_points, draw, mut debug_lines): Self::SystemData,
+        (positions, platform_cuboids, collision_points, triggers, nav, draw, mut debug_lines): Self::SystemData,
     ) {
         if !draw.0 {
             return;
         }
+        for surface in nav.surfaces.iter() {
+            debug_lines.draw_line(
+                [surface.left, surface.y, DEPTH_UI].into(),
+                [surface.right, surface.y, DEPTH_UI].into(),
+                Srgba::new(0., 0., 1., 1.),
+            );
+        }
+        for trigger in (&triggers).join() {
+            if let Some(region) = &trigger.def.region {
+                let half_size = region.size / 2.0;
+                debug_lines.draw_rectangle(
+                    [region.pos.x - half_size.x, region.pos.y - half_size.y].into(),
+                    [region.pos.x + half_size.x, region.pos.y + half_size.y].into(),
+                    DEPTH_UI,
+                    Srgba::new(0., 1., 0., 1.),
+                );
+            }
+        }
         for (platform, position) in (&platform_cuboids, &positions).join() {
             debug_lines.draw_rectangle(
                 [
@@ -267,7 +310,7 @@ impl<'s> System<'s> for CursorSpriteUpdateSystem {
         ReadStorage<'s, EditorCursor>,
         Write<'s, InsertionGameObject>,
         ReadExpect<'s, CursorState>,
-        ReadExpect<'s, BTreeMap<u8, Handle<SpriteSheet>>>,
+        ReadExpect<'s, BTreeMap<String, Handle<SpriteSheet>>>,
         Read<'s, AssetStorage<SpriteSheet>>,
     );
 
@@ -287,10 +330,16 @@ impl<'s> System<'s> for CursorSpriteUpdateSystem {
                 CursorState::InsertMode | CursorState::EditGameObject => {
                     if insertion_serialized_object.0.sprite.is_none() {
                         insertion_serialized_object.0.sprite =
-                            Some(SpriteRenderData::new(SpriteSheetType::Tiles, 0));
+                            Some(SpriteRenderData::new(SPRITE_SHEET_TILES, 0));
                     }
                     if let Some(ref mut sprite_data) = insertion_serialized_object.0.sprite {
-                        let sheet = sprite_sheets.get(&(sprite_data.sheet as u8)).unwrap();
+                        let sheet = match sprite_sheets.get(&sprite_data.sheet) {
+                            Some(sheet) => sheet,
+                            None => {
+                                error!("Unknown sprite sheet {:?}", sprite_data.sheet);
+                                continue;
+                            }
+                        };
                         sprite.sprite_sheet = sheet.clone();
                         if let Some(sheet) = sheets.get(sheet) {
                             sprite_data.number =
@@ -304,11 +353,10 @@ impl<'s> System<'s> for CursorSpriteUpdateSystem {
                 }
                 _ => {
                     // Cursor sprite
-                    sprite.sprite_sheet = sprite_sheets
-                        .get(&(SpriteSheetType::Tiles as u8))
-                        .unwrap()
-                        .clone();
-                    sprite.sprite_number = 4;
+                    if let Some(sheet) = sprite_sheets.get(SPRITE_SHEET_TILES) {
+                        sprite.sprite_sheet = sheet.clone();
+                        sprite.sprite_number = 4;
+                    }
                 }
             }
         }
@@ -323,13 +371,125 @@ impl<'s> System<'s> for DeadDrawUpdateSystem {
 
     fn run(&mut self, (mut transforms, healths): Self::SystemData) {
         for (transform, health) in (&mut transforms, &healths).join() {
-            if health.0 == 0 {
+            if !health.alive() {
                 transform.set_translation_xyz(-9999.0, -9999.0, 0.0);
             }
         }
     }
 }
 
+// Shows that a character was hit for as long as its invincibility frames last
+#[derive(SystemDesc)]
+pub struct HitFlashSystem;
+
+impl<'s> System<'s> for HitFlashSystem {
+    type SystemData = (
+        WriteStorage<'s, HitReaction>,
+        WriteStorage<'s, Tint>,
+        ReadStorage<'s, Invincibility>,
+        ReadStorage<'s, Health>,
+        Entities<'s>,
+    );
+
+    fn run(
+        &mut self,
+        (mut reactions, mut tints, invincibilities, healths, entities): Self::SystemData,
+    ) {
+        for (reaction, invincibility, health, entity) in
+            (&mut reactions, &invincibilities, &healths, &entities).join()
+        {
+            let flash = match invincibility.0 > 0.0 && health.alive() {
+                true => match reaction.flash {
+                    HitFlash::None => None,
+                    HitFlash::Tint(r, g, b, a) => Some(Srgba::new(r, g, b, a)),
+                    HitFlash::Blink(interval) => {
+                        let visible = (invincibility.0 / interval) as i32 % 2 == 0;
+                        let alpha = match visible {
+                            true => 1.0,
+                            false => 0.2,
+                        };
+                        Some(Srgba::new(1.0, 1.0, 1.0, alpha))
+                    }
+                },
+                false => None,
+            };
+            let colour = match (flash, reaction.flashing) {
+                (Some(colour), _) => colour,
+                (None, true) => Srgba::new(1.0, 1.0, 1.0, 1.0),
+                (None, false) => continue,
+            };
+            reaction.flashing = flash.is_some();
+            // Only inserted the first time, after that the same tint is updated
+            match tints.get_mut(entity) {
+                Some(tint) => tint.0 = colour,
+                None => {
+                    tints
+                        .insert(entity, Tint(colour))
+                        .expect("Failed to insert hit flash Tint");
+                }
+            }
+        }
+    }
+}
+
+// Offsets the camera's transform after it was placed, so the shake never moves its `Position`
+#[derive(SystemDesc)]
+pub struct CameraShakeSystem;
+
+impl<'s> System<'s> for CameraShakeSystem {
+    type SystemData = (
+        WriteStorage<'s, CameraShake>,
+        WriteStorage<'s, Transform>,
+        Read<'s, Time>,
+    );
+
+    fn run(&mut self, (mut shakes, mut transforms, time): Self::SystemData) {
+        let mut rng = rand::thread_rng();
+        for (shake, transform) in (&mut shakes, &mut transforms).join() {
+            if shake.remaining <= 0.0 {
+                continue;
+            }
+            // Real time so the camera keeps shaking during hit stop
+            shake.remaining = (shake.remaining - time.delta_real_seconds()).max(0.0);
+            let amount = shake.strength * shake.remaining / CAMERA_SHAKE_TIME;
+            if amount > 0.0 {
+                transform.prepend_translation_x(rng.gen_range(-amount, amount));
+                transform.prepend_translation_y(rng.gen_range(-amount, amount));
+            }
+        }
+    }
+}
+
+// Eases the camera round to its target angle, the short way
+#[derive(SystemDesc)]
+pub struct CameraRotationSystem;
+
+impl<'s> System<'s> for CameraRotationSystem {
+    type SystemData = (
+        WriteStorage<'s, CameraRotation>,
+        WriteStorage<'s, Transform>,
+        Read<'s, Time>,
+    );
+
+    fn run(&mut self, (mut rotations, mut transforms, time): Self::SystemData) {
+        const TAU: f32 = 2.0 * PI;
+        for (rotation, transform) in (&mut rotations, &mut transforms).join() {
+            let mut diff = (rotation.target - rotation.angle).rem_euclid(TAU);
+            if diff > PI {
+                diff -= TAU;
+            }
+            match diff.abs() < 0.001 {
+                true => rotation.angle = rotation.target,
+                false => {
+                    let step = (rotation.speed_factor * time.delta_seconds()).min(1.0);
+                    rotation.angle = (rotation.angle + diff * step).rem_euclid(TAU);
+                }
+            }
+            transform.set_rotation_z_axis(rotation.angle);
+        }
+    }
+}
+
 #[derive(SystemDesc)]
 pub struct AnimatedTileSystem;
 
@@ -337,11 +497,12 @@ impl<'s> System<'s> for AnimatedTileSystem {
     type SystemData = (
         WriteStorage<'s, SpriteRender>,
         WriteStorage<'s, AnimatedTileComp>,
+        ReadStorage<'s, Dormant>,
         Read<'s, Time>,
     );
 
-    fn run(&mut self, (mut sprites, mut anims, time): Self::SystemData) {
-        for (sprite, anim) in (&mut sprites, &mut anims).join() {
+    fn run(&mut self, (mut sprites, mut anims, dormants, time): Self::SystemData) {
+        for (sprite, anim, _) in (&mut sprites, &mut anims, !&dormants).join() {
             anim.counter += time.delta_seconds();
             if anim.counter > anim.anim.time_per_frame {
                 anim.counter = 0.0;
@@ -356,6 +517,56 @@ impl<'s> System<'s> for AnimatedTileSystem {
     }
 }
 
+#[derive(SystemDesc)]
+pub struct TileChunkCullingSystem;
+
+impl<'s> System<'s> for TileChunkCullingSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, Camera>,
+        ReadStorage<'s, CameraRotation>,
+        ReadStorage<'s, TileChunk>,
+        WriteStorage<'s, Hidden>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, positions, cameras, rotations, chunks, mut hiddens): Self::SystemData,
+    ) {
+        let mut camera_pos = Vec2::new(0., 0.);
+        let mut camera_angle = 0.0f32;
+        for (position, _camera, rotation) in (&positions, &cameras, (&rotations).maybe()).join() {
+            camera_pos = position.0;
+            camera_angle = rotation.map_or(0.0, |rotation| rotation.angle);
+        }
+        // Box around the screen however the camera is turned
+        let (sin, cos) = camera_angle.sin_cos();
+        let camera_half_size = Vec2::new(
+            (cos.abs() * CAM_WIDTH + sin.abs() * CAM_HEIGHT) / 2.0,
+            (sin.abs() * CAM_WIDTH + cos.abs() * CAM_HEIGHT) / 2.0,
+        );
+
+        for (entity, chunk) in (&entities, &chunks).join() {
+            let reach = chunk.half_size + camera_half_size;
+            let visible = (chunk.center.x - camera_pos.x).abs() <= reach.x
+                && (chunk.center.y - camera_pos.y).abs() <= reach.y;
+            match visible {
+                true => {
+                    hiddens.remove(entity);
+                }
+                false => {
+                    if !hiddens.contains(entity) {
+                        hiddens
+                            .insert(entity, Hidden)
+                            .expect("Failed to hide tile chunk");
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[derive(SystemDesc)]
 pub struct BackgroundDrawUpdateSystem;
 
@@ -363,101 +574,234 @@ impl<'s> System<'s> for BackgroundDrawUpdateSystem {
     type SystemData = (
         WriteStorage<'s, Position>,
         WriteStorage<'s, BackgroundParallax>,
+        WriteStorage<'s, Transform>,
         ReadStorage<'s, Camera>,
+        ReadStorage<'s, CameraRotation>,
+        Read<'s, Time>,
     );
 
-    fn run(&mut self, (mut positions, mut bgs, cameras): Self::SystemData) {
+    fn run(
+        &mut self,
+        (mut positions, mut bgs, mut transforms, cameras, rotations, time): Self::SystemData,
+    ) {
         let mut translate = Vec2::new(0., 0.);
-        for (position, _camera) in (&positions, &cameras).join() {
+        let mut camera_angle = 0.0;
+        for (position, _camera, rotation) in (&positions, &cameras, (&rotations).maybe()).join() {
             translate = position.0;
+            camera_angle = rotation.map_or(0.0, |rotation| rotation.angle);
         }
-        for (position, bg) in (&mut positions, &mut bgs).join() {
-            let id = bg.0;
-            let ratio = 0.25 / (1. + id as f32);
+        for (position, bg, transform) in (&mut positions, &mut bgs, &mut transforms).join() {
+            bg.scroll += bg.layer.scroll_speed * time.delta_seconds();
+            if bg.layer.tiled {
+                // The chain repeats every two screens, so keep the scroll small
+                bg.scroll %= 2.0 * CAM_WIDTH;
+            }
+
+            let parallax = bg.layer.parallax;
+            let scroll = bg.scroll;
             let calc_final_x = |offset_index: i32| -> f32 {
-                return translate.x - (translate.x * ratio) + offset_index as f32 * CAM_WIDTH;
+                return translate.x - (translate.x * parallax.x)
+                    + scroll
+                    + offset_index as f32 * CAM_WIDTH;
             };
-            let mut final_x = calc_final_x(bg.1);
-            let off_screen_left = final_x + CAM_WIDTH < translate.x;
-            let off_screen_right = final_x - CAM_WIDTH > translate.x;
-            if off_screen_left {
-                bg.1 += 2;
-            } else if off_screen_right {
-                bg.1 -= 2;
+            let mut final_x = calc_final_x(bg.chain);
+            if bg.layer.tiled {
+                let off_screen_left = final_x + CAM_WIDTH < translate.x;
+                let off_screen_right = final_x - CAM_WIDTH > translate.x;
+                if off_screen_left {
+                    bg.chain += 2;
+                } else if off_screen_right {
+                    bg.chain -= 2;
+                }
+                final_x = calc_final_x(bg.chain);
             }
-            final_x = calc_final_x(bg.1);
             position.0.x = final_x;
-            position.0.y = translate.y;
+            position.0.y = translate.y - (translate.y * parallax.y) + bg.layer.offset_y;
+
+            // Turned with the camera, so backgrounds stay upright on screen and still cover it
+            position.0 = translate + rotate(position.0 - translate, camera_angle);
+            transform.set_rotation_z_axis(camera_angle);
         }
     }
 }
 
+// Moves characters through the state graph of their animation library entry
+// and plays the clips of the state they're in
 #[derive(SystemDesc)]
-pub struct SpriteUpdateSystem;
+pub struct AnimationStateSystem;
 
-impl<'s> System<'s> for SpriteUpdateSystem {
+impl<'s> System<'s> for AnimationStateSystem {
     type SystemData = (
-        WriteStorage<'s, Transform>,
+        WriteStorage<'s, AnimationStateMachine>,
         WriteStorage<'s, SpriteRender>,
-        WriteStorage<'s, Scale>,
         ReadStorage<'s, Velocity>,
-        ReadStorage<'s, Orientation>,
-        ReadStorage<'s, GravityDirection>,
-        ReadStorage<'s, AnimationCounter>,
+        ReadStorage<'s, Grounded>,
         ReadStorage<'s, Ducking>,
-        ReadStorage<'s, Player>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, Invincibility>,
+        ReadStorage<'s, GravityDirection>,
+        ReadStorage<'s, Dormant>,
         ReadStorage<'s, AnimationSet<AnimationId, Transform>>,
         WriteStorage<'s, AnimationControlSet<AnimationId, Transform>>,
         ReadStorage<'s, AnimationSet<AnimationId, SpriteRender>>,
         WriteStorage<'s, AnimationControlSet<AnimationId, SpriteRender>>,
+        ReadExpect<'s, AnimationLibrary>,
+        Read<'s, Time>,
         Entities<'s>,
     );
 
     fn run(
         &mut self,
         (
-            mut transforms,
+            mut machines,
             mut sprites,
-            mut scales,
             velocities,
-            orientations,
-            gravities,
-            anim_counters,
+            grounded,
             duckings,
-            players,
+            healths,
+            invincibilities,
+            gravities,
+            dormants,
             sets,
             mut controls,
             sprite_sets,
             mut sprite_controls,
+            library,
+            time,
             entities,
         ): Self::SystemData,
     ) {
-        for (sprite, _player, _anim, ducking) in (
+        for (
+            machine,
+            sprite,
+            velocity,
+            ground,
+            ducking,
+            health,
+            invincibility,
+            gravity,
+            _,
+            entity,
+        ) in (
+            &mut machines,
             &mut sprites,
-            &players,
-            (&anim_counters).maybe(),
+            &velocities,
+            (&grounded).maybe(),
             (&duckings).maybe(),
+            (&healths).maybe(),
+            (&invincibilities).maybe(),
+            (&gravities).maybe(),
+            !&dormants,
+            &entities,
         )
             .join()
         {
-            if ducking.is_some() {
-                sprite.sprite_number = 1;
-            } else {
-                sprite.sprite_number = 0;
+            let graph = match library.character(&machine.character) {
+                Some(graph) => graph,
+                None => continue,
+            };
+            let grav_dir = gravity
+                .map(|grav| grav.0)
+                .unwrap_or(CollisionDirection::FromTop);
+
+            machine.time_in_state += time.delta_seconds();
+            let facts = AnimationFacts {
+                grounded: ground.map_or(false, |ground| ground.0),
+                velocity: gravitationally_de_adapted_velocity(
+                    &velocity.0,
+                    &GravityDirection(grav_dir),
+                ),
+                ducking: ducking.is_some(),
+                attacking: machine.attacking,
+                hurt: invincibility.map_or(false, |invincibility| invincibility.0 > 0.0),
+                dead: health.map_or(false, |health| !health.alive()),
+                time_in_state: machine.time_in_state,
+            };
+            machine.attacking = false;
+
+            let next = match machine.state {
+                AnimationId::None => Some(graph.initial),
+                state => graph.next_state(state, &facts),
+            };
+            if let Some(next) = next {
+                // Stop whatever the old state was playing
+                AnimationFactory::set_sprite_animation(
+                    &sprite_sets,
+                    &mut sprite_controls,
+                    entity,
+                    machine.state,
+                    AnimationAction::AbortAnimation,
+                    None,
+                );
+                AnimationFactory::set_animation(
+                    &sets,
+                    &mut controls,
+                    entity,
+                    machine.state,
+                    AnimationAction::AbortAnimation,
+                    None,
+                );
+                machine.state = next;
+                machine.time_in_state = 0.0;
+            }
+
+            let state = match graph.states.get(&machine.state) {
+                Some(state) => state,
+                None => continue,
+            };
+            if next.is_some() {
+                if let Some(frame) = state.frame {
+                    sprite.sprite_number = frame;
+                }
+            }
+            // Finished clips are removed from the control set, so starting them again loops them
+            if next.is_some() || state.looping {
+                let rate = match state.rate_from_speed {
+                    true => facts.velocity.x.abs(),
+                    false => 1.0,
+                };
+                AnimationFactory::set_sprite_animation(
+                    &sprite_sets,
+                    &mut sprite_controls,
+                    entity,
+                    machine.state,
+                    AnimationAction::StartAnimationOrSetRate(rate),
+                    None,
+                );
+                AnimationFactory::set_animation(
+                    &sets,
+                    &mut controls,
+                    entity,
+                    machine.state,
+                    AnimationAction::StartAnimationOrSetRate(rate),
+                    None,
+                );
             }
-            // if let Some(anim) = anim {
-            // if anim.animation_type == AnimationId::None {
-            //     sprite.sprite_number += 2;
-            // }
-            // }
         }
-        for (transform, _sprite, scale, velocity, orientation, entity, gravity) in (
+    }
+}
+
+#[derive(SystemDesc)]
+pub struct SpriteUpdateSystem;
+
+impl<'s> System<'s> for SpriteUpdateSystem {
+    type SystemData = (
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Scale>,
+        ReadStorage<'s, Velocity>,
+        ReadStorage<'s, Orientation>,
+        ReadStorage<'s, GravityDirection>,
+    );
+
+    fn run(
+        &mut self,
+        (mut transforms, mut scales, velocities, orientations, gravities): Self::SystemData,
+    ) {
+        for (transform, scale, velocity, orientation, gravity) in (
             &mut transforms,
-            &mut sprites,
             &mut scales,
             &velocities,
             &orientations,
-            &entities,
             (&gravities).maybe(),
         )
             .join()
@@ -470,28 +814,9 @@ impl<'s> System<'s> for SpriteUpdateSystem {
             let grav_vel =
                 gravitationally_de_adapted_velocity(&velocity.0, &GravityDirection(grav_dir));
 
-            // let mut sprite_number = sprite.sprite_number % 2;
             if grav_vel.x != 0.0 {
-                // AnimationFactory::set_animation(
-                //     &sets,
-                //     &mut controls,
-                //     entity,
-                //     AnimationId::Animate,
-                //     AnimationAction::StartAnimationOrSetRate(grav_vel.x.abs()),
-                //     None,
-                // );
-                AnimationFactory::set_sprite_animation(
-                    &sprite_sets,
-                    &mut sprite_controls,
-                    entity,
-                    AnimationId::Animate,
-                    AnimationAction::StartAnimationOrSetRate(grav_vel.x.abs()),
-                    None,
-                );
-
                 let mut cur_scale = &mut scale.0;
                 match orientation.vec.x > 0. {
-                    // match grav_vel.x < 0.0 {
                     false => {
                         cur_scale.x = -1.0 * cur_scale.x.abs();
                     }
@@ -499,58 +824,10 @@ impl<'s> System<'s> for SpriteUpdateSystem {
                         cur_scale.x = cur_scale.x.abs();
                     }
                 };
-            } else {
-                AnimationFactory::set_sprite_animation(
-                    &sprite_sets,
-                    &mut sprite_controls,
-                    entity,
-                    AnimationId::Animate,
-                    AnimationAction::AbortAnimation,
-                    None,
-                );
-                AnimationFactory::set_animation(
-                    &sets,
-                    &mut controls,
-                    entity,
-                    AnimationId::Animate,
-                    AnimationAction::AbortAnimation,
-                    None,
-                );
-                // sprite_number = 0;
             }
-            match grav_vel.y != 0.0 {
-                true => {
-                    // sprite_number += 2;
-                    AnimationFactory::set_animation(
-                        &sets,
-                        &mut controls,
-                        entity,
-                        AnimationId::Animate,
-                        AnimationAction::AbortAnimation,
-                        None,
-                    );
-                }
-                false => {}
-            };
-            // Uncomment me to regain spite animation
-            // sprite.sprite_number = sprite_number;
 
-            // Set the rotation for sticky nerds
-            match grav_dir {
-                CollisionDirection::FromTop => {
-                    transform.set_rotation_z_axis(0.0);
-                }
-                CollisionDirection::FromBottom => {
-                    transform.set_rotation_z_axis(std::f32::consts::PI);
-                }
-                CollisionDirection::FromLeft => {
-                    transform.set_rotation_z_axis(std::f32::consts::FRAC_PI_2);
-                }
-                CollisionDirection::FromRight => {
-                    transform
-                        .set_rotation_z_axis(std::f32::consts::PI + std::f32::consts::FRAC_PI_2);
-                }
-            }
+            // Stand on whatever gravity pulls towards
+            transform.set_rotation_z_axis(grav_dir.rotation());
         }
     }
 }
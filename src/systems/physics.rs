@@ -1,7 +1,8 @@
 use crate::{
     components::physics::{
         Collidee, CollideeDetails, CollisionPoint, CollisionSideOfBlock, Ducking, GravityDirection,
-        Grounded, PlatformCollisionPoints, PlatformCuboid, Position, RTreeEntity, Sticky, Velocity,
+        GravityZone, Grounded, PlatformCollisionPoints, PlatformCuboid, Position, RTreeEntity,
+        Sticky, Velocity,
     },
     events::Events,
     states::pizzatopia::{FRICTION, MAX_FALL_SPEED, MAX_RUN_SPEED, TILE_WIDTH},
@@ -13,9 +14,12 @@ use amethyst::{
 };
 use log::debug;
 
+use crate::components::editor::EditorFlag;
 use crate::components::game::{Block, Gifts, PicksThingsUp, Pickup, Talks};
-use crate::components::game::{CollisionEvent, Damage, Player, Projectile, Reflect, Team};
+use crate::components::game::{CollisionEvent, Damage, Dormant, Player, Projectile, Reflect, Team};
 use crate::components::physics::{ChildTo, MoveIntent, Orientation};
+use crate::factions::{Factions, Relation};
+use crate::ui::UiStack;
 use amethyst::{
     core::{
         bundle::SystemBundle,
@@ -36,11 +40,12 @@ use derivative::Derivative;
 use log::{info, warn};
 use num_traits::identities::Zero;
 use rstar::{RTree, AABB};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::ops::Sub;
 use ultraviolet::{Lerp, Vec2, Vec3};
 
-#[derive(Debug, Copy, Clone, PartialEq, Derivative)]
+#[derive(Debug, Copy, Clone, PartialEq, Derivative, Serialize, Deserialize)]
 #[derivative(Default)]
 pub enum CollisionDirection {
     #[derivative(Default)]
@@ -51,13 +56,34 @@ pub enum CollisionDirection {
 }
 
 impl CollisionDirection {
-    fn is_horizontal(&self) -> bool {
+    pub(crate) fn is_horizontal(&self) -> bool {
         match self {
             CollisionDirection::FromLeft => true,
             CollisionDirection::FromRight => true,
             _ => false,
         }
     }
+
+    // Pointing the other way
+    pub fn flipped(&self) -> CollisionDirection {
+        match self {
+            CollisionDirection::FromTop => CollisionDirection::FromBottom,
+            CollisionDirection::FromBottom => CollisionDirection::FromTop,
+            CollisionDirection::FromLeft => CollisionDirection::FromRight,
+            CollisionDirection::FromRight => CollisionDirection::FromLeft,
+        }
+    }
+
+    // Angle that turns something standing on the ground so it stands against this gravity,
+    // the same turn `gravitationally_adapted_velocity` makes
+    pub fn rotation(&self) -> f32 {
+        match self {
+            CollisionDirection::FromTop => 0.0,
+            CollisionDirection::FromLeft => std::f32::consts::FRAC_PI_2,
+            CollisionDirection::FromBottom => std::f32::consts::PI,
+            CollisionDirection::FromRight => std::f32::consts::PI + std::f32::consts::FRAC_PI_2,
+        }
+    }
 }
 
 #[derive(SystemDesc)]
@@ -69,11 +95,21 @@ impl<'s> System<'s> for ApplyStickySystem {
         ReadStorage<'s, Sticky>,
         ReadStorage<'s, Collidee>,
         WriteStorage<'s, GravityDirection>,
+        ReadStorage<'s, Dormant>,
     );
 
-    fn run(&mut self, (mut velocities, stickies, collidees, mut gravities): Self::SystemData) {
-        for (velocity, sticky, collidee, gravity) in
-            (&mut velocities, &stickies, &collidees, &mut gravities).join()
+    fn run(
+        &mut self,
+        (mut velocities, stickies, collidees, mut gravities, dormants): Self::SystemData,
+    ) {
+        for (velocity, sticky, collidee, gravity, _) in (
+            &mut velocities,
+            &stickies,
+            &collidees,
+            &mut gravities,
+            !&dormants,
+        )
+            .join()
         {
             if sticky.0 {
                 let prev_gravity = gravity.0;
@@ -229,11 +265,12 @@ impl<'s> System<'s> for ApplyVelocitySystem {
     type SystemData = (
         WriteStorage<'s, Velocity>,
         WriteStorage<'s, Position>,
+        ReadStorage<'s, Dormant>,
         Read<'s, Time>,
     );
 
-    fn run(&mut self, (mut velocities, mut positions, time): Self::SystemData) {
-        for (velocity, position) in (&mut velocities, &mut positions).join() {
+    fn run(&mut self, (mut velocities, mut positions, dormants, time): Self::SystemData) {
+        for (velocity, position, _) in (&mut velocities, &mut positions, !&dormants).join() {
             let projection = velocity.project_move(time.time_scale());
             position.0.x += projection.x;
             position.0.y += projection.y;
@@ -366,15 +403,20 @@ impl<'s> System<'s> for ApplyGravitySystem {
         ReadStorage<'s, MoveIntent>,
         ReadStorage<'s, Grounded>,
         ReadStorage<'s, GravityDirection>,
+        ReadStorage<'s, Dormant>,
         Read<'s, Time>,
     );
 
-    fn run(&mut self, (mut velocities, move_intents, grounded, gravities, time): Self::SystemData) {
-        for (velocity, intent, grounded, gravity) in (
+    fn run(
+        &mut self,
+        (mut velocities, move_intents, grounded, gravities, dormants, time): Self::SystemData,
+    ) {
+        for (velocity, intent, grounded, gravity, _) in (
             &mut velocities,
             (&move_intents).maybe(),
             (&grounded).maybe(),
             &gravities,
+            !&dormants,
         )
             .join()
         {
@@ -410,15 +452,87 @@ impl<'s> System<'s> for ApplyGravitySystem {
             velocity.0.x += gravity_vec.x * time.time_scale();
             velocity.0.y += gravity_vec.y * time.time_scale();
 
-            // Limit speed
-            velocity.0.x = f32::min(velocity.0.x, MAX_RUN_SPEED);
-            velocity.0.x = f32::max(velocity.0.x, -MAX_RUN_SPEED);
+            // Limit speed, across and along gravity so falling sideways or up is limited too
+            let mut grav_vel =
+                gravitationally_de_adapted_velocity(&velocity.0, &GravityDirection(grav_dir));
+            grav_vel.x = f32::min(grav_vel.x, MAX_RUN_SPEED);
+            grav_vel.x = f32::max(grav_vel.x, -MAX_RUN_SPEED);
 
-            velocity.0.y = f32::max(velocity.0.y, -MAX_FALL_SPEED);
+            grav_vel.y = f32::max(grav_vel.y, -MAX_FALL_SPEED);
+            velocity.0 = gravitationally_adapted_velocity(&grav_vel, &GravityDirection(grav_dir));
         }
     }
 }
 
+// Gives actors a zone's gravity when they come into it. They keep it after leaving, so a zone
+// pointing the other way is needed to turn them back.
+#[derive(SystemDesc)]
+#[system_desc(name(GravityZoneSystemDesc))]
+pub struct GravityZoneSystem {
+    // Actor and zone pairs that overlapped last frame
+    #[system_desc(skip)]
+    inside: HashSet<(Entity, Entity)>,
+}
+
+impl GravityZoneSystem {
+    pub(crate) fn new() -> Self {
+        Self {
+            inside: HashSet::new(),
+        }
+    }
+}
+
+impl<'s> System<'s> for GravityZoneSystem {
+    type SystemData = (
+        WriteStorage<'s, GravityDirection>,
+        ReadStorage<'s, GravityZone>,
+        ReadStorage<'s, Position>,
+        ReadStorage<'s, PlatformCollisionPoints>,
+        ReadStorage<'s, Dormant>,
+        ReadStorage<'s, EditorFlag>,
+        Entities<'s>,
+    );
+
+    fn run(
+        &mut self,
+        (mut gravities, zones, positions, coll_points, dormants, editor_flags, entities): Self::SystemData,
+    ) {
+        let zones: Vec<(Entity, Vec2, GravityZone)> =
+            (&entities, &zones, &positions, !&editor_flags)
+                .join()
+                .map(|(entity, zone, pos, _)| (entity, pos.0, *zone))
+                .collect();
+
+        let mut inside = HashSet::new();
+        for (entity, gravity, pos, points, _, _) in (
+            &entities,
+            &mut gravities,
+            &positions,
+            &coll_points,
+            !&dormants,
+            !&editor_flags,
+        )
+            .join()
+        {
+            for (zone_entity, zone_pos, zone) in zones.iter() {
+                let overlapping = (pos.0.x - zone_pos.x).abs()
+                    <= points.half_size.x + zone.half_size.x
+                    && (pos.0.y - zone_pos.y).abs() <= points.half_size.y + zone.half_size.y;
+                if !overlapping {
+                    continue;
+                }
+                // Only on entry, so a flip pickup used inside a zone isn't undone straight away
+                if !self.inside.contains(&(entity, *zone_entity)) && gravity.0 != zone.gravity {
+                    debug!("{:?} entered a zone pulling {:?}", entity, zone.gravity);
+                    gravity.0 = zone.gravity;
+                }
+                inside.insert((entity, *zone_entity));
+            }
+        }
+        self.inside = inside;
+    }
+}
+
 #[derive(SystemDesc)]
 pub struct ActorCollisionSystem;
 
@@ -485,9 +599,12 @@ impl<'s> System<'s> for ActorCollisionSystem {
         ReadStorage<'s, Talks>,
         ReadStorage<'s, Player>,
         WriteStorage<'s, Gifts>,
+        ReadStorage<'s, Dormant>,
         Read<'s, Input<StringBindings>>,
         Entities<'s>,
         Write<'s, EventChannel<CollisionEvent>>,
+        Read<'s, UiStack>,
+        Read<'s, Factions>,
     );
 
     fn run(
@@ -505,18 +622,28 @@ impl<'s> System<'s> for ActorCollisionSystem {
             talkers,
             players,
             mut gifters,
+            dormants,
             input,
             entities,
             mut channel,
+            ui_stack,
+            factions,
         ): Self::SystemData,
     ) {
         let mut result = Vec::new();
-        for (ent_pos1, coll_point1, entity1) in (&positions, &coll_points, &entities).join() {
+        // Down is used to pick dialogue choices while one is open
+        let talking = input.axes.single_press(&String::from("vertical")).axis < 0.0
+            && !ui_stack.is_blocking_all_input();
+        for (ent_pos1, coll_point1, entity1, _) in
+            (&positions, &coll_points, &entities, !&dormants).join()
+        {
             let pos1 = Vec2::new(ent_pos1.0.x, ent_pos1.0.y);
             let (top_left1, bottom_right1) =
                 Self::create_corners_with_coll_points_tl_br(&pos1, coll_point1);
 
-            for (ent_pos2, coll_point2, entity2) in (&positions, &coll_points, &entities).join() {
+            for (ent_pos2, coll_point2, entity2, _) in
+                (&positions, &coll_points, &entities, !&dormants).join()
+            {
                 if entity1 == entity2 {
                     continue;
                 }
@@ -531,11 +658,33 @@ impl<'s> System<'s> for ActorCollisionSystem {
                     if team1.is_some() && team2.is_some() {
                         let team1 = team1.unwrap();
                         let team2 = team2.unwrap();
-                        match (team1, team2) {
-                            (Team::GoodGuys, Team::GoodGuys) => {}
-                            (Team::BadGuys, Team::BadGuys) => {}
-                            (Team::Neutral, _) => {}
-                            (_, Team::Neutral) => {
+                        let relation = factions.relation(team1, team2);
+                        // Only friends' projectiles go through shields
+                        if relation != Relation::Friendly && projectiles.get(entity2).is_some() {
+                            // The projectile joins the reflector's team
+                            if reflects.get(entity1).is_some() {
+                                result.push(CollisionEvent::ProjectileReflection(
+                                    entity2.id(),
+                                    *team1,
+                                ));
+                            }
+                            if blocks.get(entity1).is_some() {
+                                result.push(CollisionEvent::ProjectileBlock(entity2.id()));
+                            }
+                        }
+                        match relation {
+                            Relation::Hostile => {
+                                // It's not necessary to check both permutations, the outer loop does this already
+                                if let Some(damage) = damages.get(entity1) {
+                                    result.push(CollisionEvent::EnemyCollision(
+                                        entity2.id(),
+                                        entity1.id(),
+                                        damage.0,
+                                        damage.1,
+                                    ));
+                                }
+                            }
+                            Relation::Friendly | Relation::Neutral => {
                                 let picker = pickers.get(entity1).is_some();
                                 let picked = pickeds.get(entity2).is_some();
                                 if picker && picked {
@@ -545,16 +694,17 @@ impl<'s> System<'s> for ActorCollisionSystem {
                                     ))
                                 }
                                 let talks = talkers.get(entity2);
-                                if input.axes.single_press(&String::from("vertical")).axis < 0.0
-                                    && players.get(entity1).is_some()
-                                {
+                                if talking && players.get(entity1).is_some() {
                                     if let Some(talks) = talks {
                                         result.push(CollisionEvent::Talk(
                                             talks.text.clone(),
                                             entity2.id(),
                                         ));
                                     }
-                                    let gifts = gifters.get_mut(entity2);
+                                    // Speakers with a dialogue give their gifts through its choices
+                                    let gifts = gifters
+                                        .get_mut(entity2)
+                                        .filter(|_| talks.map_or(true, |t| t.dialogue.is_none()));
                                     if let Some(gifts) = gifts {
                                         let heart = gifts.hearts > 0;
                                         let veggie = gifts.veggies > 0;
@@ -572,29 +722,6 @@ impl<'s> System<'s> for ActorCollisionSystem {
                                     }
                                 }
                             }
-                            _ => {
-                                // It's not necessary to check both permutations, the outer loop does this already
-                                if let Some(damage) = damages.get(entity1) {
-                                    result.push(CollisionEvent::EnemyCollision(
-                                        entity2.id(),
-                                        entity1.id(),
-                                        damage.0,
-                                    ));
-                                }
-                                if reflects.get(entity1).is_some()
-                                    && projectiles.get(entity2).is_some()
-                                {
-                                    result.push(CollisionEvent::ProjectileReflection(
-                                        entity2.id(),
-                                        *team1,
-                                    ));
-                                }
-                                if blocks.get(entity1).is_some()
-                                    && projectiles.get(entity2).is_some()
-                                {
-                                    result.push(CollisionEvent::ProjectileBlock(entity2.id()));
-                                }
-                            }
                         }
                     }
                 }
@@ -610,7 +737,9 @@ impl<'s> System<'s> for ActorCollisionSystem {
         let no_collide_when_block: Vec<&CollisionEvent> = result
             .iter()
             .filter(|e| match e {
-                CollisionEvent::EnemyCollision(_, projectile, _) => !block_ids.contains(projectile),
+                CollisionEvent::EnemyCollision(_, projectile, _, _) => {
+                    !block_ids.contains(projectile)
+                }
                 _ => true,
             })
             .collect();
@@ -802,16 +931,23 @@ impl<'s> System<'s> for PlatformCollisionSystem {
         ReadStorage<'s, Position>,
         ReadStorage<'s, PlatformCuboid>,
         ReadStorage<'s, PlatformCollisionPoints>,
+        ReadStorage<'s, Dormant>,
         Read<'s, RTree<RTreeEntity>>,
         Read<'s, Time>,
     );
 
     fn run(
         &mut self,
-        (mut velocities, mut collidees, positions, cuboids, coll_points, rtree, time): Self::SystemData,
+        (mut velocities, mut collidees, positions, cuboids, coll_points, dormants, rtree, time): Self::SystemData,
     ) {
-        for (velocity, collidee, ent_pos, collision_points) in
-            (&mut velocities, &mut collidees, &positions, &coll_points).join()
+        for (velocity, collidee, ent_pos, collision_points, _) in (
+            &mut velocities,
+            &mut collidees,
+            &positions,
+            &coll_points,
+            !&dormants,
+        )
+            .join()
         {
             // Reset collidees here they can be used for the rest of the frame
             std::mem::swap(&mut collidee.prev_horizontal, &mut collidee.horizontal);
@@ -986,14 +1122,19 @@ impl<'s> System<'s> for ApplyCollisionSystem {
         WriteStorage<'s, Collidee>,
         WriteStorage<'s, Grounded>,
         ReadStorage<'s, GravityDirection>,
+        ReadStorage<'s, Dormant>,
     );
 
-    fn run(&mut self, (mut velocities, mut collidees, mut grounded, gravities): Self::SystemData) {
-        for (velocity, collidee, mut grounded, gravity) in (
+    fn run(
+        &mut self,
+        (mut velocities, mut collidees, mut grounded, gravities, dormants): Self::SystemData,
+    ) {
+        for (velocity, collidee, mut grounded, gravity, _) in (
             &mut velocities,
             &mut collidees,
             (&mut grounded).maybe(),
             (&gravities).maybe(),
+            !&dormants,
         )
             .join()
         {
@@ -13,21 +13,21 @@ use crate::components::physics::{MoveIntent, Orientation};
 use crate::{
     animations::{AnimationAction, AnimationFactory, AnimationId},
     components::{
-        entity_builder::entity_builder,
-        game::{Health, Player, Team},
-        graphics::{AnimationCounter, Scale},
-        physics::{Ducking, GravityDirection, Grounded, PlatformCuboid, Position, Velocity},
+        game::{Health, HitStop, Player},
+        physics::{
+            Collidee, Ducking, GravityDirection, Grounded, PlatformCuboid, Position, Velocity,
+        },
     },
-    events::Events,
+    events::{Events, PlayerEvent},
     level::Level,
-    states::pizzatopia::{CAM_HEIGHT, TILE_HEIGHT, TILE_WIDTH},
+    states::pizzatopia::CAM_HEIGHT,
     systems::physics::{gravitationally_adapted_velocity, gravitationally_de_adapted_velocity},
+    ui::UiStack,
 };
 use amethyst::prelude::WorldExt;
 use bami::Input;
 use log::error;
 use num_traits::identities::Zero;
-use std::sync::Arc;
 use ultraviolet::Vec2;
 
 #[derive(SystemDesc)]
@@ -38,7 +38,6 @@ impl<'s> System<'s> for PlayerInputSystem {
         WriteStorage<'s, Velocity>,
         WriteStorage<'s, Orientation>,
         WriteStorage<'s, MoveIntent>,
-        WriteStorage<'s, AnimationCounter>,
         ReadStorage<'s, Position>,
         Read<'s, Input<StringBindings>>,
         ReadStorage<'s, Player>,
@@ -48,8 +47,10 @@ impl<'s> System<'s> for PlayerInputSystem {
         ReadStorage<'s, Ducking>,
         Write<'s, Time>,
         Entities<'s>,
-        ReadStorage<'s, AnimationSet<AnimationId, Transform>>,
-        WriteStorage<'s, AnimationControlSet<AnimationId, Transform>>,
+        ReadStorage<'s, Collidee>,
+        Write<'s, EventChannel<PlayerEvent>>,
+        Read<'s, HitStop>,
+        Read<'s, UiStack>,
     );
 
     fn run(
@@ -58,7 +59,6 @@ impl<'s> System<'s> for PlayerInputSystem {
             mut velocities,
             mut orientations,
             mut move_intents,
-            mut anim,
             positions,
             input,
             players,
@@ -68,11 +68,24 @@ impl<'s> System<'s> for PlayerInputSystem {
             duckings,
             mut time,
             entities,
-            sets,
-            mut controls,
+            collidees,
+            mut player_events,
+            hit_stop,
+            ui_stack,
         ): Self::SystemData,
     ) {
-        for (vel, intent, _pos, _player, health, ground, gravity, ducking, orientation, entity) in (
+        // Dialogue and the like keep the input to themselves, and stop the game until they close
+        let frozen = ui_stack.is_blocking_all_input();
+        let slowing = input.actions.status(&"insert".to_string()).is_down;
+        // The hit stop looks after the time scale until it's over
+        if !hit_stop.active() {
+            match (frozen, slowing) {
+                (true, _) => time.set_time_scale(0.),
+                (false, true) => time.set_time_scale(0.5),
+                (false, false) => time.set_time_scale(1.),
+            }
+        }
+        for (vel, intent, pos, _player, health, ground, gravity, ducking, orientation, entity) in (
             &mut velocities,
             &mut move_intents,
             &positions,
@@ -86,7 +99,11 @@ impl<'s> System<'s> for PlayerInputSystem {
         )
             .join()
         {
-            if health.0 == 0 {
+            if !health.alive() {
+                continue;
+            }
+            if frozen {
+                intent.vec = Vec2::zero();
                 continue;
             }
 
@@ -101,66 +118,6 @@ impl<'s> System<'s> for PlayerInputSystem {
 
             let jumping = input.actions.status(&"accept".to_string()).is_down;
             let release = input.actions.just_released(&"accept".to_string());
-            let slowing = input.actions.status(&"insert".to_string()).is_down;
-            let attacking = input.actions.single_press(&"attack".to_string()).is_down;
-
-            if attacking {
-                let animation = AnimationCounter::new(
-                    0.15,
-                    AnimationId::None,
-                    Arc::new(move |world| {
-                        // let pos: Position;
-                        // let vel: Velocity;
-                        let size: Vec2;
-                        let ducking: bool;
-                        {
-                            let size_st = world.read_storage::<Scale>();
-                            let duck_st = world.read_storage::<Ducking>();
-                            ducking = duck_st.contains(entity);
-                            let size_opt = size_st.get(entity);
-                            if size_opt.is_none() {
-                                return;
-                            }
-                            size = size_opt.unwrap().0;
-                        }
-                        let width = size.x.abs() * TILE_WIDTH;
-                        let height = size.y.abs() * TILE_HEIGHT;
-                        let offset_x = width / 2.;
-                        let offset_y = height
-                            * 0.25
-                            * match ducking {
-                                true => -1.,
-                                false => 1.0,
-                            };
-                        // Multiply height by one quarter to go from half height to upper body
-                        let offset = Vec2::new(offset_x, offset_y);
-                        // let p = pos.0.to_vec2().add(&offset);
-                        let p = offset;
-                        let s = Vec2::new(width, TILE_HEIGHT / 4.0);
-                        let t = Team::GoodGuys;
-                        entity_builder::initialize_damage_box(
-                            world,
-                            Some(entity),
-                            &p.clone(),
-                            &s.clone(),
-                            &t.clone(),
-                        );
-                    }),
-                );
-                if !anim.contains(entity) {
-                    anim.insert(entity, animation)
-                        .expect("Failed to insert AnimationCounter for attack");
-
-                    AnimationFactory::set_animation(
-                        &sets,
-                        &mut controls,
-                        entity,
-                        AnimationId::Rotate,
-                        AnimationAction::StartAnimationOrSetRate(1.0),
-                        None,
-                    );
-                }
-            }
 
             if !intent.vec.x.is_zero() && orientation.is_some() {
                 orientation.unwrap().vec.x = match intent.vec.x > 0. {
@@ -169,12 +126,6 @@ impl<'s> System<'s> for PlayerInputSystem {
                 };
             }
 
-            if slowing {
-                time.set_time_scale(0.5);
-            } else {
-                time.set_time_scale(1.);
-            }
-
             let ground: Option<&Grounded> = ground;
             let default_ground = Grounded(false);
             let on_ground = ground.unwrap_or(&default_ground);
@@ -185,11 +136,19 @@ impl<'s> System<'s> for PlayerInputSystem {
                 grav_vel = gravitationally_de_adapted_velocity(&grav_vel, &grav);
             }
 
+            // Grounded this frame but not colliding vertically the one before means we just landed
+            if let Some(collidee) = collidees.get(entity) {
+                if on_ground && collidee.prev_vertical.is_none() {
+                    player_events.single_write(PlayerEvent::Land(pos.0));
+                }
+            }
+
             // Do the move logic
             if jumping {
                 if on_ground {
                     let jump_velocity = 13.0;
                     grav_vel.y += jump_velocity;
+                    player_events.single_write(PlayerEvent::Jump(pos.0));
                 }
             }
             // letting go of `up` will stop your jump
@@ -0,0 +1,262 @@
+use crate::{
+    components::{
+        editor::TileLayer,
+        graphics::{ChunkTile, TileChunk},
+    },
+    states::{loading::get_sprite_sheet, pizzatopia::DEPTH_TILES},
+};
+use amethyst::{
+    core::{
+        math::{Point3, Vector3},
+        transform::Transform,
+    },
+    ecs::prelude::Entity,
+    prelude::*,
+    tiles::{FlatEncoder, TileMap},
+};
+use std::collections::BTreeMap;
+use ultraviolet::Vec2;
+
+// Width and height of a chunk, in tiles
+pub const CHUNK_SIZE: u32 = 16;
+
+pub type ChunkMap = TileMap<ChunkTile, FlatEncoder>;
+
+// A tile map can only use one sprite sheet and one tile size, so those are part of the key
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct ChunkKey {
+    layer: usize,
+    sheet: String,
+    tile_size: (u32, u32),
+    chunk: (i32, i32),
+}
+
+// Every chunk entity in the level. Recreated whenever a level is loaded.
+#[derive(Default)]
+pub struct TileChunks {
+    chunks: BTreeMap<ChunkKey, Entity>,
+}
+
+// Place (`Some`) or clear (`None`) the sprite of the static tile at `pos`,
+// creating the chunk it falls into if necessary
+pub fn set_chunk_tile(
+    world: &mut World,
+    pos: Vec2,
+    size: Vec2,
+    layer: TileLayer,
+    sheet: &str,
+    sprite: Option<usize>,
+) {
+    let (chunk, local) = locate(pos, size);
+    let key = ChunkKey {
+        layer: layer as usize,
+        sheet: String::from(sheet),
+        tile_size: (size.x as u32, size.y as u32),
+        chunk,
+    };
+
+    let existing = world
+        .read_resource::<TileChunks>()
+        .chunks
+        .get(&key)
+        .cloned();
+    let entity = match (existing, sprite) {
+        (Some(entity), _) => entity,
+        // Clearing a tile in a chunk that doesn't exist is a no-op
+        (None, None) => return,
+        (None, Some(_)) => create_chunk(world, &key, size, layer, sheet),
+    };
+
+    if let Some(tile) = world
+        .write_storage::<ChunkMap>()
+        .get_mut(entity)
+        .and_then(|map| map.get_mut(&local))
+    {
+        tile.0 = sprite;
+    }
+}
+
+// The chunk the tile at `pos` falls into, and its cell within that chunk's tile map
+fn locate(pos: Vec2, size: Vec2) -> ((i32, i32), Point3<u32>) {
+    let cell = (
+        (pos.x / size.x).round() as i32,
+        (pos.y / size.y).round() as i32,
+    );
+    let chunk_size = CHUNK_SIZE as i32;
+    let chunk = (cell.0.div_euclid(chunk_size), cell.1.div_euclid(chunk_size));
+    // Tile map rows go from the top down
+    let local = Point3::new(
+        cell.0.rem_euclid(chunk_size) as u32,
+        CHUNK_SIZE - 1 - cell.1.rem_euclid(chunk_size) as u32,
+        0,
+    );
+    (chunk, local)
+}
+
+fn create_chunk(
+    world: &mut World,
+    key: &ChunkKey,
+    size: Vec2,
+    layer: TileLayer,
+    sheet: &str,
+) -> Entity {
+    let chunk_size = CHUNK_SIZE as f32;
+    let half_size = Vec2::new(size.x * chunk_size / 2.0, size.y * chunk_size / 2.0);
+    // Tile positions are their centers, so the chunk starts half a tile before its first tile
+    let center = Vec2::new(
+        key.chunk.0 as f32 * size.x * chunk_size - size.x / 2.0 + half_size.x,
+        key.chunk.1 as f32 * size.y * chunk_size - size.y / 2.0 + half_size.y,
+    );
+
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(center.x, center.y, DEPTH_TILES + layer.to_z_offset());
+
+    let map = ChunkMap::new(
+        Vector3::new(CHUNK_SIZE, CHUNK_SIZE, 1),
+        Vector3::new(key.tile_size.0, key.tile_size.1, 1),
+        get_sprite_sheet(world, sheet),
+    );
+    let entity = world
+        .create_entity()
+        .with(map)
+        .with(transform)
+        .with(TileChunk { center, half_size })
+        .build();
+    world
+        .write_resource::<TileChunks>()
+        .chunks
+        .insert(key.clone(), entity);
+    entity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::{assets::Handle, renderer::SpriteSheet};
+
+    const TILE: Vec2 = Vec2 { x: 128.0, y: 128.0 };
+
+    fn at(x: i32, y: i32) -> Vec2 {
+        Vec2::new(x as f32 * TILE.x, y as f32 * TILE.y)
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<ChunkMap>();
+        world.register::<TileChunk>();
+        world.register::<Transform>();
+        world.insert(TileChunks::default());
+        world.insert(BTreeMap::<String, Handle<SpriteSheet>>::new());
+        world
+    }
+
+    fn sprite_at(world: &World, pos: Vec2, sheet: &str) -> Option<usize> {
+        let (chunk, local) = locate(pos, TILE);
+        let key = ChunkKey {
+            layer: TileLayer::Middle as usize,
+            sheet: String::from(sheet),
+            tile_size: (TILE.x as u32, TILE.y as u32),
+            chunk,
+        };
+        let entity = *world.read_resource::<TileChunks>().chunks.get(&key)?;
+        let maps = world.read_storage::<ChunkMap>();
+        maps.get(entity)?.get(&local)?.0
+    }
+
+    fn chunk_count(world: &World) -> usize {
+        world.read_resource::<TileChunks>().chunks.len()
+    }
+
+    #[test]
+    fn tiles_are_located_in_their_chunk() {
+        assert_eq!(locate(at(0, 0), TILE), ((0, 0), Point3::new(0, 15, 0)));
+        assert_eq!(locate(at(15, 15), TILE), ((0, 0), Point3::new(15, 0, 0)));
+        assert_eq!(locate(at(16, 0), TILE), ((1, 0), Point3::new(0, 15, 0)));
+        assert_eq!(locate(at(-1, -1), TILE), ((-1, -1), Point3::new(15, 0, 0)));
+        assert_eq!(locate(at(-16, 17), TILE), ((-1, 1), Point3::new(0, 14, 0)));
+    }
+
+    #[test]
+    fn setting_and_clearing_a_tile() {
+        let mut world = world();
+        set_chunk_tile(
+            &mut world,
+            at(3, 4),
+            TILE,
+            TileLayer::Middle,
+            "tiles",
+            Some(7),
+        );
+        assert_eq!(sprite_at(&world, at(3, 4), "tiles"), Some(7));
+        assert_eq!(sprite_at(&world, at(4, 4), "tiles"), None);
+
+        set_chunk_tile(&mut world, at(3, 4), TILE, TileLayer::Middle, "tiles", None);
+        assert_eq!(sprite_at(&world, at(3, 4), "tiles"), None);
+        // The chunk stays around for the next tile placed in it
+        assert_eq!(chunk_count(&world), 1);
+    }
+
+    #[test]
+    fn tiles_share_a_chunk_until_they_cross_its_edge() {
+        let mut world = world();
+        set_chunk_tile(
+            &mut world,
+            at(0, 0),
+            TILE,
+            TileLayer::Middle,
+            "tiles",
+            Some(1),
+        );
+        set_chunk_tile(
+            &mut world,
+            at(15, 15),
+            TILE,
+            TileLayer::Middle,
+            "tiles",
+            Some(2),
+        );
+        assert_eq!(chunk_count(&world), 1);
+        set_chunk_tile(
+            &mut world,
+            at(16, 0),
+            TILE,
+            TileLayer::Middle,
+            "tiles",
+            Some(3),
+        );
+        assert_eq!(chunk_count(&world), 2);
+        assert_eq!(sprite_at(&world, at(15, 15), "tiles"), Some(2));
+        assert_eq!(sprite_at(&world, at(16, 0), "tiles"), Some(3));
+    }
+
+    #[test]
+    fn each_sheet_gets_its_own_chunk() {
+        let mut world = world();
+        set_chunk_tile(
+            &mut world,
+            at(0, 0),
+            TILE,
+            TileLayer::Middle,
+            "tiles",
+            Some(1),
+        );
+        set_chunk_tile(
+            &mut world,
+            at(1, 0),
+            TILE,
+            TileLayer::Middle,
+            "snap",
+            Some(2),
+        );
+        assert_eq!(chunk_count(&world), 2);
+        assert_eq!(sprite_at(&world, at(1, 0), "tiles"), None);
+        assert_eq!(sprite_at(&world, at(1, 0), "snap"), Some(2));
+    }
+
+    #[test]
+    fn clearing_where_there_is_no_chunk_creates_none() {
+        let mut world = world();
+        set_chunk_tile(&mut world, at(0, 0), TILE, TileLayer::Middle, "tiles", None);
+        assert_eq!(chunk_count(&world), 0);
+    }
+}
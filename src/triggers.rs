@@ -0,0 +1,185 @@
+use crate::{
+    components::{
+        game::{Pickup, Player, SerializedObject},
+        physics::Position,
+    },
+    events::Events,
+    flags::{FlagCondition, Flags},
+    mechanisms::{swap_solids, DoorChange},
+};
+use amethyst::{
+    core::shrev::EventChannel,
+    derive::SystemDesc,
+    ecs::{
+        prelude::{Component, DenseVecStorage, NullStorage},
+        Entities, Join, Read, ReadStorage, System, SystemData, World, WorldExt, Write,
+        WriteStorage,
+    },
+    prelude::Builder,
+};
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec2;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TriggerAction {
+    SetFlag(String),
+    ClearFlag(String),
+    SetValue(String, i32),
+    AddValue(String, i32),
+    // Creates the object without an editor counterpart, it goes away when the level resets
+    Spawn(SerializedObject),
+    // Also goes away when the level resets
+    SpawnPickup(Pickup, Vec2),
+    // Name of a dialogue in the dialogue file
+    ShowDialogue(String),
+    // Moves the player
+    Warp(Vec2),
+    // Every door with the link
+    ChangeDoors(u32, DoorChange),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TriggerRegion {
+    // Center of the region
+    pub pos: Vec2,
+    pub size: Vec2,
+}
+
+impl TriggerRegion {
+    pub fn contains(&self, point: Vec2) -> bool {
+        (point.x - self.pos.x).abs() <= self.size.x / 2.0
+            && (point.y - self.pos.y).abs() <= self.size.y / 2.0
+    }
+}
+
+// Runs its actions when the player is in the region while the condition holds. Either can be
+// left out: without a region it waits on the condition alone, without either it runs when the
+// level starts.
+#[derive(Clone, Debug, Serialize, Deserialize, Derivative)]
+#[derivative(Default)]
+#[serde(default)]
+pub struct TriggerDef {
+    pub region: Option<TriggerRegion>,
+    pub condition: Option<FlagCondition>,
+    pub actions: Vec<TriggerAction>,
+    // Fire only the first time, rather than every time the player comes back in
+    #[derivative(Default(value = "true"))]
+    pub once: bool,
+}
+
+// The triggers of the currently-loaded level, kept around for resetting and saving
+#[derive(Clone, Debug, Default)]
+pub struct LevelTriggers(pub Vec<TriggerDef>);
+
+pub struct Trigger {
+    pub def: TriggerDef,
+    pub fired: bool,
+    // Whether the trigger's requirements held last frame, it fires when they start holding
+    pub was_active: bool,
+}
+
+impl Component for Trigger {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Things made by triggers, removed when the level resets
+#[derive(Default)]
+pub struct Spawned;
+
+impl Component for Spawned {
+    type Storage = NullStorage<Self>;
+}
+
+// Replaces the trigger entities and anything they spawned with a fresh copy of `LevelTriggers`
+pub fn initialize_triggers(world: &mut World) {
+    let mut to_remove = Vec::new();
+    for (entity, _) in (&world.entities(), &world.read_storage::<Trigger>()).join() {
+        to_remove.push(entity);
+    }
+    for (entity, _) in (&world.entities(), &world.read_storage::<Spawned>()).join() {
+        to_remove.push(entity);
+    }
+    swap_solids(world, &to_remove, &[]);
+    world
+        .delete_entities(to_remove.as_slice())
+        .expect("Failed to delete old triggers");
+
+    let defs = world.read_resource::<LevelTriggers>().0.clone();
+    for def in defs {
+        world
+            .create_entity()
+            .with(Trigger {
+                def,
+                fired: false,
+                was_active: false,
+            })
+            .build();
+    }
+}
+
+#[derive(SystemDesc)]
+pub struct TriggerSystem;
+
+impl<'s> System<'s> for TriggerSystem {
+    type SystemData = (
+        WriteStorage<'s, Trigger>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Position>,
+        Write<'s, Flags>,
+        Write<'s, EventChannel<Events>>,
+    );
+
+    fn run(&mut self, (mut triggers, players, positions, mut flags, mut events): Self::SystemData) {
+        let player_positions: Vec<Vec2> = (&players, &positions)
+            .join()
+            .filter(|(player, _)| player.0)
+            .map(|(_, pos)| pos.0)
+            .collect();
+
+        for trigger in (&mut triggers).join() {
+            if trigger.fired && trigger.def.once {
+                continue;
+            }
+            let inside = trigger.def.region.as_ref().map_or(true, |region| {
+                player_positions.iter().any(|pos| region.contains(*pos))
+            });
+            let active = inside
+                && trigger
+                    .def
+                    .condition
+                    .as_ref()
+                    .map_or(true, |condition| condition.holds(&flags));
+            let fire = active && !trigger.was_active;
+            trigger.was_active = active;
+            if !fire {
+                continue;
+            }
+            trigger.fired = true;
+
+            for action in trigger.def.actions.iter() {
+                match action {
+                    TriggerAction::SetFlag(flag) => flags.set(flag),
+                    TriggerAction::ClearFlag(flag) => flags.clear(flag),
+                    TriggerAction::SetValue(name, value) => flags.set_value(name, *value),
+                    TriggerAction::AddValue(name, amount) => flags.add_value(name, *amount),
+                    TriggerAction::Spawn(serialized_object) => {
+                        events.single_write(Events::SpawnGameObject(serialized_object.clone()));
+                    }
+                    TriggerAction::SpawnPickup(kind, pos) => {
+                        events.single_write(Events::SpawnPickup(kind.clone(), *pos));
+                    }
+                    TriggerAction::ShowDialogue(name) => {
+                        events.single_write(Events::ShowDialogue(name.clone()));
+                    }
+                    TriggerAction::Warp(pos) => {
+                        events.single_write(Events::Warp(*pos));
+                    }
+                    TriggerAction::ChangeDoors(link, change) => {
+                        events.single_write(Events::ChangeDoors(*link, *change));
+                    }
+                }
+            }
+        }
+    }
+}
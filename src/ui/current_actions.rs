@@ -30,7 +30,8 @@ use derivative::Derivative;
 use std::collections::BTreeMap;
 
 use crate::{
-    components::graphics::SpriteSheetType, events::Events, ui::file_picker::FilePickerFilename,
+    components::graphics::SPRITE_SHEET_UI, events::Events, states::loading::get_sprite_sheet,
+    ui::file_picker::FilePickerFilename,
 };
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
@@ -45,6 +46,9 @@ pub enum EditorActions {
     DeleteEditGameObject,
     InsertModePlayer,
     InsertModeTile,
+    InsertModeDoor,
+    InsertModeSwitch,
+    InsertModeGravityZone,
     SaveLevel,
     LoadLevel,
     ChooseSaveFile,
@@ -118,6 +122,9 @@ impl CurrentActionsUi {
                 | EditorActions::EnterEditGameObject
                 | EditorActions::InsertModeTile
                 | EditorActions::InsertModePlayer
+                | EditorActions::InsertModeDoor
+                | EditorActions::InsertModeSwitch
+                | EditorActions::InsertModeGravityZone
                 | EditorActions::DeleteEditGameObject
                 | EditorActions::PlaceEditGameObject
                 | EditorActions::EnterPlayMode => {
@@ -316,7 +323,10 @@ impl CurrentActionsUi {
                     }
                     _ => {}
                 },
-                EditorActions::InsertModePlayer => match state {
+                EditorActions::InsertModePlayer
+                | EditorActions::InsertModeDoor
+                | EditorActions::InsertModeSwitch
+                | EditorActions::InsertModeGravityZone => match state {
                     CursorState::InsertMode => {
                         self.show(action, true);
                     }
@@ -399,6 +409,9 @@ impl CurrentActionsUi {
                 EditorActions::EnterEditGameObject => ("X", "Edit Object"),
                 EditorActions::InsertModeTile => ("1", "Switch To Tiles"),
                 EditorActions::InsertModePlayer => ("2", "Switch To Players"),
+                EditorActions::InsertModeDoor => ("3", "Switch To Doors"),
+                EditorActions::InsertModeSwitch => ("4", "Switch To Switches"),
+                EditorActions::InsertModeGravityZone => ("5", "Switch To Gravity Zones"),
                 EditorActions::EnterPlayMode => ("LCTRL", "Play Level"),
                 EditorActions::SaveLevel => ("INSERT", "Save Level"),
                 EditorActions::LoadLevel => ("PgDn", "Load Level"),
@@ -472,19 +485,19 @@ impl CurrentActionsUi {
         bg: bool,
     ) -> Entity {
         // Assign the sprite
-        let sprite_sheet = world.read_resource::<BTreeMap<u8, Handle<SpriteSheet>>>()
-            [&(SpriteSheetType::Ui as u8)]
-            .clone();
-        let sprite_render = SpriteRender {
-            sprite_sheet: sprite_sheet.clone(),
-            sprite_number: 0,
+        let sprite_sheet = match bg {
+            true => get_sprite_sheet(world, SPRITE_SHEET_UI),
+            false => None,
         };
-        let sprite_render = UiImage::Sprite(sprite_render);
         let mut entity = world.create_entity().with(transform).with(text);
-        if bg {
-            entity = entity.with(sprite_render);
-        } else {
-            entity = entity.with(UiImage::SolidColor(with_transparent(COLOR_GRAY, 0.05)))
+        match sprite_sheet {
+            Some(sprite_sheet) => {
+                entity = entity.with(UiImage::Sprite(SpriteRender {
+                    sprite_sheet,
+                    sprite_number: 0,
+                }))
+            }
+            None => entity = entity.with(UiImage::SolidColor(with_transparent(COLOR_GRAY, 0.05))),
         }
 
         entity.build()
@@ -0,0 +1,373 @@
+use crate::{
+    components::game::{CollisionEvent, Gifts, Talks},
+    dialogue::{DialogueAction, DialogueNode, DialogueTree, Dialogues},
+    flags::Flags,
+    ui::{with_transparent, UiComponent, UiStack, COLOR_BLACK, COLOR_GOLD, COLOR_WHITE},
+};
+use amethyst::{
+    assets::Handle,
+    core::{shrev::EventChannel, timing::Time},
+    ecs::prelude::Entity,
+    input::StringBindings,
+    prelude::{Builder, World, WorldExt},
+    ui::{Anchor, FontAsset, LineMode, UiEvent, UiImage, UiText, UiTransform},
+};
+use bami::Input;
+use log::{error, warn};
+
+// Characters revealed per second while a line is being typed out
+const CHARS_PER_SECOND: f32 = 40.0;
+const MAX_CHOICES: usize = 4;
+
+const BOX_WIDTH: f32 = 900.0;
+const BOX_HEIGHT: f32 = 220.0;
+const BOX_Y: f32 = 140.0;
+const MARGIN: f32 = 20.0;
+const SPEAKER_FONT_SIZE: f32 = 26.0;
+const TEXT_FONT_SIZE: f32 = 22.0;
+const CHOICE_HEIGHT: f32 = 28.0;
+
+// A dialogue box along the bottom of the screen. Holds onto the player's input until it closes.
+pub struct DialogueUi {
+    panel: Entity,
+    speaker_label: Entity,
+    text_label: Entity,
+    choice_labels: Vec<Entity>,
+    tree: DialogueTree,
+    // Whoever is talking, for gifts
+    speaker: Option<Entity>,
+    node: DialogueNode,
+    line: usize,
+    // Characters of the current line shown so far
+    revealed: f32,
+    // Indices into the node's choices that the flags allow
+    choices: Vec<usize>,
+    selected: usize,
+    should_destroy: bool,
+}
+
+impl UiComponent for DialogueUi {
+    fn entities_to_remove(&self, mut to_remove: Vec<Entity>) -> Vec<Entity> {
+        to_remove.push(self.panel);
+        to_remove.push(self.speaker_label);
+        to_remove.push(self.text_label);
+        to_remove.extend(self.choice_labels.iter().cloned());
+        to_remove
+    }
+
+    fn update(&mut self, world: &World) {
+        self.handle_input(world);
+        if self.should_destroy {
+            return;
+        }
+        self.revealed += CHARS_PER_SECOND * world.read_resource::<Time>().delta_real_seconds();
+        self.update_ui(world);
+    }
+
+    fn handle_ui_events(&mut self, _world: &World, _event: UiEvent) {}
+
+    fn blocks_all_other_input(&self) -> bool {
+        true
+    }
+
+    fn should_destroy(&self) -> bool {
+        self.should_destroy
+    }
+}
+
+impl DialogueUi {
+    // Starts `speaker`'s dialogue
+    pub fn talk_to(world: &mut World, speaker: Entity) {
+        let talks = match world.read_storage::<Talks>().get(speaker) {
+            Some(talks) => talks.clone(),
+            None => return,
+        };
+        let tree = match &talks.dialogue {
+            Some(name) => match world.read_resource::<Dialogues>().0.get(name) {
+                Some(tree) => tree.clone(),
+                None => {
+                    error!("Unknown dialogue {:?}", name);
+                    DialogueTree::line("", &talks.text)
+                }
+            },
+            None => DialogueTree::line("", &talks.text),
+        };
+        Self::open(world, tree, Some(speaker));
+    }
+
+    // Starts a dialogue by name, with nobody around to hand out gifts
+    pub fn open_named(world: &mut World, name: &str) {
+        let tree = world.read_resource::<Dialogues>().0.get(name).cloned();
+        match tree {
+            Some(tree) => Self::open(world, tree, None),
+            None => error!("Unknown dialogue {:?}", name),
+        }
+    }
+
+    // Unless something is already holding onto the input
+    pub fn open(world: &mut World, tree: DialogueTree, speaker: Option<Entity>) {
+        if world.read_resource::<UiStack>().is_blocking_all_input() {
+            return;
+        }
+
+        let font = (*world.read_resource::<Handle<FontAsset>>()).clone();
+        let panel = world
+            .create_entity()
+            .with(create_ui_transform(
+                String::from("dialogue_panel"),
+                0.,
+                BOX_Y,
+                BOX_WIDTH,
+                BOX_HEIGHT,
+            ))
+            .with(UiImage::SolidColor(with_transparent(COLOR_BLACK, 0.85)))
+            .build();
+        let top = BOX_Y + BOX_HEIGHT / 2.0 - MARGIN;
+        let width = BOX_WIDTH - MARGIN * 2.0;
+        let speaker_label = create_label(
+            world,
+            font.clone(),
+            String::from("dialogue_speaker"),
+            top - SPEAKER_FONT_SIZE / 2.0,
+            width,
+            SPEAKER_FONT_SIZE,
+            SPEAKER_FONT_SIZE,
+            COLOR_GOLD,
+        );
+        let text_height = BOX_HEIGHT - MARGIN * 3.0 - SPEAKER_FONT_SIZE;
+        let text_label = create_label(
+            world,
+            font.clone(),
+            String::from("dialogue_text"),
+            top - SPEAKER_FONT_SIZE - MARGIN / 2.0 - text_height / 2.0,
+            width,
+            text_height,
+            TEXT_FONT_SIZE,
+            COLOR_WHITE,
+        );
+        if let Some(text) = world.write_storage::<UiText>().get_mut(text_label) {
+            text.line_mode = LineMode::Wrap;
+            text.align = Anchor::TopLeft;
+        }
+        // Stacked upwards from the bottom of the box, over the text
+        let choice_labels = (0..MAX_CHOICES)
+            .map(|i| {
+                let y = BOX_Y - BOX_HEIGHT / 2.0
+                    + MARGIN
+                    + CHOICE_HEIGHT * (MAX_CHOICES - 1 - i) as f32;
+                create_label(
+                    world,
+                    font.clone(),
+                    format!("dialogue_choice{}", i),
+                    y,
+                    width,
+                    CHOICE_HEIGHT,
+                    TEXT_FONT_SIZE,
+                    COLOR_WHITE,
+                )
+            })
+            .collect();
+
+        let mut dialogue = DialogueUi {
+            panel,
+            speaker_label,
+            text_label,
+            choice_labels,
+            tree,
+            speaker,
+            node: DialogueNode::default(),
+            line: 0,
+            revealed: 0.0,
+            choices: Vec::new(),
+            selected: 0,
+            should_destroy: false,
+        };
+        let start = dialogue.tree.start.clone();
+        dialogue.go_to(world, Some(start));
+        dialogue.update_ui(world);
+        world
+            .write_resource::<UiStack>()
+            .stack
+            .push(Box::new(dialogue));
+    }
+
+    fn current_line(&self) -> &str {
+        self.node
+            .lines
+            .get(self.line)
+            .map_or("", |line| line.as_str())
+    }
+
+    fn line_finished(&self) -> bool {
+        self.revealed as usize >= self.current_line().chars().count()
+    }
+
+    fn last_line(&self) -> bool {
+        self.line + 1 >= self.node.lines.len()
+    }
+
+    fn showing_choices(&self) -> bool {
+        self.last_line() && self.line_finished() && !self.choices.is_empty()
+    }
+
+    fn go_to(&mut self, world: &World, node: Option<String>) {
+        let node = match node {
+            Some(node) => node,
+            None => {
+                self.should_destroy = true;
+                return;
+            }
+        };
+        self.node = match self.tree.nodes.get(&node) {
+            Some(node) => node.clone(),
+            None => {
+                error!("Dialogue has no node {:?}", node);
+                self.should_destroy = true;
+                return;
+            }
+        };
+        let flags = world.read_resource::<Flags>();
+        self.choices = (0..self.node.choices.len())
+            .filter(|i| self.node.choices[*i].available(&flags))
+            .collect();
+        self.line = 0;
+        self.revealed = 0.0;
+        self.selected = 0;
+    }
+
+    fn handle_input(&mut self, world: &World) {
+        let input = world.read_resource::<Input<StringBindings>>();
+        if input.actions.single_press(&"cancel".to_string()).is_down {
+            self.should_destroy = true;
+            return;
+        }
+
+        if self.showing_choices() {
+            let vertical = input.axes.single_press(&"vertical".to_string()).axis;
+            if vertical < 0.0 {
+                self.selected = (self.selected + 1) % self.choices.len();
+            } else if vertical > 0.0 {
+                self.selected = (self.selected + self.choices.len() - 1) % self.choices.len();
+            }
+        }
+
+        if !input.actions.single_press(&"accept".to_string()).is_down {
+            return;
+        }
+        drop(input);
+        if !self.line_finished() {
+            // Skip the rest of the typing
+            self.revealed = self.current_line().chars().count() as f32;
+        } else if !self.last_line() {
+            self.line += 1;
+            self.revealed = 0.0;
+        } else if self.showing_choices() {
+            let choice = self.node.choices[self.choices[self.selected]].clone();
+            for action in choice.actions.iter() {
+                self.perform(world, action);
+            }
+            self.go_to(world, choice.next);
+        } else {
+            let next = self.node.next.clone();
+            self.go_to(world, next);
+        }
+    }
+
+    fn perform(&self, world: &World, action: &DialogueAction) {
+        match action {
+            DialogueAction::Gift => {
+                let speaker = match self.speaker {
+                    Some(speaker) => speaker,
+                    None => {
+                        warn!("Nobody to give a gift in this dialogue");
+                        return;
+                    }
+                };
+                if let Some(gifts) = world.write_storage::<Gifts>().get_mut(speaker) {
+                    let heart = gifts.hearts > 0;
+                    let veggie = gifts.veggies > 0;
+                    if heart {
+                        gifts.hearts -= 1;
+                    }
+                    if veggie {
+                        gifts.veggies -= 1;
+                    }
+                    world
+                        .write_resource::<EventChannel<CollisionEvent>>()
+                        .single_write(CollisionEvent::Gift(heart, veggie, speaker.id()));
+                }
+            }
+            DialogueAction::SetFlag(flag) => world.write_resource::<Flags>().set(flag),
+            DialogueAction::ClearFlag(flag) => world.write_resource::<Flags>().clear(flag),
+        }
+    }
+
+    fn update_ui(&self, world: &World) {
+        let mut texts = world.write_storage::<UiText>();
+        if let Some(text) = texts.get_mut(self.speaker_label) {
+            text.text = self.tree.speaker.clone();
+        }
+        if let Some(text) = texts.get_mut(self.text_label) {
+            text.text = self
+                .current_line()
+                .chars()
+                .take(self.revealed as usize)
+                .collect();
+        }
+        let showing_choices = self.showing_choices();
+        for (i, entity) in self.choice_labels.iter().enumerate() {
+            if let Some(text) = texts.get_mut(*entity) {
+                let choice = self
+                    .choices
+                    .get(i)
+                    .filter(|_| showing_choices)
+                    .map(|index| &self.node.choices[*index]);
+                match choice {
+                    Some(choice) => match i == self.selected {
+                        true => {
+                            text.text = format!("> {}", choice.text);
+                            text.color = COLOR_GOLD;
+                        }
+                        false => {
+                            text.text = format!("  {}", choice.text);
+                            text.color = COLOR_WHITE;
+                        }
+                    },
+                    None => text.text = String::new(),
+                }
+            }
+        }
+    }
+}
+
+fn create_ui_transform(id: String, x: f32, y: f32, width: f32, height: f32) -> UiTransform {
+    UiTransform::new(
+        id,
+        Anchor::BottomMiddle,
+        Anchor::Middle,
+        x,
+        y,
+        2.,
+        width,
+        height,
+    )
+}
+
+fn create_label(
+    world: &mut World,
+    font: Handle<FontAsset>,
+    id: String,
+    y: f32,
+    width: f32,
+    height: f32,
+    font_size: f32,
+    color: [f32; 4],
+) -> Entity {
+    let mut text = UiText::new(font, String::new(), color, font_size);
+    text.align = Anchor::MiddleLeft;
+    world
+        .create_entity()
+        .with(create_ui_transform(id, 0., y, width, height))
+        .with(text)
+        .build()
+}
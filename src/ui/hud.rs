@@ -0,0 +1,309 @@
+use crate::{
+    components::{
+        game::{Health, PicksThingsUp, Player},
+        graphics::SPRITE_SHEET_UI,
+    },
+    states::loading::get_sprite_sheet,
+    ui::{COLOR_GOLD, COLOR_WHITE},
+    utils::MaybeWith,
+};
+use amethyst::{
+    assets::Handle,
+    core::{timing::Time, HiddenPropagate},
+    derive::SystemDesc,
+    ecs::{
+        prelude::Entity, Join, Read, ReadStorage, System, SystemData, World, WorldExt, Write,
+        WriteStorage,
+    },
+    prelude::Builder,
+    renderer::SpriteRender,
+    ui::{Anchor, FontAsset, UiImage, UiText, UiTransform},
+};
+
+// Sprites in the ui sheet
+const SPRITE_HEART_FULL: usize = 3;
+const SPRITE_HEART_EMPTY: usize = 4;
+const SPRITE_VEGGIE: usize = 5;
+
+// Hearts shown even when the player has less health than this
+const MIN_HEARTS: usize = 5;
+const MAX_HEARTS: usize = 10;
+
+const ICON_SIZE: f32 = 32.0;
+const MARGIN: f32 = 10.0;
+const FONT_SIZE: f32 = 24.0;
+const LABEL_WIDTH: f32 = 120.0;
+
+// Seconds a value bounces for after it changes, and how much bigger it gets
+const POP_TIME: f32 = 0.25;
+const POP_SCALE: f32 = 0.5;
+
+// A HUD element that bounces when its value changes
+struct HudElement {
+    entity: Entity,
+    width: f32,
+    height: f32,
+    pop: f32,
+}
+
+impl HudElement {
+    fn new(entity: Entity, width: f32, height: f32) -> HudElement {
+        HudElement {
+            entity,
+            width,
+            height,
+            pop: 0.0,
+        }
+    }
+}
+
+// Hearts, veggies and time for the level being played. Only follows the player, so the
+// values are re-read every frame and the HUD reacts to whatever changed them.
+pub struct Hud {
+    hearts: Vec<HudElement>,
+    veggie_icon: Entity,
+    veggies: HudElement,
+    timer: HudElement,
+    shown_health: Option<u32>,
+    shown_veggies: Option<u32>,
+    // Seconds spent playing the level
+    pub elapsed: f32,
+}
+
+impl Hud {
+    pub fn entities(&self) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = self.hearts.iter().map(|heart| heart.entity).collect();
+        entities.push(self.veggie_icon);
+        entities.push(self.veggies.entity);
+        entities.push(self.timer.entity);
+        entities
+    }
+
+    pub fn set_hidden(&self, world: &World, hidden: bool) {
+        let mut storage = world.write_storage::<HiddenPropagate>();
+        for entity in self.entities() {
+            match hidden {
+                true => {
+                    storage
+                        .insert(entity, HiddenPropagate::new())
+                        .expect("Failed to hide HUD");
+                }
+                false => {
+                    storage.remove(entity);
+                }
+            }
+        }
+    }
+}
+
+fn create_ui_transform(id: String, x: f32, y: f32, width: f32, height: f32) -> UiTransform {
+    UiTransform::new(
+        id,
+        Anchor::TopLeft,
+        Anchor::TopLeft,
+        x,
+        y,
+        1.,
+        width,
+        height,
+    )
+}
+
+fn create_icon(world: &mut World, id: String, x: f32, y: f32, sprite_number: usize) -> Entity {
+    let icon = get_sprite_sheet(world, SPRITE_SHEET_UI).map(|sprite_sheet| {
+        UiImage::Sprite(SpriteRender {
+            sprite_sheet,
+            sprite_number,
+        })
+    });
+    world
+        .create_entity()
+        .with(create_ui_transform(id, x, y, ICON_SIZE, ICON_SIZE))
+        .maybe_with(icon)
+        .build()
+}
+
+fn create_label(world: &mut World, id: String, x: f32, y: f32, color: [f32; 4]) -> Entity {
+    let font = (*world.read_resource::<Handle<FontAsset>>()).clone();
+    let mut text = UiText::new(font, String::new(), color, FONT_SIZE);
+    text.align = Anchor::MiddleLeft;
+    world
+        .create_entity()
+        .with(create_ui_transform(id, x, y, LABEL_WIDTH, ICON_SIZE))
+        .with(text)
+        .build()
+}
+
+// Top left corner, below the FPS counter: hearts, then veggies, then the timer
+pub fn initialize_hud(world: &mut World) {
+    if let Some(hud) = world.remove::<Hud>() {
+        world
+            .delete_entities(hud.entities().as_slice())
+            .expect("Failed to delete old HUD");
+    }
+
+    let row = |i: usize| -(MARGIN + 50.0 + (ICON_SIZE + MARGIN) * i as f32);
+    let mut hearts = Vec::new();
+    for i in 0..MAX_HEARTS {
+        let x = MARGIN + (ICON_SIZE + MARGIN / 2.0) * i as f32;
+        let entity = create_icon(
+            world,
+            format!("hud_heart{}", i),
+            x,
+            row(0),
+            SPRITE_HEART_FULL,
+        );
+        hearts.push(HudElement::new(entity, ICON_SIZE, ICON_SIZE));
+    }
+    let veggie_icon = create_icon(
+        world,
+        String::from("hud_veggie_icon"),
+        MARGIN,
+        row(1),
+        SPRITE_VEGGIE,
+    );
+    let veggies = create_label(
+        world,
+        String::from("hud_veggies"),
+        MARGIN * 2.0 + ICON_SIZE,
+        row(1),
+        COLOR_GOLD,
+    );
+    let timer = create_label(
+        world,
+        String::from("hud_timer"),
+        MARGIN,
+        row(2),
+        COLOR_WHITE,
+    );
+
+    world.insert(Hud {
+        hearts,
+        veggie_icon,
+        veggies: HudElement::new(veggies, LABEL_WIDTH, ICON_SIZE),
+        timer: HudElement::new(timer, LABEL_WIDTH, ICON_SIZE),
+        shown_health: None,
+        shown_veggies: None,
+        elapsed: 0.0,
+    });
+}
+
+fn format_time(seconds: f32) -> String {
+    let seconds = seconds.max(0.0);
+    let minutes = (seconds / 60.0) as u32;
+    format!("{}:{:05.2}", minutes, seconds % 60.0)
+}
+
+#[derive(SystemDesc)]
+pub struct HudSystem;
+
+impl<'s> System<'s> for HudSystem {
+    type SystemData = (
+        Option<Write<'s, Hud>>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Health>,
+        ReadStorage<'s, PicksThingsUp>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiImage>,
+        WriteStorage<'s, UiText>,
+        WriteStorage<'s, HiddenPropagate>,
+        Read<'s, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            hud,
+            players,
+            healths,
+            pickers,
+            mut transforms,
+            mut images,
+            mut texts,
+            mut hiddens,
+            time,
+        ): Self::SystemData,
+    ) {
+        let mut hud = match hud {
+            Some(hud) => hud,
+            None => return,
+        };
+        let hud = &mut *hud;
+
+        let mut player = None;
+        for (p, health, picker) in (&players, &healths, (&pickers).maybe()).join() {
+            if p.0 {
+                player = Some((
+                    health.current,
+                    picker.map_or(0, |picker| picker.amount_gathered),
+                ));
+            }
+        }
+        let (health, veggies) = match player {
+            Some(player) => player,
+            None => return,
+        };
+
+        if health > 0 {
+            hud.elapsed += time.delta_seconds();
+        }
+
+        if hud.shown_health != Some(health) {
+            let previous = hud.shown_health.unwrap_or(health) as usize;
+            let shown = (health as usize).max(MIN_HEARTS).min(MAX_HEARTS);
+            for (i, heart) in hud.hearts.iter_mut().enumerate() {
+                // Bounce every heart that was gained or lost
+                let changed = (i < previous) != (i < health as usize);
+                if changed && hud.shown_health.is_some() {
+                    heart.pop = POP_TIME;
+                }
+                if let Some(UiImage::Sprite(sprite)) = images.get_mut(heart.entity) {
+                    sprite.sprite_number = match i < health as usize {
+                        true => SPRITE_HEART_FULL,
+                        false => SPRITE_HEART_EMPTY,
+                    };
+                }
+                match i < shown {
+                    true => {
+                        hiddens.remove(heart.entity);
+                    }
+                    false => {
+                        hiddens
+                            .insert(heart.entity, HiddenPropagate::new())
+                            .expect("Failed to hide HUD heart");
+                    }
+                }
+            }
+            hud.shown_health = Some(health);
+        }
+
+        if hud.shown_veggies != Some(veggies) {
+            if hud.shown_veggies.is_some() {
+                hud.veggies.pop = POP_TIME;
+            }
+            if let Some(text) = texts.get_mut(hud.veggies.entity) {
+                text.text = format!("x {}", veggies);
+            }
+            hud.shown_veggies = Some(veggies);
+        }
+
+        let elapsed = format_time(hud.elapsed);
+        if let Some(text) = texts.get_mut(hud.timer.entity) {
+            text.text = elapsed;
+        }
+
+        let delta = time.delta_real_seconds();
+        for element in hud
+            .hearts
+            .iter_mut()
+            .chain(std::iter::once(&mut hud.veggies))
+        {
+            element.pop = (element.pop - delta).max(0.0);
+            let scale = 1.0 + POP_SCALE * element.pop / POP_TIME;
+            if let Some(transform) = transforms.get_mut(element.entity) {
+                transform.width = element.width * scale;
+                transform.height = element.height * scale;
+            }
+        }
+    }
+}
@@ -7,7 +7,9 @@ use amethyst::{
 use derivative::Derivative;
 
 pub mod current_actions;
+pub mod dialogue;
 pub mod file_picker;
+pub mod hud;
 pub mod tile_characteristics;
 pub mod ui_builder;
 
@@ -77,6 +79,27 @@ impl UiStack {
             }
         }
     }
+    // Updates every component and removes the ones that are done
+    pub fn update(world: &mut World) {
+        let mut index = 0;
+        let mut remove_indices = Vec::new();
+        for ui in &mut world.write_resource::<UiStack>().stack {
+            ui.update(world);
+            if ui.should_destroy() {
+                remove_indices.insert(0, index);
+            }
+            index += 1;
+        }
+        let mut entities = Vec::new();
+        for index in remove_indices {
+            entities = world.read_resource::<UiStack>().stack[index].entities_to_remove(entities);
+            world.write_resource::<UiStack>().stack.remove(index);
+        }
+        world
+            .delete_entities(entities.as_slice())
+            .expect("Failed to delete ui entities.");
+    }
+
     pub fn is_blocking_all_input(&self) -> bool {
         for ui in &self.stack {
             if ui.blocks_all_other_input() {
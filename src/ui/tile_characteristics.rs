@@ -1,13 +1,15 @@
 use crate::components::game::{AnimatedTile, SpriteRenderData};
-use crate::components::graphics::SpriteSheetType;
 use crate::{
     components::{
         editor::{CursorState, EditorCursor, InsertionGameObject},
         game::SerializedObjectType,
         physics::Position,
     },
-    states::pizzatopia::TILE_HEIGHT,
-    systems::editor::EDITOR_MODIFIERS_UI,
+    states::{
+        loading::{get_sprite_sheet, SpriteSheetManifest},
+        pizzatopia::TILE_HEIGHT,
+    },
+    systems::{editor::EDITOR_MODIFIERS_UI, physics::CollisionDirection},
     ui::{with_transparent, UiComponent, COLOR_BLACK},
 };
 use amethyst::{
@@ -292,7 +294,7 @@ impl EditorFieldUiComponents {
     }
 
     fn update_ui_text_object_specific_properties(&mut self, world: &World) {
-        let insertion = *world.read_resource::<InsertionGameObject>().clone();
+        let insertion = (*world.read_resource::<InsertionGameObject>()).clone();
         let mut ui_text_storage = world.write_storage::<UiText>();
         self.show_components(world, 0, 9);
         let mut counter = 4;
@@ -300,6 +302,13 @@ impl EditorFieldUiComponents {
         // Object-specific properties
         match insertion.0.object_type {
             SerializedObjectType::StaticTile { animation } => {
+                if let Some(text) = ui_text_storage.get_mut(self.labels[counter]) {
+                    text.text = match &insertion.0.tile_group {
+                        Some(group) => format!("Tile group: {}", group),
+                        None => String::from("Tile group: None"),
+                    };
+                    counter += 1;
+                }
                 if let Some(text) = ui_text_storage.get_mut(self.labels[counter]) {
                     text.text = format!("Animated: {}", animation.is_some());
                     counter += 1;
@@ -322,6 +331,35 @@ impl EditorFieldUiComponents {
                     counter += 1;
                 }
             }
+            SerializedObjectType::Door { link, open } => {
+                if let Some(text) = ui_text_storage.get_mut(self.labels[counter]) {
+                    text.text = match link {
+                        Some(link) => format!("Link: {}", link),
+                        None => String::from("Link: None"),
+                    };
+                    counter += 1;
+                }
+                if let Some(text) = ui_text_storage.get_mut(self.labels[counter]) {
+                    text.text = format!("Open: {}", open);
+                    counter += 1;
+                }
+            }
+            SerializedObjectType::Switch { link, plate } => {
+                if let Some(text) = ui_text_storage.get_mut(self.labels[counter]) {
+                    text.text = format!("Link: {}", link);
+                    counter += 1;
+                }
+                if let Some(text) = ui_text_storage.get_mut(self.labels[counter]) {
+                    text.text = format!("Pressure plate: {}", plate);
+                    counter += 1;
+                }
+            }
+            SerializedObjectType::GravityZone { gravity } => {
+                if let Some(text) = ui_text_storage.get_mut(self.labels[counter]) {
+                    text.text = format!("Gravity: {:?}", gravity);
+                    counter += 1;
+                }
+            }
         }
         self.hide_components(world, counter, 9);
         self.ui_index.index = self.ui_index.index.max(0).min(counter - 1);
@@ -341,12 +379,12 @@ impl EditorFieldUiComponents {
             }
         }
         if let Some(text) = ui_text_storage.get_mut(self.labels[2]) {
-            if let Some(sprite) = insertion.0.sprite {
-                text.text = format!("Sprite sheet: {:?}", sprite.sheet);
+            if let Some(sprite) = &insertion.0.sprite {
+                text.text = format!("Sprite sheet: {}", sprite.sheet);
             }
         }
         if let Some(text) = ui_text_storage.get_mut(self.labels[3]) {
-            if let Some(sprite) = insertion.0.sprite {
+            if let Some(sprite) = &insertion.0.sprite {
                 text.text = format!("Sprite number: {}", sprite.number);
             }
         }
@@ -390,18 +428,59 @@ pub struct UiIndex {
 }
 
 fn sprite_max(world: &World, sprite: &SpriteRenderData) -> usize {
-    let sheet = {
-        let sprite_sheets = &world.read_resource::<BTreeMap<u8, Handle<SpriteSheet>>>();
-        let sheet = sprite_sheets.get(&(sprite.sheet as u8)).unwrap();
-        sheet.clone()
+    let sheets = &world.read_resource::<AssetStorage<SpriteSheet>>();
+    get_sprite_sheet(world, &sprite.sheet)
+        .and_then(|sheet| sheets.get(&sheet).map(|sheet| sheet.sprites.len()))
+        .unwrap_or(0)
+}
+
+// Sprite sheets are cycled in the order they are listed in the manifest
+fn cycle_sprite_sheet(world: &World, current: &str, forward: bool) -> String {
+    let manifest = world.read_resource::<SpriteSheetManifest>();
+    let ids: Vec<&String> = manifest
+        .sprite_sheets
+        .iter()
+        .map(|entry| &entry.id)
+        .collect();
+    if ids.is_empty() {
+        return String::from(current);
+    }
+    let index = ids
+        .iter()
+        .position(|id| id.as_str() == current)
+        .unwrap_or(0);
+    let next = match forward {
+        true => (index + 1) % ids.len(),
+        false => (index + ids.len() - 1) % ids.len(),
     };
+    ids[next].clone()
+}
 
-    let sheets = &world.read_resource::<AssetStorage<SpriteSheet>>();
-    if let Some(sheet) = sheets.get(&sheet) {
-        sheet.sprites.len()
-    } else {
-        0
+// Auto-tile groups of the sheet are cycled in name order, going through no group in between
+fn cycle_tile_group(
+    world: &World,
+    sheet: &str,
+    current: &Option<String>,
+    forward: bool,
+) -> Option<String> {
+    let manifest = world.read_resource::<SpriteSheetManifest>();
+    let mut groups: Vec<Option<String>> = vec![None];
+    if let Some(entry) = manifest
+        .sprite_sheets
+        .iter()
+        .find(|entry| entry.id == sheet)
+    {
+        groups.extend(entry.auto_tile.keys().cloned().map(Some));
     }
+    let index = groups
+        .iter()
+        .position(|group| group == current)
+        .unwrap_or(0);
+    let next = match forward {
+        true => (index + 1) % groups.len(),
+        false => (index + groups.len() - 1) % groups.len(),
+    };
+    groups[next].clone()
 }
 
 impl EditorFieldUiComponents {
@@ -440,9 +519,13 @@ impl EditorFieldUiComponents {
             2 => {
                 if let Some(ref mut sprite) = insertion_serialized_object.0.sprite {
                     sprite.sheet = match button_info.editor_button_type {
-                        EditorButtonType::Label => sprite.sheet,
-                        EditorButtonType::RightArrow => sprite.sheet.next(),
-                        EditorButtonType::LeftArrow => sprite.sheet.prev(),
+                        EditorButtonType::Label => sprite.sheet.clone(),
+                        EditorButtonType::RightArrow => {
+                            cycle_sprite_sheet(world, &sprite.sheet, true)
+                        }
+                        EditorButtonType::LeftArrow => {
+                            cycle_sprite_sheet(world, &sprite.sheet, false)
+                        }
                     };
                 }
             }
@@ -465,6 +548,8 @@ impl EditorFieldUiComponents {
             }
             _ => {}
         }
+        let tile_group = insertion_serialized_object.0.tile_group.clone();
+        let mut next_tile_group = tile_group.clone();
         match insertion_serialized_object.0.object_type {
             SerializedObjectType::StaticTile { ref mut animation } => {
                 match button_info.editor_button_type {
@@ -475,12 +560,17 @@ impl EditorFieldUiComponents {
                             _ => 1,
                         };
                         if button_info.id == START_ID {
+                            if let Some(sprite) = &sprite_render {
+                                next_tile_group =
+                                    cycle_tile_group(world, &sprite.sheet, &tile_group, sign > 0);
+                            }
+                        } else if button_info.id == START_ID + 1 {
                             if animation.is_some() {
                                 *animation = None;
                             } else {
                                 *animation = Some(AnimatedTile::default());
                             }
-                        } else if button_info.id == START_ID + 1 && animation.is_some() {
+                        } else if button_info.id == START_ID + 2 && animation.is_some() {
                             // NUM ANIMATION TILES
                             if let Some(ref mut anim) = *animation {
                                 let mut result = sign + anim.num_frames as i32;
@@ -491,7 +581,7 @@ impl EditorFieldUiComponents {
                                 }
                                 anim.num_frames = result as usize;
                             }
-                        } else if button_info.id == START_ID + 2 && animation.is_some() {
+                        } else if button_info.id == START_ID + 3 && animation.is_some() {
                             // ANIMATION LEN
                             if let Some(ref mut anim) = *animation {
                                 let mut result = anim.time_per_frame as f32;
@@ -512,6 +602,61 @@ impl EditorFieldUiComponents {
                     }
                 }
             }
+            SerializedObjectType::Door {
+                ref mut link,
+                ref mut open,
+            } => match button_info.editor_button_type {
+                EditorButtonType::Label => {}
+                // Going left from link 0 unlinks the door
+                EditorButtonType::LeftArrow if button_info.id == START_ID => {
+                    *link = link.and_then(|link| link.checked_sub(1));
+                }
+                EditorButtonType::RightArrow if button_info.id == START_ID => {
+                    *link = Some(link.map_or(0, |link| link + 1));
+                }
+                EditorButtonType::RightArrow | EditorButtonType::LeftArrow => {
+                    if button_info.id == START_ID + 1 {
+                        *open = !*open;
+                    }
+                }
+            },
+            SerializedObjectType::Switch {
+                ref mut link,
+                ref mut plate,
+            } => match button_info.editor_button_type {
+                EditorButtonType::Label => {}
+                EditorButtonType::LeftArrow if button_info.id == START_ID => {
+                    *link = link.saturating_sub(1);
+                }
+                EditorButtonType::RightArrow if button_info.id == START_ID => {
+                    *link += 1;
+                }
+                EditorButtonType::RightArrow | EditorButtonType::LeftArrow => {
+                    if button_info.id == START_ID + 1 {
+                        *plate = !*plate;
+                    }
+                }
+            },
+            SerializedObjectType::GravityZone { ref mut gravity } => {
+                if button_info.id == START_ID {
+                    // Goes round the sides in the order gravity turns
+                    let sides = [
+                        CollisionDirection::FromTop,
+                        CollisionDirection::FromLeft,
+                        CollisionDirection::FromBottom,
+                        CollisionDirection::FromRight,
+                    ];
+                    let index = sides.iter().position(|side| side == gravity).unwrap_or(0);
+                    *gravity = match button_info.editor_button_type {
+                        EditorButtonType::Label => *gravity,
+                        EditorButtonType::RightArrow => sides[(index + 1) % sides.len()],
+                        EditorButtonType::LeftArrow => {
+                            sides[(index + sides.len() - 1) % sides.len()]
+                        }
+                    };
+                }
+            }
         }
+        insertion_serialized_object.0.tile_group = next_tile_group;
     }
 }
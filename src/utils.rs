@@ -1,3 +1,4 @@
+use amethyst::ecs::{Builder, Component};
 use derivative::Derivative;
 use serde::{Deserialize, Serialize};
 use std::{cmp::Ordering, io};
@@ -132,3 +133,17 @@ pub fn read_line_from_console() -> String {
     }
     input
 }
+
+// Lets entity builders skip a component that may have failed to load, e.g. a missing sprite sheet
+pub trait MaybeWith {
+    fn maybe_with<C: Component + Send + Sync>(self, component: Option<C>) -> Self;
+}
+
+impl<B: Builder> MaybeWith for B {
+    fn maybe_with<C: Component + Send + Sync>(self, component: Option<C>) -> Self {
+        match component {
+            Some(component) => self.with(component),
+            None => self,
+        }
+    }
+}